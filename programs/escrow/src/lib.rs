@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use anchor_spl::associated_token::AssociatedToken;
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
 use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, Transfer};
 
 declare_id!("FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk");
@@ -8,12 +9,22 @@ declare_id!("FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk");
 pub mod escrow {
     use super::*;
 
-    pub fn create_offer(ctx: Context<CreateOffer>, offer_id: u64, amount_a: u64, amount_b: u64) -> Result<()> {
+    pub fn create_offer(
+        ctx: Context<CreateOffer>,
+        offer_id: u64,
+        amount_a: u64,
+        amount_b: u64,
+        client_ref: [u8; 32],
+    ) -> Result<()> {
         require!(amount_a > 0, EscrowError::InvalidAmount);
         require!(amount_b > 0, EscrowError::InvalidAmount);
+        require!(
+            ctx.accounts.maker_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
 
         let st = &mut ctx.accounts.escrow_state;
-        st.version = 1;
+        st.version = 2;
         st.status = EscrowStatus::Created as u8;
         st.offer_id = offer_id;
         st.maker = ctx.accounts.maker.key();
@@ -26,6 +37,7 @@ pub mod escrow {
         st.created_slot = Clock::get()?.slot;
         st.filled_slot = 0;
         st.cancelled_slot = 0;
+        st.client_ref = client_ref;
 
         // maker token A -> vault ATA
         token::transfer(
@@ -40,20 +52,30 @@ pub mod escrow {
             amount_a,
         )?;
 
+        // the vault must hold at least what the offer promises (fee-on-transfer / short deposits)
+        ctx.accounts.vault_ata.reload()?;
+        require!(
+            ctx.accounts.vault_ata.amount >= amount_a,
+            EscrowError::VaultShortfall
+        );
+
         // demo: stable JSON log line for off-chain parsing
         msg!(
-            r#"{{"event":"OfferCreated","offer_id":"{}","maker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{} }}"#,
+            r#"{{"event":"OfferCreated","offer_id":"{}","maker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{},"client_ref":"{}" }}"#,
             offer_id,
             st.maker,
             st.mint_a,
             amount_a,
             st.mint_b,
-            amount_b
+            amount_b,
+            hex32(&client_ref)
         );
 
         Ok(())
     }
 
+    /// Any vault balance above `amount_a` (tokens sent straight to the vault) goes back to
+    /// `maker_ata_a`, so a donation can never block the take.
     pub fn take_offer(ctx: Context<TakeOffer>) -> Result<()> {
         // read state immutably for CPI (avoid holding a mutable borrow across CPIs)
         let maker = ctx.accounts.escrow_state.maker;
@@ -63,12 +85,28 @@ pub mod escrow {
         let mint_b = ctx.accounts.escrow_state.mint_b;
         let amount_a = ctx.accounts.escrow_state.amount_a;
         let amount_b = ctx.accounts.escrow_state.amount_b;
+        let client_ref = ctx.accounts.escrow_state.client_ref;
 
         require!(
             ctx.accounts.escrow_state.status == EscrowStatus::Created as u8,
             EscrowError::InvalidStatus
         );
 
+        require!(
+            ctx.accounts.maker_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
+        require!(
+            ctx.accounts.taker_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
+
+        // reconcile against the real vault balance: anything above amount_a was donated
+        // after create and is swept back to the maker so the vault can be closed
+        let vault_amount = ctx.accounts.vault_ata.amount;
+        require!(vault_amount >= amount_a, EscrowError::VaultShortfall);
+        let surplus = vault_amount - amount_a;
+
         // basic mint sanity checks (also enforced by account constraints)
         require_keys_eq!(ctx.accounts.mint_a.key(), mint_a, EscrowError::InvalidMint);
         require_keys_eq!(ctx.accounts.mint_b.key(), mint_b, EscrowError::InvalidMint);
@@ -107,6 +145,21 @@ pub mod escrow {
             amount_a,
         )?;
 
+        if surplus > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault_ata.to_account_info(),
+                        to: ctx.accounts.maker_ata_a.to_account_info(),
+                        authority: ctx.accounts.escrow_state.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                surplus,
+            )?;
+        }
+
         // mutate state after CPIs
         {
             let st = &mut ctx.accounts.escrow_state;
@@ -116,14 +169,16 @@ pub mod escrow {
         }
 
         msg!(
-            r#"{{"event":"OfferFilled","offer_id":"{}","maker":"{}","taker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{} }}"#,
+            r#"{{"event":"OfferFilled","offer_id":"{}","maker":"{}","taker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{},"client_ref":"{}","vault_amount":{} }}"#,
             offer_id,
             maker,
             ctx.accounts.taker.key(),
             mint_a,
             amount_a,
             mint_b,
-            amount_b
+            amount_b,
+            hex32(&client_ref),
+            vault_amount
         );
 
         // optional: close vault ATA to maker (saves rent)
@@ -149,6 +204,7 @@ pub mod escrow {
         let mint_b = ctx.accounts.escrow_state.mint_b;
         let amount_a = ctx.accounts.escrow_state.amount_a;
         let amount_b = ctx.accounts.escrow_state.amount_b;
+        let client_ref = ctx.accounts.escrow_state.client_ref;
 
         require!(
             ctx.accounts.escrow_state.status == EscrowStatus::Created as u8,
@@ -163,19 +219,171 @@ pub mod escrow {
             &[escrow_bump],
         ];
 
-        // vault token A -> maker token A (PDA signs)
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault_ata.to_account_info(),
-                    to: ctx.accounts.maker_ata_a.to_account_info(),
-                    authority: ctx.accounts.escrow_state.to_account_info(),
-                },
-                &[signer_seeds],
-            ),
+        // vault token A -> maker token A (PDA signs); refund the real balance, which may
+        // differ from amount_a, so the vault is empty for close_account
+        let vault_amount = ctx.accounts.vault_ata.amount;
+        if vault_amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault_ata.to_account_info(),
+                        to: ctx.accounts.maker_ata_a.to_account_info(),
+                        authority: ctx.accounts.escrow_state.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                vault_amount,
+            )?;
+        }
+
+        // mutate state after CPI
+        {
+            let st = &mut ctx.accounts.escrow_state;
+            st.status = EscrowStatus::Cancelled as u8;
+            st.cancelled_slot = Clock::get()?.slot;
+        }
+
+        msg!(
+            r#"{{"event":"OfferCancelled","offer_id":"{}","maker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{},"client_ref":"{}","vault_amount":{} }}"#,
+            offer_id,
+            maker,
+            mint_a,
             amount_a,
-        )?;
+            mint_b,
+            amount_b,
+            hex32(&client_ref),
+            vault_amount
+        );
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault_ata.to_account_info(),
+                destination: ctx.accounts.maker.to_account_info(),
+                authority: ctx.accounts.escrow_state.to_account_info(),
+            },
+            &[signer_seeds],
+        ))?;
+
+        Ok(())
+    }
+
+    /// Grows a version-1 offer (created before `client_ref` existed) to the current layout so
+    /// `take_offer` / `cancel_offer` / `force_cancel` can load it again; `client_ref` reads as
+    /// zeros. Changes nothing else, so anyone may pay the extra rent.
+    pub fn migrate_offer(ctx: Context<MigrateOffer>) -> Result<()> {
+        let info = ctx.accounts.escrow_state.to_account_info();
+        {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() == EscrowState::V1_SPACE,
+                EscrowError::NotMigratable
+            );
+            require!(
+                data[..8] == EscrowState::DISCRIMINATOR,
+                EscrowError::NotMigratable
+            );
+            require!(data[8] == 1, EscrowError::NotMigratable);
+        }
+
+        let rent = Rent::get()?.minimum_balance(EscrowState::SPACE);
+        let top_up = rent.saturating_sub(info.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+
+        // appended bytes are zeroed: client_ref = [0; 32]
+        info.realloc(EscrowState::SPACE, true)?;
+        info.try_borrow_mut_data()?[8] = 2;
+        Ok(())
+    }
+
+    pub fn init_config(ctx: Context<InitConfig>) -> Result<()> {
+        let cfg = &mut ctx.accounts.config;
+        cfg.admin = ctx.accounts.admin.key();
+        cfg.bump = ctx.bumps.config;
+        Ok(())
+    }
+
+    pub fn set_admin(ctx: Context<AdminOnly>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.config.admin = new_admin;
+        Ok(())
+    }
+
+    pub fn deny_wallet(ctx: Context<DenyWallet>, wallet: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.deny_entry;
+        entry.wallet = wallet;
+        entry.added_slot = Clock::get()?.slot;
+        entry.bump = ctx.bumps.deny_entry;
+
+        msg!(
+            r#"{{"event":"WalletDenied","wallet":"{}","admin":"{}"}}"#,
+            wallet,
+            ctx.accounts.admin.key()
+        );
+        Ok(())
+    }
+
+    pub fn allow_wallet(ctx: Context<AllowWallet>, wallet: Pubkey) -> Result<()> {
+        msg!(
+            r#"{{"event":"WalletAllowed","wallet":"{}","admin":"{}"}}"#,
+            wallet,
+            ctx.accounts.admin.key()
+        );
+        Ok(())
+    }
+
+    /// Admin-only cancel: returns the vault to the maker regardless of who signs.
+    pub fn force_cancel(ctx: Context<ForceCancel>) -> Result<()> {
+        // read state immutably for CPI (avoid holding a mutable borrow across CPIs)
+        let maker = ctx.accounts.escrow_state.maker;
+        let offer_id = ctx.accounts.escrow_state.offer_id;
+        let escrow_bump = ctx.accounts.escrow_state.escrow_bump;
+        let mint_a = ctx.accounts.escrow_state.mint_a;
+        let mint_b = ctx.accounts.escrow_state.mint_b;
+        let amount_a = ctx.accounts.escrow_state.amount_a;
+        let amount_b = ctx.accounts.escrow_state.amount_b;
+        let client_ref = ctx.accounts.escrow_state.client_ref;
+
+        require!(
+            ctx.accounts.escrow_state.status == EscrowStatus::Created as u8,
+            EscrowError::InvalidStatus
+        );
+
+        let signer_seeds: &[&[u8]] = &[
+            b"escrow",
+            maker.as_ref(),
+            &offer_id.to_le_bytes(),
+            &[escrow_bump],
+        ];
+
+        // vault token A -> maker token A (PDA signs); refund the real balance, which may
+        // differ from amount_a, so the vault is empty for close_account
+        let vault_amount = ctx.accounts.vault_ata.amount;
+        if vault_amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault_ata.to_account_info(),
+                        to: ctx.accounts.maker_ata_a.to_account_info(),
+                        authority: ctx.accounts.escrow_state.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                vault_amount,
+            )?;
+        }
 
         // mutate state after CPI
         {
@@ -185,13 +393,16 @@ pub mod escrow {
         }
 
         msg!(
-            r#"{{"event":"OfferCancelled","offer_id":"{}","maker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{} }}"#,
+            r#"{{"event":"OfferForceCancelled","offer_id":"{}","maker":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{},"client_ref":"{}","vault_amount":{},"admin":"{}" }}"#,
             offer_id,
             maker,
             mint_a,
             amount_a,
             mint_b,
-            amount_b
+            amount_b,
+            hex32(&client_ref),
+            vault_amount,
+            ctx.accounts.admin.key()
         );
 
         token::close_account(CpiContext::new_with_signer(
@@ -206,6 +417,425 @@ pub mod escrow {
 
         Ok(())
     }
+
+    // ---------------------------------------------------------------------
+    // RFQ: requester names mints + size, responders lock quotes, requester accepts one
+    // ---------------------------------------------------------------------
+
+    pub fn create_rfq(ctx: Context<CreateRfq>, rfq_id: u64, size: u64) -> Result<()> {
+        require!(size > 0, EscrowError::InvalidAmount);
+        require!(
+            ctx.accounts.requester_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
+
+        let rfq = &mut ctx.accounts.rfq;
+        rfq.version = 1;
+        rfq.status = RfqStatus::Open as u8;
+        rfq.bump = ctx.bumps.rfq;
+        rfq.rfq_id = rfq_id;
+        rfq.requester = ctx.accounts.requester.key();
+        rfq.mint_a = ctx.accounts.mint_a.key();
+        rfq.mint_b = ctx.accounts.mint_b.key();
+        rfq.size = size;
+        rfq.open_quotes = 0;
+        rfq.accepted_quote = Pubkey::default();
+        rfq.created_slot = Clock::get()?.slot;
+        rfq.filled_slot = 0;
+
+        msg!(
+            r#"{{"event":"RfqCreated","rfq_id":"{}","requester":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}" }}"#,
+            rfq_id,
+            rfq.requester,
+            rfq.mint_a,
+            size,
+            rfq.mint_b
+        );
+
+        Ok(())
+    }
+
+    /// Responder locks `rfq.size` of token A and asks `amount_b` of token B for it.
+    pub fn submit_quote(ctx: Context<SubmitQuote>, amount_b: u64) -> Result<()> {
+        require!(amount_b > 0, EscrowError::InvalidAmount);
+        require!(
+            ctx.accounts.rfq.status == RfqStatus::Open as u8,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            ctx.accounts.responder_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
+
+        ctx.accounts.rfq.open_quotes += 1;
+        let rfq = &ctx.accounts.rfq;
+        let amount_a = rfq.size;
+
+        let quote = &mut ctx.accounts.quote;
+        quote.version = 1;
+        quote.status = QuoteStatus::Open as u8;
+        quote.bump = ctx.bumps.quote;
+        quote.rfq = rfq.key();
+        quote.responder = ctx.accounts.responder.key();
+        quote.amount_a = amount_a;
+        quote.amount_b = amount_b;
+        quote.created_slot = Clock::get()?.slot;
+
+        // responder token A -> quote vault
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.responder_ata_a.to_account_info(),
+                    to: ctx.accounts.quote_vault.to_account_info(),
+                    authority: ctx.accounts.responder.to_account_info(),
+                },
+            ),
+            amount_a,
+        )?;
+
+        ctx.accounts.quote_vault.reload()?;
+        require!(
+            ctx.accounts.quote_vault.amount >= amount_a,
+            EscrowError::VaultShortfall
+        );
+
+        msg!(
+            r#"{{"event":"QuoteSubmitted","rfq_id":"{}","requester":"{}","responder":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{} }}"#,
+            rfq.rfq_id,
+            rfq.requester,
+            ctx.accounts.responder.key(),
+            rfq.mint_a,
+            amount_a,
+            rfq.mint_b,
+            amount_b
+        );
+
+        Ok(())
+    }
+
+    /// Settles the chosen quote. Losing quotes passed in `remaining_accounts` as
+    /// `[quote, quote_vault, responder, responder_ata_a]` groups are refunded in the
+    /// same transaction; any left over can be refunded later with `refund_quote`.
+    pub fn accept_quote<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AcceptQuote<'info>>,
+    ) -> Result<()> {
+        let rfq_key = ctx.accounts.rfq.key();
+        let rfq_id = ctx.accounts.rfq.rfq_id;
+        let requester = ctx.accounts.rfq.requester;
+        let mint_a = ctx.accounts.rfq.mint_a;
+        let mint_b = ctx.accounts.rfq.mint_b;
+        let responder = ctx.accounts.quote.responder;
+        let quote_bump = ctx.accounts.quote.bump;
+        let amount_a = ctx.accounts.quote.amount_a;
+        let amount_b = ctx.accounts.quote.amount_b;
+
+        require!(
+            ctx.accounts.rfq.status == RfqStatus::Open as u8,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            ctx.accounts.quote.status == QuoteStatus::Open as u8,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            ctx.accounts.requester_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
+        require!(
+            ctx.accounts.responder_deny.data_is_empty(),
+            EscrowError::Denylisted
+        );
+
+        let vault_amount = ctx.accounts.quote_vault.amount;
+        require!(vault_amount >= amount_a, EscrowError::VaultShortfall);
+        let surplus = vault_amount - amount_a;
+
+        // requester token B -> responder token B
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.requester_ata_b.to_account_info(),
+                    to: ctx.accounts.responder_ata_b.to_account_info(),
+                    authority: ctx.accounts.requester.to_account_info(),
+                },
+            ),
+            amount_b,
+        )?;
+
+        let signer_seeds: &[&[u8]] = &[
+            b"quote",
+            rfq_key.as_ref(),
+            responder.as_ref(),
+            &[quote_bump],
+        ];
+
+        // quote vault token A -> requester token A (quote PDA signs)
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.quote_vault.to_account_info(),
+                    to: ctx.accounts.requester_ata_a.to_account_info(),
+                    authority: ctx.accounts.quote.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            amount_a,
+        )?;
+
+        if surplus > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.quote_vault.to_account_info(),
+                        to: ctx.accounts.responder_ata_a.to_account_info(),
+                        authority: ctx.accounts.quote.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                surplus,
+            )?;
+        }
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.quote_vault.to_account_info(),
+                destination: ctx.accounts.responder.to_account_info(),
+                authority: ctx.accounts.quote.to_account_info(),
+            },
+            &[signer_seeds],
+        ))?;
+
+        // mutate state after CPIs
+        let slot = Clock::get()?.slot;
+        {
+            let quote = &mut ctx.accounts.quote;
+            quote.status = QuoteStatus::Accepted as u8;
+        }
+        {
+            let rfq = &mut ctx.accounts.rfq;
+            rfq.open_quotes -= 1;
+            rfq.status = RfqStatus::Filled as u8;
+            rfq.accepted_quote = ctx.accounts.quote.key();
+            rfq.filled_slot = slot;
+        }
+
+        msg!(
+            r#"{{"event":"QuoteAccepted","rfq_id":"{}","requester":"{}","responder":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{},"vault_amount":{} }}"#,
+            rfq_id,
+            requester,
+            responder,
+            mint_a,
+            amount_a,
+            mint_b,
+            amount_b,
+            vault_amount
+        );
+
+        let accepted_quote = ctx.accounts.quote.key();
+        refund_quote_groups(
+            &ctx.accounts.token_program,
+            rfq_key,
+            &mut ctx.accounts.rfq,
+            Some(accepted_quote),
+            ctx.remaining_accounts,
+        )
+    }
+
+    /// Closes an open RFQ and returns its rent to the requester. Quotes still open are
+    /// refunded from `remaining_accounts` groups as in `accept_quote`; any not passed (or
+    /// not withdrawn by their responders beforehand) make the cancel fail.
+    pub fn cancel_rfq<'info>(ctx: Context<'_, '_, 'info, 'info, CancelRfq<'info>>) -> Result<()> {
+        require!(
+            ctx.accounts.rfq.status == RfqStatus::Open as u8,
+            EscrowError::InvalidStatus
+        );
+
+        let rfq_key = ctx.accounts.rfq.key();
+        refund_quote_groups(
+            &ctx.accounts.token_program,
+            rfq_key,
+            &mut ctx.accounts.rfq,
+            None,
+            ctx.remaining_accounts,
+        )?;
+        let rfq = &ctx.accounts.rfq;
+        require!(rfq.open_quotes == 0, EscrowError::OpenQuotes);
+
+        msg!(
+            r#"{{"event":"RfqCancelled","rfq_id":"{}","requester":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}" }}"#,
+            rfq.rfq_id,
+            rfq.requester,
+            rfq.mint_a,
+            rfq.size,
+            rfq.mint_b
+        );
+
+        Ok(())
+    }
+
+    /// Returns a quote's locked token A to its responder. While the RFQ is open only the
+    /// responder may withdraw; once it is filled anyone may crank the refund.
+    pub fn refund_quote(ctx: Context<RefundQuote>) -> Result<()> {
+        require!(
+            ctx.accounts.quote.status == QuoteStatus::Open as u8,
+            EscrowError::InvalidStatus
+        );
+        if ctx.accounts.rfq.status == RfqStatus::Open as u8 {
+            require_keys_eq!(
+                ctx.accounts.caller.key(),
+                ctx.accounts.quote.responder,
+                EscrowError::Unauthorized
+            );
+        }
+
+        refund_quote_vault(
+            &ctx.accounts.token_program,
+            &mut ctx.accounts.rfq,
+            &mut ctx.accounts.quote,
+            &ctx.accounts.quote_vault,
+            ctx.accounts.responder_ata_a.to_account_info(),
+            ctx.accounts.responder.to_account_info(),
+        )
+    }
+}
+
+/// Refunds the quotes passed as `[quote, quote_vault, responder, responder_ata_a]` groups.
+/// Quotes of `rfq` that are no longer open are skipped, so a group may be stale.
+fn refund_quote_groups<'info>(
+    token_program: &Program<'info, Token>,
+    rfq_key: Pubkey,
+    rfq: &mut Rfq,
+    accepted_quote: Option<Pubkey>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let groups = remaining_accounts.chunks_exact(4);
+    require!(
+        groups.remainder().is_empty(),
+        EscrowError::InvalidRemainingAccounts
+    );
+    for group in groups {
+        if let Some(accepted) = accepted_quote {
+            require_keys_neq!(
+                group[0].key(),
+                accepted,
+                EscrowError::InvalidRemainingAccounts
+            );
+        }
+        let mut quote: Account<'info, Quote> = Account::try_from(&group[0])?;
+        require_keys_eq!(quote.rfq, rfq_key, EscrowError::InvalidRemainingAccounts);
+        if quote.status != QuoteStatus::Open as u8 {
+            continue;
+        }
+
+        let vault: Account<'info, TokenAccount> = Account::try_from(&group[1])?;
+        let responder_info = &group[2];
+        let responder_ata_a: Account<'info, TokenAccount> = Account::try_from(&group[3])?;
+
+        require_keys_eq!(
+            quote.responder,
+            responder_info.key(),
+            EscrowError::InvalidRemainingAccounts
+        );
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address(&quote.key(), &rfq.mint_a),
+            EscrowError::InvalidRemainingAccounts
+        );
+        require_keys_eq!(
+            responder_ata_a.key(),
+            get_associated_token_address(&quote.responder, &rfq.mint_a),
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        refund_quote_vault(
+            token_program,
+            rfq,
+            &mut quote,
+            &vault,
+            responder_ata_a.to_account_info(),
+            responder_info.clone(),
+        )?;
+        quote.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+/// Empties a quote vault back to the responder, closes it and marks the quote refunded.
+fn refund_quote_vault<'info>(
+    token_program: &Program<'info, Token>,
+    rfq: &mut Rfq,
+    quote: &mut Account<'info, Quote>,
+    vault: &Account<'info, TokenAccount>,
+    responder_ata_a: AccountInfo<'info>,
+    responder: AccountInfo<'info>,
+) -> Result<()> {
+    let rfq_key = quote.rfq;
+    let responder_key = quote.responder;
+    let signer_seeds: &[&[u8]] = &[
+        b"quote",
+        rfq_key.as_ref(),
+        responder_key.as_ref(),
+        &[quote.bump],
+    ];
+
+    let vault_amount = vault.amount;
+    if vault_amount > 0 {
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                Transfer {
+                    from: vault.to_account_info(),
+                    to: responder_ata_a,
+                    authority: quote.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            vault_amount,
+        )?;
+    }
+
+    token::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: vault.to_account_info(),
+            destination: responder,
+            authority: quote.to_account_info(),
+        },
+        &[signer_seeds],
+    ))?;
+
+    quote.status = QuoteStatus::Refunded as u8;
+    rfq.open_quotes -= 1;
+
+    msg!(
+        r#"{{"event":"QuoteRefunded","rfq_id":"{}","requester":"{}","responder":"{}","mint_a":"{}","amount_a":{},"mint_b":"{}","amount_b":{},"vault_amount":{} }}"#,
+        rfq.rfq_id,
+        rfq.requester,
+        responder_key,
+        rfq.mint_a,
+        quote.amount_a,
+        rfq.mint_b,
+        quote.amount_b,
+        vault_amount
+    );
+
+    Ok(())
+}
+
+/// Lowercase hex for fixed-size byte fields in the JSON log lines.
+fn hex32(bytes: &[u8; 32]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(64);
+    for b in bytes {
+        out.push(HEX[(b >> 4) as usize] as char);
+        out.push(HEX[(b & 0x0f) as usize] as char);
+    }
+    out
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
@@ -232,10 +862,95 @@ pub struct EscrowState {
     pub created_slot: u64,
     pub filled_slot: u64,
     pub cancelled_slot: u64,
+
+    /// Opaque caller-supplied reference (e.g. OMS order id), zero-padded.
+    pub client_ref: [u8; 32],
 }
 
 impl EscrowState {
-    pub const SPACE: usize = 8 /*disc*/ + 1 + 1 + 1 + 5 + 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8;
+    pub const SPACE: usize =
+        8 /*disc*/ + 1 + 1 + 1 + 5 + 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 32;
+    /// Version 1 (no `client_ref`); such accounts must go through `migrate_offer`.
+    pub const V1_SPACE: usize = Self::SPACE - 32;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RfqStatus {
+    Open = 0,
+    Filled = 1,
+}
+
+/// Request-for-quote: the requester wants `size` of `mint_a` and pays in `mint_b`.
+#[account]
+pub struct Rfq {
+    pub version: u8,
+    pub status: u8,
+    pub bump: u8,
+    /// quotes still holding tokens; the RFQ can only be cancelled once this is zero
+    pub open_quotes: u32,
+    pub _pad: [u8; 1],
+
+    pub rfq_id: u64,
+    pub requester: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub size: u64,
+    pub accepted_quote: Pubkey,
+    pub created_slot: u64,
+    pub filled_slot: u64,
+}
+
+impl Rfq {
+    pub const SPACE: usize = 8 /*disc*/ + 1 + 1 + 1 + 4 + 1 + 8 + 32 + 32 + 32 + 8 + 32 + 8 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStatus {
+    Open = 0,
+    Accepted = 1,
+    Refunded = 2,
+}
+
+/// A responder's locked quote; PDA at `[b"quote", rfq, responder]`, vault = ATA(mint_a, quote).
+#[account]
+pub struct Quote {
+    pub version: u8,
+    pub status: u8,
+    pub bump: u8,
+    pub _pad: [u8; 5],
+
+    pub rfq: Pubkey,
+    pub responder: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub created_slot: u64,
+}
+
+impl Quote {
+    pub const SPACE: usize = 8 /*disc*/ + 1 + 1 + 1 + 5 + 32 + 32 + 8 + 8 + 8;
+}
+
+/// Program-wide settings; singleton PDA at `[b"config"]`.
+#[account]
+pub struct Config {
+    pub admin: Pubkey,
+    pub bump: u8,
+}
+
+impl Config {
+    pub const SPACE: usize = 8 /*disc*/ + 32 + 1;
+}
+
+/// Existence of this PDA at `[b"deny", wallet]` blocks the wallet from trading.
+#[account]
+pub struct DenylistEntry {
+    pub wallet: Pubkey,
+    pub added_slot: u64,
+    pub bump: u8,
+}
+
+impl DenylistEntry {
+    pub const SPACE: usize = 8 /*disc*/ + 32 + 8 + 1;
 }
 
 #[derive(Accounts)]
@@ -271,6 +986,10 @@ pub struct CreateOffer<'info> {
     )]
     pub maker_ata_a: Account<'info, TokenAccount>,
 
+    /// CHECK: denylist PDA for the maker; must be uninitialized
+    #[account(seeds = [b"deny", maker.key().as_ref()], bump)]
+    pub maker_deny: UncheckedAccount<'info>,
+
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
@@ -324,6 +1043,22 @@ pub struct TakeOffer<'info> {
     )]
     pub maker_ata_b: Account<'info, TokenAccount>,
 
+    /// receives any vault surplus above amount_a
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker
+    )]
+    pub maker_ata_a: Account<'info, TokenAccount>,
+
+    /// CHECK: denylist PDA for the maker; must be uninitialized
+    #[account(seeds = [b"deny", maker.key().as_ref()], bump)]
+    pub maker_deny: UncheckedAccount<'info>,
+
+    /// CHECK: denylist PDA for the taker; must be uninitialized
+    #[account(seeds = [b"deny", taker.key().as_ref()], bump)]
+    pub taker_deny: UncheckedAccount<'info>,
+
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
@@ -360,6 +1095,333 @@ pub struct CancelOffer<'info> {
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateOffer<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: a version-1 `EscrowState`, which `Account` cannot deserialize; the handler
+    /// checks size, discriminator and version
+    #[account(mut, owner = crate::ID)]
+    pub escrow_state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(init, payer = admin, space = Config::SPACE, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+
+    /// only the upgrade authority may claim the admin role (prevents init front-running)
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Escrow>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ EscrowError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminOnly<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump, has_one = admin @ EscrowError::Unauthorized)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct DenyWallet<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ EscrowError::Unauthorized)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = DenylistEntry::SPACE,
+        seeds = [b"deny", wallet.as_ref()],
+        bump
+    )]
+    pub deny_entry: Account<'info, DenylistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AllowWallet<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ EscrowError::Unauthorized)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"deny", wallet.as_ref()],
+        bump = deny_entry.bump
+    )]
+    pub deny_entry: Account<'info, DenylistEntry>,
+}
+
+#[derive(Accounts)]
+pub struct ForceCancel<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ EscrowError::Unauthorized)]
+    pub config: Account<'info, Config>,
+
+    pub mint_a: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow_state.maker.as_ref(), &escrow_state.offer_id.to_le_bytes()],
+        bump = escrow_state.escrow_bump
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    /// maker receives the refund + vault rent
+    #[account(mut, address = escrow_state.maker)]
+    pub maker: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow_state
+    )]
+    pub vault_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker
+    )]
+    pub maker_ata_a: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(rfq_id: u64)]
+pub struct CreateRfq<'info> {
+    #[account(mut)]
+    pub requester: Signer<'info>,
+
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = requester,
+        space = Rfq::SPACE,
+        seeds = [b"rfq", requester.key().as_ref(), &rfq_id.to_le_bytes()],
+        bump
+    )]
+    pub rfq: Account<'info, Rfq>,
+
+    /// CHECK: denylist PDA for the requester; must be uninitialized
+    #[account(seeds = [b"deny", requester.key().as_ref()], bump)]
+    pub requester_deny: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitQuote<'info> {
+    #[account(mut)]
+    pub responder: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"rfq", rfq.requester.as_ref(), &rfq.rfq_id.to_le_bytes()],
+        bump = rfq.bump
+    )]
+    pub rfq: Account<'info, Rfq>,
+
+    #[account(address = rfq.mint_a @ EscrowError::InvalidMint)]
+    pub mint_a: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = responder,
+        space = Quote::SPACE,
+        seeds = [b"quote", rfq.key().as_ref(), responder.key().as_ref()],
+        bump
+    )]
+    pub quote: Account<'info, Quote>,
+
+    #[account(
+        init_if_needed,
+        payer = responder,
+        associated_token::mint = mint_a,
+        associated_token::authority = quote
+    )]
+    pub quote_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = responder
+    )]
+    pub responder_ata_a: Account<'info, TokenAccount>,
+
+    /// CHECK: denylist PDA for the responder; must be uninitialized
+    #[account(seeds = [b"deny", responder.key().as_ref()], bump)]
+    pub responder_deny: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptQuote<'info> {
+    #[account(mut)]
+    pub requester: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"rfq", rfq.requester.as_ref(), &rfq.rfq_id.to_le_bytes()],
+        bump = rfq.bump,
+        has_one = requester @ EscrowError::Unauthorized,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = mint_b @ EscrowError::InvalidMint
+    )]
+    pub rfq: Account<'info, Rfq>,
+
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"quote", rfq.key().as_ref(), quote.responder.as_ref()],
+        bump = quote.bump
+    )]
+    pub quote: Account<'info, Quote>,
+
+    /// responder is used as token-b receiver + vault close destination
+    #[account(mut, address = quote.responder)]
+    pub responder: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = quote
+    )]
+    pub quote_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = requester
+    )]
+    pub requester_ata_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = requester
+    )]
+    pub requester_ata_b: Account<'info, TokenAccount>,
+
+    /// receives any vault surplus above the quoted amount
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = responder
+    )]
+    pub responder_ata_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = responder
+    )]
+    pub responder_ata_b: Account<'info, TokenAccount>,
+
+    /// CHECK: denylist PDA for the requester; must be uninitialized
+    #[account(seeds = [b"deny", requester.key().as_ref()], bump)]
+    pub requester_deny: UncheckedAccount<'info>,
+
+    /// CHECK: denylist PDA for the responder; must be uninitialized
+    #[account(seeds = [b"deny", responder.key().as_ref()], bump)]
+    pub responder_deny: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRfq<'info> {
+    #[account(mut)]
+    pub requester: Signer<'info>,
+
+    #[account(
+        mut,
+        close = requester,
+        seeds = [b"rfq", rfq.requester.as_ref(), &rfq.rfq_id.to_le_bytes()],
+        bump = rfq.bump,
+        has_one = requester @ EscrowError::Unauthorized
+    )]
+    pub rfq: Account<'info, Rfq>,
+
+    pub token_program: Program<'info, Token>,
+    // remaining_accounts: open quotes to refund, see `cancel_rfq`
+}
+
+#[derive(Accounts)]
+pub struct RefundQuote<'info> {
+    pub caller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"rfq", rfq.requester.as_ref(), &rfq.rfq_id.to_le_bytes()],
+        bump = rfq.bump,
+        has_one = mint_a @ EscrowError::InvalidMint
+    )]
+    pub rfq: Account<'info, Rfq>,
+
+    pub mint_a: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"quote", rfq.key().as_ref(), quote.responder.as_ref()],
+        bump = quote.bump
+    )]
+    pub quote: Account<'info, Quote>,
+
+    /// responder receives the refund + vault rent
+    #[account(mut, address = quote.responder)]
+    pub responder: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = quote
+    )]
+    pub quote_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = responder
+    )]
+    pub responder_ata_a: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
 #[error_code]
 pub enum EscrowError {
     #[msg("invalid amount")]
@@ -370,5 +1432,14 @@ pub enum EscrowError {
     Unauthorized,
     #[msg("invalid mint")]
     InvalidMint,
+    #[msg("wallet is denylisted")]
+    Denylisted,
+    #[msg("vault holds less than the offered amount")]
+    VaultShortfall,
+    #[msg("invalid remaining accounts")]
+    InvalidRemainingAccounts,
+    #[msg("account is not a version-1 offer")]
+    NotMigratable,
+    #[msg("rfq still has open quotes")]
+    OpenQuotes,
 }
-
@@ -0,0 +1,301 @@
+//! Versioned wire format of `escrow.events.v1`.
+//!
+//! Version 1 is a bare [`NormalizedEvent`]. Version 2 wraps it as
+//! `{"schema_version": 2, "event_type": "...", "data": {...}}` so a consumer can route or
+//! skip a record before interpreting `data`. Versions 1 and 2 carry camelCase field names;
+//! version 3 switches `data` to the snake_case names of the documented contract.
+//! Compatible changes (new optional fields, new event types) keep the version: consumers
+//! ignore unknown fields and see new types as [`EventType::Unknown`]. Anything else bumps
+//! it; since v2 every consumer skips versions newer than it knows, so a bump that keeps
+//! `schema_version`/`event_type` in place can share the topic, and any other goes to a new one.
+
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{EventType, NormalizedEvent};
+
+/// Newest version this build reads and the one producers write by default.
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Envelope<T> {
+    pub schema_version: u32,
+    /// same as `data.event_type`
+    pub event_type: EventType,
+    pub data: T,
+}
+
+/// A record from the events topic.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Decoded {
+    Event(NormalizedEvent),
+    /// written by a producer newer than this build; `data` was not read
+    Unsupported {
+        schema_version: u32,
+        event_type: EventType,
+    },
+}
+
+/// Serializes `ev` as `schema_version` (1 to 3).
+pub fn encode_event(ev: &NormalizedEvent, schema_version: u32) -> Result<String> {
+    let data = serde_json::to_value(ev).context("serialize event")?;
+    let data = match schema_version {
+        1 | 2 => camel_case_keys(data),
+        3 => data,
+        v => bail!("unknown event schema version {v}"),
+    };
+    if schema_version == 1 {
+        return Ok(data.to_string());
+    }
+    serde_json::to_string(&Envelope {
+        schema_version,
+        event_type: ev.event_type.clone(),
+        data,
+    })
+    .context("serialize event envelope")
+}
+
+/// The field names consumers built before v3 expect.
+fn camel_case_keys(data: Value) -> Value {
+    let Value::Object(fields) = data else {
+        return data;
+    };
+    let fields = fields.into_iter().map(|(name, v)| {
+        let mut camel = String::with_capacity(name.len());
+        let mut upper = false;
+        for c in name.chars() {
+            match c {
+                '_' => upper = true,
+                c if upper => {
+                    camel.push(c.to_ascii_uppercase());
+                    upper = false;
+                }
+                c => camel.push(c),
+            }
+        }
+        (camel, v)
+    });
+    Value::Object(fields.collect())
+}
+
+/// Reads any version up to [`SCHEMA_VERSION`].
+pub fn decode_event(payload: &str) -> Result<Decoded> {
+    #[derive(Deserialize)]
+    struct Header {
+        schema_version: Option<u32>,
+        event_type: Option<EventType>,
+    }
+
+    let header: Header = serde_json::from_str(payload).context("event json")?;
+    let Some(schema_version) = header.schema_version else {
+        let ev = serde_json::from_str(payload).context("v1 event")?;
+        return Ok(Decoded::Event(ev));
+    };
+    if schema_version > SCHEMA_VERSION {
+        let event_type = header
+            .event_type
+            .unwrap_or_else(|| EventType::Unknown(String::new()));
+        return Ok(Decoded::Unsupported {
+            schema_version,
+            event_type,
+        });
+    }
+    let envelope: Envelope<NormalizedEvent> =
+        serde_json::from_str(payload).with_context(|| format!("v{schema_version} event"))?;
+    if envelope.event_type != envelope.data.event_type {
+        bail!(
+            "envelope event_type {} does not match data {}",
+            envelope.event_type.as_str(),
+            envelope.data.event_type.as_str()
+        );
+    }
+    Ok(Decoded::Event(envelope.data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Every payload shape the events topic has carried, oldest first. Fixtures are never
+    /// edited; a new shape gets a new file. (`v4-01-future-layout` stands for a version
+    /// newer than this build and moves up when the version does.)
+    fn fixtures() -> Vec<(String, String)> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/events");
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect();
+        files.sort();
+        files
+            .into_iter()
+            .map(|p| {
+                let name = p.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read_to_string(&p).unwrap())
+            })
+            .collect()
+    }
+
+    /// `NormalizedEvent` as v1 consumers were built against it (before the envelope), frozen
+    /// here so changes to the current type cannot loosen what they accept.
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    #[allow(dead_code)] // only ever deserialized
+    struct V1Event {
+        event_id: String,
+        event_type: V1EventType,
+        cluster: String,
+        slot: u64,
+        signature: String,
+        program_id: String,
+        offer_id: String,
+        maker: String,
+        taker: Option<String>,
+        mint_a: String,
+        mint_b: String,
+        amount_a: String,
+        amount_b: String,
+        client_ref: Option<String>,
+        vault_amount: Option<String>,
+        #[serde(default)]
+        block_time: Option<i64>,
+        #[serde(default)]
+        fee_lamports: Option<u64>,
+        #[serde(default)]
+        fee_payer: Option<String>,
+        #[serde(default)]
+        compute_units_consumed: Option<u64>,
+        #[serde(default)]
+        priority_fee_lamports: Option<u64>,
+        commitment: String,
+        ts_ingest_ms: u64,
+        #[serde(default)]
+        ref_event_id: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    enum V1EventType {
+        OfferCreated,
+        OfferFilled,
+        OfferCancelled,
+        OfferForceCancelled,
+        RfqCreated,
+        QuoteSubmitted,
+        QuoteAccepted,
+        QuoteRefunded,
+        EventFinalized,
+        EventRetracted,
+    }
+
+    /// How each consumer generation reads the topic: before the envelope, consumers
+    /// deserialized a bare `V1Event`; since, they go through `decode_event`.
+    fn read_v1(payload: &str) -> Result<V1Event> {
+        Ok(serde_json::from_str(payload)?)
+    }
+
+    fn read_current(payload: &str) -> Result<NormalizedEvent> {
+        match decode_event(payload)? {
+            Decoded::Event(ev) => Ok(ev),
+            Decoded::Unsupported { schema_version, .. } => bail!("unsupported v{schema_version}"),
+        }
+    }
+
+    #[test]
+    fn every_fixture_with_every_consumer() {
+        let fixtures = fixtures();
+        assert!(fixtures.len() >= 8, "fixtures missing: {fixtures:?}");
+        for (name, payload) in &fixtures {
+            let version: u32 = name[1..name.find('-').unwrap()].parse().unwrap();
+
+            // v1 consumers only ever saw v1 records; they must keep reading all of them
+            if version == 1 {
+                read_v1(payload).unwrap_or_else(|e| panic!("{name}: v1 consumer: {e:#}"));
+            }
+            match version {
+                v if v <= SCHEMA_VERSION => {
+                    let ev = read_current(payload).unwrap_or_else(|e| panic!("{name}: {e:#}"));
+                    // re-encoding at any version reads back the same event
+                    for v in 1..=SCHEMA_VERSION {
+                        let payload = encode_event(&ev, v).unwrap();
+                        if v == 1 && version == 1 {
+                            read_v1(&payload).unwrap_or_else(|e| {
+                                panic!("{name}: v1 consumer, re-encoded: {e:#}")
+                            });
+                        }
+                        let value: Value = serde_json::from_str(&payload).unwrap();
+                        let data = if v == 1 { &value } else { &value["data"] };
+                        // consumers built before v3 only know the camelCase names
+                        let snake = data.as_object().unwrap().keys().any(|k| k.contains('_'));
+                        assert_eq!(snake, v >= 3, "{name}: v{v} field names");
+                        let again = read_current(&payload).unwrap();
+                        assert_eq!(
+                            serde_json::to_value(&again).unwrap(),
+                            serde_json::to_value(&ev).unwrap(),
+                            "{name}: v{v} round trip"
+                        );
+                    }
+                }
+                _ => assert!(
+                    matches!(decode_event(payload).unwrap(), Decoded::Unsupported { .. }),
+                    "{name}: newer versions must be reported, not misread"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_event_types_survive() {
+        let (_, payload) = fixtures()
+            .into_iter()
+            .find(|(name, _)| name.contains("unknown-type"))
+            .unwrap();
+        let ev = read_current(&payload).unwrap();
+        assert_eq!(
+            ev.event_type,
+            EventType::Unknown("OfferExpired".to_string())
+        );
+        let value = serde_json::to_value(&ev).unwrap();
+        assert_eq!(value["event_type"], "OfferExpired");
+    }
+
+    #[test]
+    fn rfq_ids_get_their_own_key() {
+        let (_, payload) = fixtures()
+            .into_iter()
+            .find(|(name, _)| name.starts_with("v3-") && name.contains("offer-created"))
+            .unwrap();
+        let offer = read_current(&payload).unwrap();
+        assert_eq!(offer.key(), offer.offer_id);
+
+        // an RFQ whose id collides with the offer's
+        let rfq = NormalizedEvent {
+            event_type: EventType::RfqCreated,
+            rfq_id: Some(offer.offer_id.clone()),
+            ..offer.clone()
+        };
+        assert_eq!(rfq.key(), format!("rfq:{}:{}", rfq.maker, offer.offer_id));
+        assert_eq!(rfq.follow_up(EventType::EventFinalized).key(), rfq.key());
+        for v in 1..=SCHEMA_VERSION {
+            let again = read_current(&encode_event(&rfq, v).unwrap()).unwrap();
+            assert_eq!(again.rfq_id, rfq.rfq_id, "v{v}");
+        }
+    }
+
+    #[test]
+    fn mismatched_envelope_is_rejected() {
+        let (_, payload) = fixtures()
+            .into_iter()
+            .find(|(name, _)| name.starts_with("v2-") && name.contains("offer-created"))
+            .unwrap();
+        let payload = payload.replacen(
+            "\"event_type\": \"OfferCreated\"",
+            "\"event_type\": \"OfferFilled\"",
+            1,
+        );
+        assert!(decode_event(&payload).is_err());
+    }
+}
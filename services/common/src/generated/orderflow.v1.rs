@@ -0,0 +1,87 @@
+// This file is @generated by prost-build.
+/// escrow.events.v1; the JSON v2 envelope
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EventEnvelope {
+    #[prost(uint32, tag = "1")]
+    pub schema_version: u32,
+    /// same as data.event_type; a string so that types added later still decode
+    #[prost(string, tag = "2")]
+    pub event_type: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub data: ::core::option::Option<NormalizedEvent>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NormalizedEvent {
+    #[prost(string, tag = "1")]
+    pub event_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub event_type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub cluster: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "4")]
+    pub slot: u64,
+    #[prost(string, tag = "5")]
+    pub signature: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub program_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub offer_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub maker: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "9")]
+    pub taker: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, tag = "10")]
+    pub mint_a: ::prost::alloc::string::String,
+    #[prost(string, tag = "11")]
+    pub mint_b: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "12")]
+    pub amount_a: u64,
+    #[prost(uint64, tag = "13")]
+    pub amount_b: u64,
+    #[prost(string, optional, tag = "14")]
+    pub client_ref: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint64, optional, tag = "15")]
+    pub vault_amount: ::core::option::Option<u64>,
+    #[prost(int64, optional, tag = "16")]
+    pub block_time: ::core::option::Option<i64>,
+    #[prost(uint64, optional, tag = "17")]
+    pub fee_lamports: ::core::option::Option<u64>,
+    #[prost(string, optional, tag = "18")]
+    pub fee_payer: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint64, optional, tag = "19")]
+    pub compute_units_consumed: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "20")]
+    pub priority_fee_lamports: ::core::option::Option<u64>,
+    #[prost(string, tag = "21")]
+    pub commitment: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "22")]
+    pub ts_ingest_ms: u64,
+    #[prost(string, optional, tag = "23")]
+    pub ref_event_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "24")]
+    pub rfq_id: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// escrow.alerts.v1
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AlertEvent {
+    #[prost(string, tag = "1")]
+    pub alert_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub rule_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub severity: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub maker: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "5")]
+    pub offer_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint64, tag = "6")]
+    pub ts_ms: u64,
+    /// rule-specific object, as JSON text
+    #[prost(string, tag = "7")]
+    pub details_json: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "8")]
+    pub withdraws: ::core::option::Option<::prost::alloc::string::String>,
+}
@@ -1,32 +1,318 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+pub mod envelope;
+pub mod schema;
+pub mod signing;
+pub mod sink;
+pub mod types;
+pub mod wire;
+
+pub use types::{Pubkey58, Signature58, U64String};
+
+/// Offer and RFQ events share this shape. For RFQ events (`Rfq*`/`Quote*`): `rfq_id` is
+/// set, `maker` = requester, `taker` = responder, `amount_a` = size, `amount_b` = quoted
+/// price (`"0"` for `RfqCreated`/`RfqCancelled`); `offer_id` repeats the rfq id for
+/// consumers that predate `rfq_id`, so route on `rfq_id`, never on `offer_id` alone.
+///
+/// Fields are snake_case; the camelCase aliases read records from before schema v3.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct NormalizedEvent {
+    #[serde(alias = "eventId")]
     pub event_id: String,
+    #[serde(alias = "eventType")]
     pub event_type: EventType,
     pub cluster: String,
     pub slot: u64,
-    pub signature: String,
-    pub program_id: String,
+    pub signature: Signature58,
+    #[serde(alias = "programId")]
+    pub program_id: Pubkey58,
+    #[serde(alias = "offerId")]
     pub offer_id: String,
-    pub maker: String,
-    pub taker: Option<String>,
-    pub mint_a: String,
-    pub mint_b: String,
-    /// u64 encoded as string to avoid JS precision issues
-    pub amount_a: String,
-    /// u64 encoded as string to avoid JS precision issues
-    pub amount_b: String,
+    /// RFQ events and their follow-ups only; scoped per requester (`maker`)
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "rfqId")]
+    pub rfq_id: Option<String>,
+    pub maker: Pubkey58,
+    pub taker: Option<Pubkey58>,
+    #[serde(alias = "mintA")]
+    pub mint_a: Pubkey58,
+    #[serde(alias = "mintB")]
+    pub mint_b: Pubkey58,
+    #[serde(alias = "amountA")]
+    pub amount_a: U64String,
+    #[serde(alias = "amountB")]
+    pub amount_b: U64String,
+    /// caller-supplied offer reference, hex-encoded 32 bytes
+    #[serde(alias = "clientRef")]
+    pub client_ref: Option<String>,
+    /// actual vault balance at settlement; None for OfferCreated.
+    /// Differs from `amount_a` when the vault was topped up (surplus swept to maker).
+    #[serde(alias = "vaultAmount")]
+    pub vault_amount: Option<U64String>,
+    /// unix seconds of the block, when the node knows it
+    #[serde(default, alias = "blockTime")]
+    pub block_time: Option<i64>,
+    /// transaction fee in lamports (signature fees plus `priority_fee_lamports`)
+    #[serde(default, alias = "feeLamports")]
+    pub fee_lamports: Option<u64>,
+    #[serde(default, alias = "feePayer")]
+    pub fee_payer: Option<Pubkey58>,
+    #[serde(default, alias = "computeUnitsConsumed")]
+    pub compute_units_consumed: Option<u64>,
+    /// compute unit price × compute unit limit, rounded up to whole lamports
+    #[serde(default, alias = "priorityFeeLamports")]
+    pub priority_fee_lamports: Option<u64>,
     pub commitment: String,
+    #[serde(alias = "tsIngestMs")]
     pub ts_ingest_ms: u64,
+    /// follow-ups only: the `event_id` this finalizes or retracts
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "refEventId")]
+    pub ref_event_id: Option<String>,
+}
+
+impl NormalizedEvent {
+    /// `EventFinalized`/`EventRetracted` for an event ingested below `finalized`. The
+    /// follow-up copies the original fields so consumers can route it the same way
+    /// (same `offer_id` key, same snapshot row).
+    pub fn follow_up(&self, event_type: EventType) -> NormalizedEvent {
+        let suffix = match event_type {
+            EventType::EventFinalized => "finalized",
+            _ => "retracted",
+        };
+        NormalizedEvent {
+            event_id: format!("{}:{suffix}", self.event_id),
+            event_type,
+            commitment: "finalized".to_string(),
+            ts_ingest_ms: now_ms(),
+            ref_event_id: Some(self.event_id.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Kafka key: `offer_id` for offers, `rfq:<requester>:<rfq_id>` for RFQs, so offer and
+    /// RFQ ids never share a key.
+    pub fn key(&self) -> String {
+        match &self.rfq_id {
+            Some(rfq_id) => format!("rfq:{}:{rfq_id}", self.maker),
+            None => self.offer_id.clone(),
+        }
+    }
+
+    pub fn is_follow_up(&self) -> bool {
+        matches!(
+            self.event_type,
+            EventType::EventFinalized | EventType::EventRetracted
+        )
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Serialized as its name. Names this build does not know deserialize to
+/// [`EventType::Unknown`] instead of failing, so a producer can add event types ahead of
+/// its consumers (see [`envelope`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EventType {
     OfferCreated,
     OfferFilled,
     OfferCancelled,
+    /// admin `force_cancel`; vault refunded to the maker
+    OfferForceCancelled,
+    RfqCreated,
+    QuoteSubmitted,
+    QuoteAccepted,
+    QuoteRefunded,
+    /// the requester closed an RFQ without accepting a quote
+    RfqCancelled,
+    /// the confirmed event `ref_event_id` reached `finalized`
+    EventFinalized,
+    /// the confirmed event `ref_event_id` was on a fork that did not finalize; undo it
+    EventRetracted,
+    /// published by a newer producer; keep it, but do not act on it
+    Unknown(String),
+}
+
+impl EventType {
+    /// Every type this build knows, in the order they were added.
+    pub const KNOWN: &'static [EventType] = &[
+        EventType::OfferCreated,
+        EventType::OfferFilled,
+        EventType::OfferCancelled,
+        EventType::OfferForceCancelled,
+        EventType::RfqCreated,
+        EventType::QuoteSubmitted,
+        EventType::QuoteAccepted,
+        EventType::QuoteRefunded,
+        EventType::EventFinalized,
+        EventType::EventRetracted,
+        EventType::RfqCancelled,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventType::OfferCreated => "OfferCreated",
+            EventType::OfferFilled => "OfferFilled",
+            EventType::OfferCancelled => "OfferCancelled",
+            EventType::OfferForceCancelled => "OfferForceCancelled",
+            EventType::RfqCreated => "RfqCreated",
+            EventType::QuoteSubmitted => "QuoteSubmitted",
+            EventType::QuoteAccepted => "QuoteAccepted",
+            EventType::QuoteRefunded => "QuoteRefunded",
+            EventType::RfqCancelled => "RfqCancelled",
+            EventType::EventFinalized => "EventFinalized",
+            EventType::EventRetracted => "EventRetracted",
+            EventType::Unknown(name) => name,
+        }
+    }
+
+    pub fn from_name(name: &str) -> EventType {
+        match name {
+            "OfferCreated" => EventType::OfferCreated,
+            "OfferFilled" => EventType::OfferFilled,
+            "OfferCancelled" => EventType::OfferCancelled,
+            "OfferForceCancelled" => EventType::OfferForceCancelled,
+            "RfqCreated" => EventType::RfqCreated,
+            "QuoteSubmitted" => EventType::QuoteSubmitted,
+            "QuoteAccepted" => EventType::QuoteAccepted,
+            "QuoteRefunded" => EventType::QuoteRefunded,
+            "RfqCancelled" => EventType::RfqCancelled,
+            "EventFinalized" => EventType::EventFinalized,
+            "EventRetracted" => EventType::EventRetracted,
+            other => EventType::Unknown(other.to_string()),
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, EventType::Unknown(_))
+    }
+}
+
+impl Serialize for EventType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for EventType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = std::borrow::Cow::<str>::deserialize(deserializer)?;
+        Ok(EventType::from_name(&name))
+    }
+}
+
+/// Lifecycle of an `EscrowState` account; mirrors the program's `EscrowStatus`.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum OfferStatus {
+    Created,
+    Filled,
+    Cancelled,
+}
+
+impl OfferStatus {
+    /// The offer log event that moves an offer into this status.
+    pub fn event_type(self) -> EventType {
+        match self {
+            OfferStatus::Created => EventType::OfferCreated,
+            OfferStatus::Filled => EventType::OfferFilled,
+            OfferStatus::Cancelled => EventType::OfferCancelled,
+        }
+    }
+}
+
+/// A status an `EscrowState` account reached, read from account data instead of logs.
+/// Published to `escrow.offers.state.v1` by the listener's `accounts` source, as an
+/// independent view to reconcile the log-derived `escrow.events.v1` against.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OfferStateEvent {
+    /// `<escrow_state>:<status>`; statuses only move forward, so each is reached once
+    pub state_id: String,
+    /// the log event this transition corresponds to (a force-cancel shows as `OfferCancelled`)
+    pub event_type: EventType,
+    pub status: OfferStatus,
+    /// `None` when the account was first seen already in `status` (startup snapshot)
+    pub previous_status: Option<OfferStatus>,
+    pub cluster: String,
+    pub program_id: Pubkey58,
+    pub escrow_state: String,
+    pub offer_id: String,
+    pub maker: Pubkey58,
+    pub taker: Option<Pubkey58>,
+    pub mint_a: Pubkey58,
+    pub mint_b: Pubkey58,
+    pub amount_a: U64String,
+    pub amount_b: U64String,
+    /// caller-supplied offer reference, hex-encoded 32 bytes
+    pub client_ref: Option<String>,
+    /// slot the program stamped for `status` (`created_slot`/`filled_slot`/`cancelled_slot`)
+    pub slot: u64,
+    /// context slot of the notification or snapshot the account was read from
+    pub observed_slot: u64,
+    pub commitment: String,
+    pub ts_ingest_ms: u64,
+}
+
+/// Why part of a transaction produced no event.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub enum QuarantineReason {
+    /// a `Program log:` line inside the program's own frame that looks like an event but
+    /// does not decode
+    UndecodablePayload,
+    /// invoke/exit lines stop nesting; the rest of the transaction was skipped
+    InconsistentFrames,
+    /// the runtime truncated the logs (or the node kept none) and the event could not be
+    /// rebuilt from the instruction and account state
+    LogTruncated,
+}
+
+/// Part of a transaction the listener could not turn into events, with the raw logs for
+/// inspection or reprocessing. Published to `escrow.quarantine.v1`, keyed by signature.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QuarantineRecord {
+    /// `<signature>:<reason>:<log index, or instruction when there is no line>`
+    pub quarantine_id: String,
+    pub reason: QuarantineReason,
+    pub cluster: String,
+    pub program_id: String,
+    pub signature: String,
+    pub slot: u64,
+    /// instruction position (`outer` or `outer.inner`), when known
+    pub instruction: Option<String>,
+    /// index of the offending line in `logs`, when there is one
+    pub log_index: Option<u32>,
+    pub detail: String,
+    pub logs: Vec<String>,
+    pub ts_ingest_ms: u64,
+}
+
+/// A denylist change by the program admin (`deny_wallet` / `allow_wallet`). Not an offer
+/// event: published to `escrow.admin.v1`, keyed by wallet, and never followed up.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AdminEvent {
+    /// `signature:instruction:log_index`, as for [`NormalizedEvent`]
+    pub event_id: String,
+    pub event_type: AdminEventType,
+    pub cluster: String,
+    pub slot: u64,
+    pub signature: Signature58,
+    pub program_id: Pubkey58,
+    pub wallet: Pubkey58,
+    pub admin: Pubkey58,
+    pub commitment: String,
+    pub ts_ingest_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub enum AdminEventType {
+    WalletDenied,
+    WalletAllowed,
+}
+
+/// On-chain JSON log payload of the denylist instructions (demo format).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnchainAdminLogEvent {
+    pub event: AdminEventType,
+    pub wallet: Pubkey58,
+    pub admin: Pubkey58,
 }
 
 /// The on-chain JSON log payload (demo format).
@@ -35,15 +321,62 @@ pub enum EventType {
 pub struct OnchainLogEvent {
     pub event: String,
     pub offer_id: String,
-    pub maker: String,
-    pub taker: Option<String>,
-    pub mint_a: String,
-    pub mint_b: String,
+    pub maker: Pubkey58,
+    pub taker: Option<Pubkey58>,
+    pub mint_a: Pubkey58,
+    pub mint_b: Pubkey58,
     pub amount_a: u64,
     pub amount_b: u64,
+    /// absent in logs from program versions before `client_ref` existed
+    #[serde(default)]
+    pub client_ref: Option<String>,
+    /// settlement events only; absent in older program versions
+    #[serde(default)]
+    pub vault_amount: Option<u64>,
+    /// set when converted from an [`OnchainRfqLogEvent`]
+    #[serde(skip)]
+    pub rfq_id: Option<String>,
 }
 
+/// On-chain JSON log payload for the RFQ flow (demo format).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnchainRfqLogEvent {
+    pub event: String,
+    pub rfq_id: String,
+    pub requester: Pubkey58,
+    /// absent on `RfqCreated`/`RfqCancelled`
+    #[serde(default)]
+    pub responder: Option<Pubkey58>,
+    pub mint_a: Pubkey58,
+    pub mint_b: Pubkey58,
+    pub amount_a: u64,
+    /// absent on `RfqCreated`/`RfqCancelled`
+    #[serde(default)]
+    pub amount_b: Option<u64>,
+    #[serde(default)]
+    pub vault_amount: Option<u64>,
+}
+
+impl From<OnchainRfqLogEvent> for OnchainLogEvent {
+    fn from(r: OnchainRfqLogEvent) -> Self {
+        OnchainLogEvent {
+            event: r.event,
+            offer_id: r.rfq_id.clone(),
+            rfq_id: Some(r.rfq_id),
+            maker: r.requester,
+            taker: r.responder,
+            mint_a: r.mint_a,
+            mint_b: r.mint_b,
+            amount_a: r.amount_a,
+            amount_b: r.amount_b.unwrap_or(0),
+            client_ref: None,
+            vault_amount: r.vault_amount,
+        }
+    }
+}
+
+/// Published to `escrow.alerts.v1` by the risk-engine, keyed by maker.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AlertEvent {
     pub alert_id: String,
     pub rule_id: String,
@@ -52,6 +385,9 @@ pub struct AlertEvent {
     pub offer_id: Option<String>,
     pub ts_ms: u64,
     pub details: serde_json::Value,
+    /// set on a withdrawal: the `alert_id` this retracts (its triggering event was rolled back)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdraws: Option<String>,
 }
 
 pub fn now_ms() -> u64 {
@@ -61,8 +397,3 @@ pub fn now_ms() -> u64 {
         .unwrap_or_default()
         .as_millis() as u64
 }
-
-pub fn parse_u64_str(s: &str) -> Option<u64> {
-    s.parse::<u64>().ok()
-}
-
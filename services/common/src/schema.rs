@@ -0,0 +1,197 @@
+//! JSON Schemas of the Kafka record values, generated from the Rust types. The published
+//! copies are `docs/schema/<topic>.schema.json`; a test fails when they drift from the
+//! types, and `UPDATE_SCHEMAS=1 cargo test -p orderflow-common schema` rewrites them.
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Metadata, RootSchema, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde_json::Value;
+
+use crate::envelope::{Envelope, SCHEMA_VERSION};
+use crate::{
+    AdminEvent, AlertEvent, EventType, NormalizedEvent, OfferStateEvent, QuarantineRecord,
+};
+
+pub const EVENTS: &str = "escrow.events.v1";
+pub const OFFER_STATES: &str = "escrow.offers.state.v1";
+pub const QUARANTINE: &str = "escrow.quarantine.v1";
+pub const ALERTS: &str = "escrow.alerts.v1";
+pub const ADMIN: &str = "escrow.admin.v1";
+
+impl JsonSchema for EventType {
+    fn schema_name() -> String {
+        "EventType".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        // not an enum: consumers must accept types added after them
+        let known: Vec<_> = EventType::KNOWN
+            .iter()
+            .map(|t| Value::from(t.as_str()))
+            .collect();
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "event type name; producers may add names a consumer does not know".to_string(),
+                ),
+                examples: known,
+                ..Default::default()
+            })),
+            instance_type: Some(InstanceType::String.into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+fn root<T: JsonSchema>(topic: &str) -> RootSchema {
+    let mut schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<T>();
+    schema.schema.metadata().title = Some(topic.to_string());
+    schema
+}
+
+/// The schema of each topic's record value (JSON encoding), by topic.
+pub fn topics() -> Vec<(&'static str, RootSchema)> {
+    let mut events = root::<Envelope<NormalizedEvent>>(EVENTS);
+    // this build writes the current version; older ones are described in event-contract.md
+    let version = SchemaObject {
+        const_value: Some(SCHEMA_VERSION.into()),
+        ..Default::default()
+    };
+    events
+        .schema
+        .object()
+        .properties
+        .insert("schema_version".to_string(), version.into());
+    vec![
+        (EVENTS, events),
+        (OFFER_STATES, root::<OfferStateEvent>(OFFER_STATES)),
+        (QUARANTINE, root::<QuarantineRecord>(QUARANTINE)),
+        (ALERTS, root::<AlertEvent>(ALERTS)),
+        (ADMIN, root::<AdminEvent>(ADMIN)),
+    ]
+}
+
+/// Checks a record value against its topic's schema, listing every violation.
+#[cfg(any(test, feature = "conformance"))]
+pub fn validate(topic: &str, value: &Value) -> anyhow::Result<()> {
+    let Some((_, schema)) = topics().into_iter().find(|(t, _)| *t == topic) else {
+        anyhow::bail!("no schema for topic {topic}");
+    };
+    let schema = serde_json::to_value(schema)?;
+    let compiled = jsonschema::JSONSchema::compile(&schema)
+        .map_err(|e| anyhow::anyhow!("{topic} schema: {e}"))?;
+    if let Err(errors) = compiled.validate(value) {
+        let errors: Vec<_> = errors
+            .map(|e| format!("{}: {e}", e.instance_path))
+            .collect();
+        anyhow::bail!("{topic}: {}", errors.join("; "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{decode_event, encode_event, Decoded};
+    use crate::QuarantineReason;
+    use std::path::PathBuf;
+
+    fn published(topic: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join(format!("../../docs/schema/{topic}.schema.json"))
+    }
+
+    #[test]
+    fn published_schemas_are_current() {
+        for (topic, schema) in topics() {
+            let generated = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+            let path = published(topic);
+            if std::env::var_os("UPDATE_SCHEMAS").is_some() {
+                std::fs::write(&path, &generated).unwrap();
+            }
+            let on_disk = std::fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                on_disk == generated,
+                "{} is stale; rerun with UPDATE_SCHEMAS=1",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn current_events_conform_and_legacy_ones_do_not() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/events");
+        let mut checked = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let payload = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let Decoded::Event(ev) = decode_event(&payload).unwrap() else {
+                continue;
+            };
+            let value: Value =
+                serde_json::from_str(&encode_event(&ev, SCHEMA_VERSION).unwrap()).unwrap();
+            validate(EVENTS, &value).unwrap_or_else(|e| panic!("{}: {e:#}", ev.event_id));
+            // a pre-v3 (camelCase) payload must not pass for a current one
+            let legacy: Value = serde_json::from_str(&encode_event(&ev, 2).unwrap()).unwrap();
+            assert!(validate(EVENTS, &legacy).is_err());
+            checked += 1;
+        }
+        assert!(checked >= 9);
+
+        let mut value: Value =
+            serde_json::from_str(&encode_event(&decode_any(), SCHEMA_VERSION).unwrap()).unwrap();
+        value["data"]["amount_a"] = "1e9".into();
+        assert!(validate(EVENTS, &value).is_err());
+    }
+
+    fn decode_any() -> NormalizedEvent {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/events/v3-01-snake-case-offer-created.json");
+        match decode_event(&std::fs::read_to_string(path).unwrap()).unwrap() {
+            Decoded::Event(ev) => ev,
+            Decoded::Unsupported { .. } => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn quarantine_record_conforms() {
+        let record = QuarantineRecord {
+            quarantine_id: "sig:UndecodablePayload:3".to_string(),
+            reason: QuarantineReason::UndecodablePayload,
+            cluster: "localnet".to_string(),
+            program_id: "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk".to_string(),
+            signature: "sig".to_string(),
+            slot: 7,
+            instruction: Some("0".to_string()),
+            log_index: Some(3),
+            detail: "event payload does not decode".to_string(),
+            logs: vec!["Program log: {\"event\":\"OfferFilled\"}".to_string()],
+            ts_ingest_ms: 1_730_000_000_000,
+        };
+        validate(QUARANTINE, &serde_json::to_value(&record).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn admin_event_conforms() {
+        let event = crate::AdminEvent {
+            event_id: "4FpiGQPEkuh3GqVkbqievxrpE8w9mZkvVVmh8CksP2GQuedKf7xbmPaXTv31EnPqixdMxZjkvVuemDbBM9qHttoo:0:1".to_string(),
+            event_type: crate::AdminEventType::WalletDenied,
+            cluster: "localnet".to_string(),
+            slot: 7,
+            signature: "4FpiGQPEkuh3GqVkbqievxrpE8w9mZkvVVmh8CksP2GQuedKf7xbmPaXTv31EnPqixdMxZjkvVuemDbBM9qHttoo"
+                .parse()
+                .unwrap(),
+            program_id: "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk".parse().unwrap(),
+            wallet: "7Np41oeYqPefeNQEHSv1UDhYrehxin3NStELsSKCT4K2".parse().unwrap(),
+            admin: "EeFX3g6HePRn5ipNu5ey3jiYWRVmeK3Frxec2ZwqKs6W".parse().unwrap(),
+            commitment: "confirmed".to_string(),
+            ts_ingest_ms: 1_730_000_000_000,
+        };
+        let mut value = serde_json::to_value(&event).unwrap();
+        validate(ADMIN, &value).unwrap();
+        value["wallet"] = "W".into();
+        assert!(validate(ADMIN, &value).is_err());
+    }
+}
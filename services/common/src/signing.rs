@@ -0,0 +1,326 @@
+//! Provenance for records crossing the broker. The producer signs a canonical form of each
+//! payload, bound to the topic and record key it is sent under, with ed25519 and sends the
+//! signature and its key id as record headers; consumers check them against the keys they
+//! trust before acting on the payload.
+
+use anyhow::{bail, Context, Result};
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer as _};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// base58 ed25519 signature over [`signed_message`]
+pub const SIGNATURE_HEADER: &str = "orderflow-signature";
+/// which trusted key verifies [`SIGNATURE_HEADER`]
+pub const KEY_ID_HEADER: &str = "orderflow-key-id";
+
+/// Prefix of every [`signed_message`], so these signatures cannot be taken for any other
+/// ed25519 message made with the same key.
+const SIGNING_DOMAIN: &[u8] = b"orderflow-record-v1\0";
+
+/// The message that is signed: [`SIGNING_DOMAIN`], then the topic and the record key, each
+/// preceded by its length as a big-endian u32, then [`signed_bytes`] of the payload. A
+/// record replayed to another topic or under another key no longer verifies.
+pub fn signed_message(topic: &str, key: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    let payload = signed_bytes(payload)?;
+    let mut out =
+        Vec::with_capacity(SIGNING_DOMAIN.len() + 8 + topic.len() + key.len() + payload.len());
+    out.extend_from_slice(SIGNING_DOMAIN);
+    for field in [topic.as_bytes(), key] {
+        let len = u32::try_from(field.len()).context("topic or key too long to sign")?;
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(field);
+    }
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// The canonical payload bytes. A JSON payload is signed in canonical form (object keys
+/// sorted, no whitespace), so a consumer that re-encodes the record (or a producer whose
+/// field order changes) still verifies; a binary (protobuf) payload is signed as is.
+pub fn signed_bytes(payload: &[u8]) -> Result<Vec<u8>> {
+    if !payload.trim_ascii_start().starts_with(b"{") {
+        return Ok(payload.to_vec());
+    }
+    let value: Value = serde_json::from_slice(payload).context("payload is not JSON")?;
+    let mut out = Vec::with_capacity(payload.len());
+    write_canonical(&value, &mut out)?;
+    Ok(out)
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push(b'{');
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, k)?;
+                out.push(b':');
+                write_canonical(v, out)?;
+            }
+            out.push(b'}');
+        }
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, v) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(v, out)?;
+            }
+            out.push(b']');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
+
+/// Signs outgoing payloads with one ed25519 key.
+pub struct EventSigner {
+    key_id: String,
+    keypair: Keypair,
+}
+
+impl EventSigner {
+    /// `bytes` is the 64-byte secret+public keypair (the `solana-keygen` layout).
+    /// `key_id` defaults to the base58 public key.
+    pub fn from_keypair_bytes(bytes: &[u8], key_id: Option<&str>) -> Result<Self> {
+        let keypair =
+            Keypair::from_bytes(bytes).map_err(|e| anyhow::anyhow!("ed25519 keypair: {e}"))?;
+        let key_id = match key_id {
+            Some(id) => id.to_string(),
+            None => bs58::encode(keypair.public.as_bytes()).into_string(),
+        };
+        Ok(Self { key_id, keypair })
+    }
+
+    /// Reads a `solana-keygen` JSON keypair file (an array of 64 bytes).
+    pub fn from_keypair_file(path: &Path, key_id: Option<&str>) -> Result<Self> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let bytes: Vec<u8> =
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+        Self::from_keypair_bytes(&bytes, key_id).with_context(|| path.display().to_string())
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub fn public_key(&self) -> String {
+        bs58::encode(self.keypair.public.as_bytes()).into_string()
+    }
+
+    /// The [`KEY_ID_HEADER`] and [`SIGNATURE_HEADER`] headers for `payload` sent to `topic`
+    /// under `key`.
+    pub fn headers(&self, topic: &str, key: &str, payload: &[u8]) -> Result<Vec<(String, String)>> {
+        let signature = self
+            .keypair
+            .sign(&signed_message(topic, key.as_bytes(), payload)?);
+        Ok(vec![
+            (KEY_ID_HEADER.to_string(), self.key_id.clone()),
+            (
+                SIGNATURE_HEADER.to_string(),
+                bs58::encode(signature.to_bytes()).into_string(),
+            ),
+        ])
+    }
+}
+
+/// Public keys a consumer accepts signatures from, by key id. Empty means verification
+/// is off and every record is accepted.
+#[derive(Debug, Default)]
+pub struct TrustedKeys {
+    keys: HashMap<String, PublicKey>,
+}
+
+impl TrustedKeys {
+    /// Entries are `<key_id>=<base58 public key>`, or a bare public key whose key id is
+    /// the key itself (the signer's default).
+    pub fn parse(entries: &[String]) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for entry in entries.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            let (id, key) = entry.split_once('=').unwrap_or((entry, entry));
+            let bytes = bs58::decode(key)
+                .into_vec()
+                .with_context(|| format!("trusted key {id:?}: not base58"))?;
+            let key = PublicKey::from_bytes(&bytes)
+                .map_err(|e| anyhow::anyhow!("trusted key {id:?}: {e}"))?;
+            if keys.insert(id.to_string(), key).is_some() {
+                bail!("trusted key {id:?} listed twice");
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Checks `payload`, received on `topic` under `key`, against the header values it
+    /// arrived with. Always `Ok` when no keys are configured.
+    pub fn verify(
+        &self,
+        topic: &str,
+        key: &[u8],
+        payload: &[u8],
+        key_id: Option<&[u8]>,
+        signature: Option<&[u8]>,
+    ) -> Result<()> {
+        if self.keys.is_empty() {
+            return Ok(());
+        }
+        let (Some(key_id), Some(signature)) = (key_id, signature) else {
+            bail!("unsigned record");
+        };
+        let key_id = String::from_utf8_lossy(key_id);
+        let Some(trusted) = self.keys.get(key_id.as_ref()) else {
+            bail!("untrusted key id {key_id:?}");
+        };
+        let signature = bs58::decode(signature)
+            .into_vec()
+            .context("signature is not base58")?;
+        let signature = Signature::from_bytes(&signature)
+            .map_err(|e| anyhow::anyhow!("malformed signature: {e}"))?;
+        let message = signed_message(topic, key, payload)?;
+        trusted
+            .verify_strict(&message, &signature)
+            .map_err(|_| anyhow::anyhow!("bad signature for key id {key_id:?}"))
+    }
+
+    /// [`TrustedKeys::verify`] on a consumed Kafka message.
+    #[cfg(feature = "kafka")]
+    pub fn verify_message<M: rdkafka::message::Message>(&self, msg: &M) -> Result<()> {
+        use rdkafka::message::Headers;
+
+        if self.keys.is_empty() {
+            return Ok(());
+        }
+        let payload = msg.payload().unwrap_or_default();
+        let header = |name: &str| {
+            msg.headers()
+                .and_then(|h| h.iter().find(|h| h.key == name))
+                .and_then(|h| h.value)
+        };
+        self.verify(
+            msg.topic(),
+            msg.key().unwrap_or_default(),
+            payload,
+            header(KEY_ID_HEADER),
+            header(SIGNATURE_HEADER),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SecretKey;
+
+    fn signer(seed: u8, key_id: Option<&str>) -> EventSigner {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        let bytes = [secret.to_bytes(), public.to_bytes()].concat();
+        EventSigner::from_keypair_bytes(&bytes, key_id).unwrap()
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a [u8]> {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_bytes())
+    }
+
+    const TOPIC: &str = "escrow.events.v1";
+    const KEY: &str = "42";
+
+    #[test]
+    fn verifies_reencoded_payload_and_rejects_tampering() {
+        let signer = signer(7, Some("listener-1"));
+        let trusted = TrustedKeys::parse(&[format!("listener-1={}", signer.public_key())]).unwrap();
+        let payload = br#"{"eventId":"s:0:2","slot":5,"amountA":"10","taker":null}"#;
+        let headers = signer.headers(TOPIC, KEY, payload).unwrap();
+        let (id, sig) = (
+            header(&headers, KEY_ID_HEADER),
+            header(&headers, SIGNATURE_HEADER),
+        );
+        let verify =
+            |payload: &[u8], id, sig| trusted.verify(TOPIC, KEY.as_bytes(), payload, id, sig);
+
+        verify(payload, id, sig).unwrap();
+        let reordered = br#"{ "taker": null, "slot": 5, "amountA": "10", "eventId": "s:0:2" }"#;
+        verify(reordered, id, sig).unwrap();
+
+        let tampered = String::from_utf8_lossy(payload).replace("\"10\"", "\"11\"");
+        assert!(verify(tampered.as_bytes(), id, sig).is_err());
+        assert!(verify(payload, None, None).is_err());
+        assert!(verify(payload, Some(b"listener-2"), sig).is_err());
+    }
+
+    #[test]
+    fn rejects_a_record_replayed_to_another_topic_or_key() {
+        let signer = signer(3, None);
+        let trusted = TrustedKeys::parse(&[signer.public_key()]).unwrap();
+        let payload = br#"{"offer_id":"42","status":"open"}"#;
+        let headers = signer.headers(TOPIC, KEY, payload).unwrap();
+        let (id, sig) = (
+            header(&headers, KEY_ID_HEADER),
+            header(&headers, SIGNATURE_HEADER),
+        );
+
+        trusted
+            .verify(TOPIC, KEY.as_bytes(), payload, id, sig)
+            .unwrap();
+        assert!(trusted
+            .verify("escrow.offers.state.v1", KEY.as_bytes(), payload, id, sig)
+            .is_err());
+        assert!(trusted.verify(TOPIC, b"43", payload, id, sig).is_err());
+        // the length prefixes keep the topic/key boundary from shifting
+        assert!(trusted
+            .verify("escrow.events.v14", b"2", payload, id, sig)
+            .is_err());
+    }
+
+    #[test]
+    fn default_key_id_is_the_public_key() {
+        let (ours, theirs) = (signer(1, None), signer(2, None));
+        let trusted = TrustedKeys::parse(&[ours.public_key()]).unwrap();
+        assert_eq!(ours.key_id(), ours.public_key());
+
+        let payload = br#"{"a":1}"#;
+        let headers = ours.headers(TOPIC, KEY, payload).unwrap();
+        trusted
+            .verify(
+                TOPIC,
+                KEY.as_bytes(),
+                payload,
+                header(&headers, KEY_ID_HEADER),
+                header(&headers, SIGNATURE_HEADER),
+            )
+            .unwrap();
+
+        // a valid signature from a key that is not trusted
+        let headers = theirs.headers(TOPIC, KEY, payload).unwrap();
+        let forged = header(&headers, SIGNATURE_HEADER);
+        assert!(trusted
+            .verify(
+                TOPIC,
+                KEY.as_bytes(),
+                payload,
+                Some(ours.key_id().as_bytes()),
+                forged
+            )
+            .is_err());
+        assert!(TrustedKeys::default()
+            .verify(TOPIC, KEY.as_bytes(), payload, None, None)
+            .is_ok());
+    }
+}
@@ -0,0 +1,990 @@
+//! Where the listener delivers serialized events. Kafka is the production path; the file
+//! and stdout sinks let the pipeline run on a laptop without a broker.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+
+use crate::signing::EventSigner;
+
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Deliver one serialized event. `key` orders related events (Kafka partition key);
+    /// sinks without partitions ignore it. Returns once the sink has accepted the record.
+    /// Line-oriented sinks (stdout, jsonl) expect a single-line UTF-8 payload.
+    async fn send(&self, key: &str, payload: &[u8]) -> Result<()>;
+
+    /// [`Sink::send`] with record headers (name, value). Sinks without headers (stdout,
+    /// jsonl) drop them.
+    async fn send_with_headers(
+        &self,
+        key: &str,
+        payload: &[u8],
+        _headers: &[(String, String)],
+    ) -> Result<()> {
+        self.send(key, payload).await
+    }
+
+    /// Push out anything buffered; called periodically by long-running producers.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes each record as one line to stdout.
+pub struct StdoutSink;
+
+#[async_trait]
+impl Sink for StdoutSink {
+    async fn send(&self, _key: &str, payload: &[u8]) -> Result<()> {
+        let mut out = std::io::stdout().lock();
+        out.write_all(payload).context("write stdout")?;
+        writeln!(out).context("write stdout")?;
+        Ok(())
+    }
+}
+
+/// Append-only JSONL files in `dir`, one record per line. A new file
+/// (`<prefix>.<unix_ms>.jsonl`) is started once the current one reaches `max_bytes`.
+pub struct JsonlSink {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    current: Mutex<Option<(File, u64)>>,
+}
+
+impl JsonlSink {
+    pub fn new(dir: impl Into<PathBuf>, prefix: &str, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            prefix: prefix.to_string(),
+            max_bytes,
+            current: Mutex::new(None),
+        })
+    }
+
+    fn open_next(&self) -> Result<File> {
+        let path = self
+            .dir
+            .join(format!("{}.{}.jsonl", self.prefix, crate::now_ms()));
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))
+    }
+}
+
+#[async_trait]
+impl Sink for JsonlSink {
+    async fn send(&self, _key: &str, payload: &[u8]) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        if current
+            .as_ref()
+            .is_none_or(|(_, written)| *written >= self.max_bytes)
+        {
+            *current = Some((self.open_next()?, 0));
+        }
+        let (file, written) = current.as_mut().expect("opened above");
+        let line = [payload, b"\n"].concat();
+        file.write_all(&line).context("append jsonl")?;
+        file.flush().context("flush jsonl")?;
+        *written += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Delivery counters of a [`SpoolingSink`], shared with whatever exports metrics.
+#[derive(Debug, Default)]
+pub struct SpoolStats {
+    pub delivery_failures_total: AtomicU64,
+    /// records currently waiting in the spool
+    pub spooled: AtomicU64,
+    pub replayed_total: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpooledRecord {
+    key: String,
+    /// UTF-8 payloads; binary ones go to `payload_base64`
+    #[serde(default)]
+    payload: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload_base64: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<(String, String)>,
+}
+
+impl SpooledRecord {
+    fn new(key: &str, payload: &[u8], headers: &[(String, String)]) -> Self {
+        let (payload, payload_base64) = match std::str::from_utf8(payload) {
+            Ok(text) => (text.to_string(), None),
+            Err(_) => (String::new(), Some(BASE64.encode(payload))),
+        };
+        Self {
+            key: key.to_string(),
+            payload,
+            payload_base64,
+            headers: headers.to_vec(),
+        }
+    }
+
+    fn payload(&self) -> Result<Vec<u8>> {
+        match &self.payload_base64 {
+            Some(b64) => BASE64.decode(b64).context("decode spooled payload"),
+            None => Ok(self.payload.clone().into_bytes()),
+        }
+    }
+}
+
+/// Wraps a sink so that records it fails to deliver are appended to an on-disk spool
+/// instead of being lost. While the spool is non-empty every new record goes behind it and
+/// a background task replays it in order, retrying with backoff, so delivery order is kept
+/// and a broker outage never stalls the sender. Needs a tokio runtime.
+pub struct SpoolingSink {
+    spool: Arc<Spool>,
+    drainer: tokio::task::JoinHandle<()>,
+}
+
+struct Spool {
+    inner: Box<dyn Sink>,
+    path: PathBuf,
+    stats: Arc<SpoolStats>,
+    /// serializes appends, direct sends and rewrites of the spool file
+    lock: tokio::sync::Mutex<()>,
+    /// wakes the drainer when the spool goes from empty to non-empty
+    wake: tokio::sync::Notify,
+}
+
+/// Backoff of the spool drainer after a failed replay.
+const DRAIN_RETRY_MIN: Duration = Duration::from_millis(500);
+const DRAIN_RETRY_MAX: Duration = Duration::from_secs(30);
+
+impl SpoolingSink {
+    /// Picks up records left in `dir` by a previous run.
+    pub fn open(
+        inner: Box<dyn Sink>,
+        dir: impl Into<PathBuf>,
+        stats: Arc<SpoolStats>,
+    ) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+        let path = dir.join("spool.jsonl");
+        let pending = match File::open(&path) {
+            Ok(f) => BufReader::new(f).lines().count() as u64,
+            Err(_) => 0,
+        };
+        stats.spooled.store(pending, Ordering::Relaxed);
+        let spool = Arc::new(Spool {
+            inner,
+            path,
+            stats,
+            lock: tokio::sync::Mutex::new(()),
+            wake: tokio::sync::Notify::new(),
+        });
+        let drainer = tokio::spawn(spool.clone().drain_forever());
+        Ok(Self { spool, drainer })
+    }
+}
+
+impl Drop for SpoolingSink {
+    fn drop(&mut self) {
+        self.drainer.abort();
+    }
+}
+
+impl Spool {
+    fn read(&self) -> Result<Vec<String>> {
+        match File::open(&self.path) {
+            Ok(f) => BufReader::new(f)
+                .lines()
+                .collect::<std::io::Result<Vec<_>>>()
+                .context("read spool"),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// Caller holds `lock`.
+    fn append(&self, key: &str, payload: &[u8], headers: &[(String, String)]) -> Result<()> {
+        let line = serde_json::to_string(&SpooledRecord::new(key, payload, headers))?;
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("open {}", self.path.display()))?;
+        writeln!(f, "{line}").context("append spool")?;
+        f.sync_data().context("sync spool")?;
+        if self.stats.spooled.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.wake.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Replays the spool until it is empty, backing off while the inner sink fails.
+    async fn drain_forever(self: Arc<Self>) {
+        let mut delay = DRAIN_RETRY_MIN;
+        loop {
+            if self.stats.spooled.load(Ordering::Relaxed) == 0 {
+                self.wake.notified().await;
+                continue;
+            }
+            match self.drain().await {
+                Ok(true) => delay = DRAIN_RETRY_MIN,
+                Ok(false) => {
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(DRAIN_RETRY_MAX);
+                }
+                Err(e) => {
+                    eprintln!("spool replay failed, retrying in {delay:?}: {e:#}");
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(DRAIN_RETRY_MAX);
+                }
+            }
+        }
+    }
+
+    /// Replays the spool oldest first without holding `lock`, stopping at the first
+    /// failure, then drops what was delivered; records appended meanwhile stay behind the
+    /// rest. Returns whether everything read was delivered.
+    async fn drain(&self) -> Result<bool> {
+        let records = {
+            let _guard = self.lock.lock().await;
+            self.read()?
+        };
+
+        let mut sent = 0;
+        for line in &records {
+            let rec: SpooledRecord = serde_json::from_str(line).context("decode spool record")?;
+            if let Err(e) = self
+                .inner
+                .send_with_headers(&rec.key, &rec.payload()?, &rec.headers)
+                .await
+            {
+                self.stats
+                    .delivery_failures_total
+                    .fetch_add(1, Ordering::Relaxed);
+                eprintln!(
+                    "spool replay stalled ({} left): {e:#}",
+                    records.len() - sent
+                );
+                break;
+            }
+            sent += 1;
+            self.stats.replayed_total.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let _guard = self.lock.lock().await;
+        let rest = self.read()?.split_off(sent);
+        if rest.is_empty() {
+            if sent > 0 {
+                std::fs::remove_file(&self.path).context("remove spool")?;
+                eprintln!("spool drained: {sent} records replayed");
+            }
+        } else if sent > 0 {
+            let tmp = self.path.with_extension("jsonl.tmp");
+            std::fs::write(&tmp, rest.join("\n") + "\n").context("write spool")?;
+            std::fs::rename(&tmp, &self.path).context("replace spool")?;
+        }
+        self.stats
+            .spooled
+            .store(rest.len() as u64, Ordering::Relaxed);
+        Ok(sent == records.len())
+    }
+}
+
+#[async_trait]
+impl Sink for SpoolingSink {
+    async fn send(&self, key: &str, payload: &[u8]) -> Result<()> {
+        self.send_with_headers(key, payload, &[]).await
+    }
+
+    async fn send_with_headers(
+        &self,
+        key: &str,
+        payload: &[u8],
+        headers: &[(String, String)],
+    ) -> Result<()> {
+        let spool = &self.spool;
+        let _guard = spool.lock.lock().await;
+        if spool.stats.spooled.load(Ordering::Relaxed) == 0 {
+            match spool.inner.send_with_headers(key, payload, headers).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    spool
+                        .stats
+                        .delivery_failures_total
+                        .fetch_add(1, Ordering::Relaxed);
+                    eprintln!("delivery failed, spooling: {e:#}");
+                }
+            }
+        }
+        spool.append(key, payload, headers)
+    }
+
+    /// Flushes the inner sink; a non-empty spool is left to the drainer.
+    async fn flush(&self) -> Result<()> {
+        self.spool.inner.flush().await
+    }
+}
+
+/// Wraps a sink so that every record carries an ed25519 signature of its payload, bound to
+/// `topic` and the record key, in the [`SIGNATURE_HEADER`](crate::signing::SIGNATURE_HEADER) and
+/// [`KEY_ID_HEADER`](crate::signing::KEY_ID_HEADER) headers. Goes outside a
+/// [`SpoolingSink`], so spooled records keep the signature they were first sent with.
+pub struct SigningSink {
+    inner: Box<dyn Sink>,
+    signer: EventSigner,
+    topic: String,
+}
+
+impl SigningSink {
+    /// `topic` is where `inner` delivers records (the Kafka topic or NATS subject); consumers
+    /// verify against the topic they read from.
+    pub fn new(inner: Box<dyn Sink>, signer: EventSigner, topic: &str) -> Self {
+        Self {
+            inner,
+            signer,
+            topic: topic.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for SigningSink {
+    async fn send(&self, key: &str, payload: &[u8]) -> Result<()> {
+        self.send_with_headers(key, payload, &[]).await
+    }
+
+    async fn send_with_headers(
+        &self,
+        key: &str,
+        payload: &[u8],
+        headers: &[(String, String)],
+    ) -> Result<()> {
+        let mut headers = headers.to_vec();
+        headers.extend(self.signer.headers(&self.topic, key, payload)?);
+        self.inner.send_with_headers(key, payload, &headers).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::Sink;
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::message::{Header, OwnedHeaders};
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use std::time::Duration;
+
+    pub struct KafkaSink {
+        producer: FutureProducer,
+        topic: String,
+    }
+
+    impl KafkaSink {
+        pub fn new(brokers: &str, topic: &str) -> Result<Self> {
+            let producer: FutureProducer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                // exactly-once per partition on broker-side retries, acks from all ISRs
+                .set("enable.idempotence", "true")
+                .set("acks", "all")
+                // librdkafka retries internally until this expires, then send() fails
+                .set("message.timeout.ms", "30000")
+                .create()
+                .context("create kafka producer")?;
+            Ok(Self {
+                producer,
+                topic: topic.to_string(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Sink for KafkaSink {
+        async fn send(&self, key: &str, payload: &[u8]) -> Result<()> {
+            self.send_with_headers(key, payload, &[]).await
+        }
+
+        async fn send_with_headers(
+            &self,
+            key: &str,
+            payload: &[u8],
+            headers: &[(String, String)],
+        ) -> Result<()> {
+            let mut record = FutureRecord::to(&self.topic).key(key).payload(payload);
+            if !headers.is_empty() {
+                let owned = headers
+                    .iter()
+                    .fold(OwnedHeaders::new(), |h, (name, value)| {
+                        h.insert(Header {
+                            key: name,
+                            value: Some(value),
+                        })
+                    });
+                record = record.headers(owned);
+            }
+            self.producer
+                .send(record, Duration::from_secs(5))
+                .await
+                .map_err(|(e, _)| e)
+                .with_context(|| format!("kafka send to {}", self.topic))?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+pub use nats::NatsSink;
+
+#[cfg(feature = "nats")]
+mod nats {
+    //! Just enough of the NATS client protocol to publish into JetStream and wait for the
+    //! PubAck. The `async-nats` crate pulls in `ed25519-dalek` 2, which cannot coexist
+    //! with the `curve25519-dalek` 3 pinned by solana 1.18.
+
+    use super::Sink;
+    use anyhow::{anyhow, bail, Context, Result};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+    use tokio::net::TcpStream;
+    use tokio::sync::{oneshot, Mutex};
+    use tokio::task::JoinHandle;
+
+    const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Publishes to a subject bound to a JetStream stream and waits for each PubAck. A
+    /// reader task owns the socket's read side and hands acks to their publish by reply
+    /// subject; a connection that fails is replaced on the next send.
+    pub struct NatsSink {
+        url: String,
+        subject: String,
+        /// per-publish reply subject suffix, so a late ack is never taken for the next one
+        next_reply: AtomicU64,
+        conn: Mutex<Option<Conn>>,
+    }
+
+    struct Conn {
+        writer: Arc<Mutex<OwnedWriteHalf>>,
+        inbox: String,
+        acks: Arc<std::sync::Mutex<Acks>>,
+        reader: JoinHandle<()>,
+    }
+
+    /// Publishes waiting for an ack, by reply subject. Once the reader stops, `closed` says
+    /// why and nothing more is registered.
+    #[derive(Default)]
+    struct Acks {
+        waiting: HashMap<String, oneshot::Sender<Result<()>>>,
+        closed: Option<String>,
+    }
+
+    impl Acks {
+        fn close(&mut self, reason: String) {
+            for (_, tx) in self.waiting.drain() {
+                let _ = tx.send(Err(anyhow!("nats: {reason}")));
+            }
+            self.closed = Some(reason);
+        }
+    }
+
+    impl NatsSink {
+        /// `url` is `nats://[token@]host:port`; TLS is not supported.
+        pub async fn connect(url: &str, subject: &str) -> Result<Self> {
+            let conn = Conn::open(url).await?;
+            Ok(Self {
+                url: url.to_string(),
+                subject: subject.to_string(),
+                next_reply: AtomicU64::new(0),
+                conn: Mutex::new(Some(conn)),
+            })
+        }
+    }
+
+    impl Conn {
+        async fn open(url: &str) -> Result<Self> {
+            let rest = url.strip_prefix("nats://").unwrap_or(url);
+            let (token, addr) = match rest.rsplit_once('@') {
+                Some((token, addr)) => (Some(token), addr),
+                None => (None, rest),
+            };
+
+            let stream = TcpStream::connect(addr)
+                .await
+                .with_context(|| format!("nats connect {addr}"))?;
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            let inbox = format!("_INBOX.orderflow.{}", crate::now_ms());
+
+            let info = read_line(&mut reader).await?;
+            let Some(info) = info.strip_prefix("INFO ") else {
+                bail!("nats: expected INFO, got {info:?}");
+            };
+            let info: serde_json::Value = serde_json::from_str(info).context("nats INFO")?;
+            if info["tls_required"].as_bool() == Some(true) {
+                bail!("nats: server requires TLS, which this sink does not support");
+            }
+
+            // headers for HPUB; no_responders turns a publish nobody stores into a 503 reply
+            let connect = serde_json::json!({
+                "verbose": false,
+                "pedantic": false,
+                "lang": "rust",
+                "version": env!("CARGO_PKG_VERSION"),
+                "auth_token": token,
+                "headers": true,
+                "no_responders": true,
+            });
+            let hello = format!("CONNECT {connect}\r\nSUB {inbox}.* 1\r\nPING\r\n");
+            writer
+                .write_all(hello.as_bytes())
+                .await
+                .context("nats CONNECT")?;
+
+            // the server answers PING only after accepting CONNECT; auth failures arrive as -ERR
+            loop {
+                let line = read_line(&mut reader).await?;
+                match line.as_str() {
+                    "PONG" => break,
+                    "PING" => writer.write_all(b"PONG\r\n").await.context("nats PONG")?,
+                    l if l.starts_with("-ERR") => bail!("nats: {l}"),
+                    _ => {}
+                }
+            }
+
+            let writer = Arc::new(Mutex::new(writer));
+            let acks = Arc::new(std::sync::Mutex::new(Acks::default()));
+            let reader = tokio::spawn(read_loop(reader, writer.clone(), acks.clone()));
+            Ok(Self {
+                writer,
+                inbox,
+                acks,
+                reader,
+            })
+        }
+
+        fn is_closed(&self) -> bool {
+            self.acks.lock().unwrap().closed.is_some()
+        }
+    }
+
+    impl Drop for Conn {
+        fn drop(&mut self) {
+            self.reader.abort();
+        }
+    }
+
+    async fn read_line(reader: &mut BufReader<OwnedReadHalf>) -> Result<String> {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.context("nats read")? == 0 {
+            bail!("connection closed");
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    /// Runs until the connection fails, then fails every publish still waiting.
+    async fn read_loop(
+        mut reader: BufReader<OwnedReadHalf>,
+        writer: Arc<Mutex<OwnedWriteHalf>>,
+        acks: Arc<std::sync::Mutex<Acks>>,
+    ) {
+        let e = match route_acks(&mut reader, &writer, &acks).await {
+            Ok(never) => match never {},
+            Err(e) => e,
+        };
+        acks.lock().unwrap().close(format!("{e:#}"));
+    }
+
+    async fn route_acks(
+        reader: &mut BufReader<OwnedReadHalf>,
+        writer: &Mutex<OwnedWriteHalf>,
+        acks: &std::sync::Mutex<Acks>,
+    ) -> Result<std::convert::Infallible> {
+        loop {
+            let line = read_line(reader).await?;
+            if line == "PING" {
+                writer
+                    .lock()
+                    .await
+                    .write_all(b"PONG\r\n")
+                    .await
+                    .context("nats PONG")?;
+                continue;
+            }
+            // the server closes the connection after most errors; treat them all as fatal
+            if line.starts_with("-ERR") {
+                bail!("{line}");
+            }
+            // MSG <subject> <sid> [reply-to] <#bytes>
+            // HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>
+            let (fields, with_headers) = match line.split_once(' ') {
+                Some(("MSG", fields)) => (fields, false),
+                Some(("HMSG", fields)) => (fields, true),
+                _ => continue,
+            };
+            let fields: Vec<&str> = fields.split(' ').collect();
+            let sizes = |i: usize| -> Result<usize> {
+                fields
+                    .len()
+                    .checked_sub(i)
+                    .and_then(|i| fields[i].parse().ok())
+                    .with_context(|| format!("bad frame {line:?}"))
+            };
+            let total = sizes(1)?;
+            let header_len = if with_headers { sizes(2)? } else { 0 };
+            if header_len > total {
+                bail!("bad frame {line:?}");
+            }
+            let mut body = vec![0u8; total + 2];
+            reader
+                .read_exact(&mut body)
+                .await
+                .context("nats read MSG")?;
+            body.truncate(total);
+
+            let waiting = acks.lock().unwrap().waiting.remove(fields[0]);
+            if let Some(tx) = waiting {
+                let (headers, ack) = body.split_at(header_len);
+                let _ = tx.send(pub_ack(headers, ack));
+            }
+        }
+    }
+
+    /// The outcome carried by a reply: a status header (503 when no stream stores the
+    /// subject) or a PubAck, which may hold a JetStream error.
+    fn pub_ack(headers: &[u8], body: &[u8]) -> Result<()> {
+        let status = String::from_utf8_lossy(headers);
+        // NATS/1.0 <code> [description]
+        if let Some(code) = status.lines().next().and_then(|l| l.split(' ').nth(1)) {
+            if code == "503" {
+                bail!("nats: no stream is bound to the subject (503 no responders)");
+            }
+            bail!("nats: status {}", status.lines().next().unwrap_or_default());
+        }
+        let ack: serde_json::Value = serde_json::from_slice(body).context("nats PubAck")?;
+        if let Some(err) = ack.get("error") {
+            bail!("jetstream: {err}");
+        }
+        Ok(())
+    }
+
+    #[async_trait]
+    impl Sink for NatsSink {
+        async fn send(&self, key: &str, payload: &[u8]) -> Result<()> {
+            self.send_with_headers(key, payload, &[]).await
+        }
+
+        /// Headers go out as an HPUB (NATS 2.2+); without any it is a plain PUB.
+        async fn send_with_headers(
+            &self,
+            _key: &str,
+            payload: &[u8],
+            headers: &[(String, String)],
+        ) -> Result<()> {
+            let (reply, rx, acks) = {
+                let mut conn = self.conn.lock().await;
+                if conn.as_ref().is_none_or(Conn::is_closed) {
+                    *conn = None;
+                    *conn = Some(Conn::open(&self.url).await?);
+                }
+                let conn = conn.as_mut().expect("connected above");
+
+                let reply = format!(
+                    "{}.{}",
+                    conn.inbox,
+                    self.next_reply.fetch_add(1, Ordering::Relaxed) + 1
+                );
+                let (tx, rx) = oneshot::channel();
+                {
+                    let mut acks = conn.acks.lock().unwrap();
+                    if let Some(reason) = &acks.closed {
+                        bail!("nats: {reason}");
+                    }
+                    acks.waiting.insert(reply.clone(), tx);
+                }
+
+                let mut frame = if headers.is_empty() {
+                    format!("PUB {} {reply} {}\r\n", self.subject, payload.len()).into_bytes()
+                } else {
+                    let mut block = String::from("NATS/1.0\r\n");
+                    for (name, value) in headers {
+                        block.push_str(&format!("{name}: {value}\r\n"));
+                    }
+                    block.push_str("\r\n");
+                    let total = block.len() + payload.len();
+                    format!(
+                        "HPUB {} {reply} {} {total}\r\n{block}",
+                        self.subject,
+                        block.len()
+                    )
+                    .into_bytes()
+                };
+                frame.extend_from_slice(payload);
+                frame.extend_from_slice(b"\r\n");
+                if let Err(e) = conn.writer.lock().await.write_all(&frame).await {
+                    conn.acks
+                        .lock()
+                        .unwrap()
+                        .close(format!("write failed: {e}"));
+                    return Err(e).context("nats PUB");
+                }
+                (reply, rx, conn.acks.clone())
+            };
+            match tokio::time::timeout(ACK_TIMEOUT, rx).await {
+                Ok(Ok(outcome)) => outcome,
+                Ok(Err(_)) => bail!("nats: connection dropped before the ack"),
+                Err(_) => {
+                    acks.lock().unwrap().waiting.remove(&reply);
+                    bail!("jetstream ack timeout");
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tokio::net::TcpListener;
+
+        /// How the mock server answers each publish, in order, across connections.
+        enum Reply {
+            Ack,
+            /// a PING, an ack for some other reply subject, then the ack as an HMSG in two writes
+            AckInPieces,
+            NoResponders,
+            /// `-ERR`, then the server drops the connection
+            Error,
+        }
+
+        async fn mock_server(replies: Vec<Reply>) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("nats://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                let mut replies = replies.into_iter();
+                let mut seq = 0;
+                loop {
+                    let (sock, _) = listener.accept().await.unwrap();
+                    let (r, mut w) = sock.into_split();
+                    let mut r = BufReader::new(r);
+                    w.write_all(b"INFO {\"server_id\":\"mock\",\"headers\":true}\r\n")
+                        .await
+                        .unwrap();
+                    while let Ok(line) = read_line(&mut r).await {
+                        let fields: Vec<&str> = line.split(' ').collect();
+                        match fields[0] {
+                            "PING" => w.write_all(b"PONG\r\n").await.unwrap(),
+                            "PUB" | "HPUB" => {
+                                let reply = fields[2];
+                                let mut body =
+                                    vec![0u8; fields.last().unwrap().parse::<usize>().unwrap() + 2];
+                                r.read_exact(&mut body).await.unwrap();
+                                seq += 1;
+                                let ack = format!(r#"{{"stream":"events","seq":{seq}}}"#);
+                                match replies.next().unwrap() {
+                                    Reply::Ack => {
+                                        w.write_all(
+                                            format!("MSG {reply} 1 {}\r\n{ack}\r\n", ack.len())
+                                                .as_bytes(),
+                                        )
+                                        .await
+                                        .unwrap();
+                                    }
+                                    Reply::AckInPieces => {
+                                        let stale =
+                                            format!("MSG {reply}0 1 {}\r\n{ack}\r\n", ack.len());
+                                        w.write_all(format!("PING\r\n{stale}").as_bytes())
+                                            .await
+                                            .unwrap();
+                                        let headers = "NATS/1.0\r\n\r\n";
+                                        let frame = format!(
+                                            "HMSG {reply} 1 {} {}\r\n{headers}{ack}\r\n",
+                                            headers.len(),
+                                            headers.len() + ack.len()
+                                        );
+                                        let (head, tail) = frame.split_at(frame.len() - 10);
+                                        w.write_all(head.as_bytes()).await.unwrap();
+                                        tokio::time::sleep(Duration::from_millis(50)).await;
+                                        w.write_all(tail.as_bytes()).await.unwrap();
+                                    }
+                                    Reply::NoResponders => {
+                                        let headers = "NATS/1.0 503\r\n\r\n";
+                                        let frame = format!(
+                                            "HMSG {reply} 1 {0} {0}\r\n{headers}\r\n",
+                                            headers.len()
+                                        );
+                                        w.write_all(frame.as_bytes()).await.unwrap();
+                                    }
+                                    Reply::Error => {
+                                        w.write_all(b"-ERR 'Permissions Violation'\r\n")
+                                            .await
+                                            .unwrap();
+                                        break;
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            });
+            url
+        }
+
+        #[tokio::test]
+        async fn routes_acks_and_reconnects() {
+            let replies = vec![
+                Reply::AckInPieces,
+                Reply::NoResponders,
+                Reply::Ack,
+                Reply::Error,
+                Reply::Ack,
+            ];
+            let url = mock_server(replies).await;
+            let sink = NatsSink::connect(&url, "escrow.events").await.unwrap();
+
+            let headers = [("Nats-Msg-Id".to_string(), "e1".to_string())];
+            sink.send_with_headers("k", b"{}", &headers).await.unwrap();
+            let err = sink.send("k", b"{}").await.unwrap_err();
+            assert!(format!("{err:#}").contains("503"), "{err:#}");
+            sink.send("k", b"{}").await.unwrap();
+
+            let err = sink.send("k", b"{}").await.unwrap_err();
+            assert!(
+                format!("{err:#}").contains("Permissions Violation"),
+                "{err:#}"
+            );
+            // the next send opens a new connection
+            sink.send("k", b"{}").await.unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what it delivers; fails every send while down.
+    #[derive(Clone, Default)]
+    struct Broker {
+        up: Arc<std::sync::atomic::AtomicBool>,
+        delivered: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Sink for Broker {
+        async fn send(&self, key: &str, payload: &[u8]) -> Result<()> {
+            if !self.up.load(Ordering::Relaxed) {
+                anyhow::bail!("broker down");
+            }
+            self.delivered
+                .lock()
+                .unwrap()
+                .push(format!("{key}={}", String::from_utf8_lossy(payload)));
+            Ok(())
+        }
+    }
+
+    fn spool_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("orderflow-spool-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    async fn delivered(broker: &Broker, n: usize) -> Vec<String> {
+        for _ in 0..200 {
+            if broker.delivered.lock().unwrap().len() >= n {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        broker.delivered.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn undelivered_records_survive_a_restart_and_replay_in_order() {
+        let dir = spool_dir("restart");
+        let broker = Broker::default();
+        let stats = Arc::new(SpoolStats::default());
+        let sink = SpoolingSink::open(Box::new(broker.clone()), &dir, stats.clone()).unwrap();
+        for (key, payload) in [("o1", "a"), ("o2", "b"), ("o1", "c")] {
+            // accepted without waiting for the broker
+            sink.send(key, payload.as_bytes()).await.unwrap();
+        }
+        assert_eq!(stats.spooled.load(Ordering::Relaxed), 3);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("spool.jsonl"))
+                .unwrap()
+                .lines()
+                .count(),
+            3
+        );
+        drop(sink);
+
+        // a new process finds the spool; records sent before it drains go behind it
+        broker.up.store(true, Ordering::Relaxed);
+        let stats = Arc::new(SpoolStats::default());
+        let sink = SpoolingSink::open(Box::new(broker.clone()), &dir, stats.clone()).unwrap();
+        assert_eq!(stats.spooled.load(Ordering::Relaxed), 3);
+        sink.send("o2", b"d").await.unwrap();
+        assert_eq!(
+            delivered(&broker, 4).await,
+            ["o1=a", "o2=b", "o1=c", "o2=d"]
+        );
+        assert_eq!(stats.spooled.load(Ordering::Relaxed), 0);
+        assert!(!dir.join("spool.jsonl").exists());
+
+        // with the spool empty, sends go straight through
+        sink.send("o3", b"e").await.unwrap();
+        assert_eq!(broker.delivered.lock().unwrap().last().unwrap(), "o3=e");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_stops_at_the_first_failure_and_keeps_the_rest() {
+        let dir = spool_dir("partial");
+        let broker = Broker::default();
+        let stats = Arc::new(SpoolStats::default());
+        let sink = SpoolingSink::open(Box::new(broker.clone()), &dir, stats.clone()).unwrap();
+        sink.send("o1", b"a").await.unwrap();
+        sink.send("o1", b"b").await.unwrap();
+
+        let replayed = sink.spool.drain().await.unwrap();
+        assert!(!replayed);
+        assert_eq!(stats.spooled.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("spool.jsonl"))
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+
+        broker.up.store(true, Ordering::Relaxed);
+        assert_eq!(delivered(&broker, 2).await, ["o1=a", "o1=b"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
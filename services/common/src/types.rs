@@ -0,0 +1,247 @@
+//! Validated string types of the event contract. Each keeps its JSON form (a string) but
+//! is checked when it is parsed or deserialized, so a malformed value fails at the edge
+//! instead of turning into a zero or an unmatched key further in.
+
+use anyhow::{bail, Context};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// A string schema; JSON Schema cannot check the decoded length, `pattern` approximates it.
+fn string_schema(description: &str, pattern: &str) -> Schema {
+    SchemaObject {
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.to_string()),
+            ..Default::default()
+        })),
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(pattern.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn check_base58(s: &str, len: usize, what: &str) -> anyhow::Result<()> {
+    let bytes = bs58::decode(s)
+        .into_vec()
+        .with_context(|| format!("{what} {s:?} is not base58"))?;
+    if bytes.len() != len {
+        bail!("{what} {s:?} is {} bytes, expected {len}", bytes.len());
+    }
+    Ok(())
+}
+
+macro_rules! base58_type {
+    ($(#[$doc:meta])* $name:ident, $len:expr, $what:expr, $pattern:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// The decoded bytes; the string was checked on construction.
+            pub fn to_bytes(&self) -> [u8; $len] {
+                let mut out = [0u8; $len];
+                bs58::decode(&self.0).into(&mut out).expect("validated on construction");
+                out
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> anyhow::Result<Self> {
+                check_base58(s, $len, $what)?;
+                Ok(Self(s.to_string()))
+            }
+        }
+
+        impl From<[u8; $len]> for $name {
+            fn from(bytes: [u8; $len]) -> Self {
+                Self(bs58::encode(bytes).into_string())
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = std::borrow::Cow::<str>::deserialize(deserializer)?;
+                s.parse().map_err(|e: anyhow::Error| serde::de::Error::custom(format!("{e:#}")))
+            }
+        }
+
+        impl JsonSchema for $name {
+            fn schema_name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                string_schema(&format!("base58 {}, {} bytes decoded", $what, $len), $pattern)
+            }
+        }
+    };
+}
+
+base58_type!(
+    /// A 32-byte account address, base58 as Solana prints it.
+    Pubkey58,
+    32,
+    "pubkey",
+    "^[1-9A-HJ-NP-Za-km-z]{32,44}$"
+);
+
+base58_type!(
+    /// A 64-byte transaction signature, base58 as Solana prints it.
+    Signature58,
+    64,
+    "signature",
+    "^[1-9A-HJ-NP-Za-km-z]{64,88}$"
+);
+
+/// A u64 written as a decimal string, so JavaScript consumers keep full precision.
+/// Only plain digits parse: no sign, whitespace, exponent or JSON number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct U64String(pub u64);
+
+impl U64String {
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for U64String {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("{s:?} is not a decimal u64");
+        }
+        let v = s
+            .parse()
+            .with_context(|| format!("{s:?} does not fit a u64"))?;
+        Ok(Self(v))
+    }
+}
+
+impl From<u64> for U64String {
+    fn from(v: u64) -> Self {
+        Self(v)
+    }
+}
+
+impl fmt::Display for U64String {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for U64String {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for U64String {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = std::borrow::Cow::<str>::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|e: anyhow::Error| serde::de::Error::custom(format!("{e:#}")))
+    }
+}
+
+impl JsonSchema for U64String {
+    fn schema_name() -> String {
+        "U64String".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        // 20 digits admits up to 99999999999999999999; u64::MAX has 20
+        string_schema("u64 as a decimal string", "^[0-9]{1,20}$")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn malformed_values_fail_to_deserialize() {
+        let key = "EeFX3g6HePRn5ipNu5ey3jiYWRVmeK3Frxec2ZwqKs6W";
+        let pk: Pubkey58 = serde_json::from_value(json!(key)).unwrap();
+        assert_eq!(pk, key);
+        assert_eq!(Pubkey58::from(pk.to_bytes()), pk);
+        assert_eq!(serde_json::to_value(&pk).unwrap(), json!(key));
+        for bad in [json!("maker"), json!("0OIl"), json!(""), json!(7)] {
+            assert!(
+                serde_json::from_value::<Pubkey58>(bad.clone()).is_err(),
+                "{bad}"
+            );
+        }
+        let sig = "4FpiGQPEkuh3GqVkbqievxrpE8w9mZkvVVmh8CksP2GQuedKf7xbmPaXTv31EnPqixdMxZjkvVuemDbBM9qHttoo";
+        assert!(serde_json::from_value::<Signature58>(json!(sig)).is_ok());
+        assert!(serde_json::from_value::<Signature58>(json!(key)).is_err());
+
+        let amount: U64String = serde_json::from_value(json!("18446744073709551615")).unwrap();
+        assert_eq!(amount.get(), u64::MAX);
+        assert_eq!(
+            serde_json::to_value(amount).unwrap(),
+            json!("18446744073709551615")
+        );
+        for bad in [
+            json!("18446744073709551616"),
+            json!("-1"),
+            json!("+1"),
+            json!("1e9"),
+            json!(" 1"),
+            json!(""),
+            json!(1),
+        ] {
+            assert!(
+                serde_json::from_value::<U64String>(bad.clone()).is_err(),
+                "{bad}"
+            );
+        }
+    }
+}
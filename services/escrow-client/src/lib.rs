@@ -0,0 +1,663 @@
+//! Client SDK for the escrow program.
+//!
+//! Account lists and instruction data are produced by the program crate's Anchor
+//! codegen (`escrow::accounts::*` / `escrow::instruction::*`), so a change to an
+//! `Accounts` struct or handler signature fails to compile here instead of
+//! producing a transaction the program rejects.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::{bpf_loader_upgradeable, system_program, sysvar};
+use anchor_lang::{
+    AccountDeserialize, AnchorDeserialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token::get_associated_token_address;
+
+pub use escrow::{
+    Config, DenylistEntry, EscrowState, EscrowStatus, Quote, QuoteStatus, Rfq, RfqStatus,
+    ID as PROGRAM_ID,
+};
+
+/// PDA derivation, mirroring the `seeds = [...]` constraints in the program.
+pub mod pda {
+    use super::*;
+
+    pub fn escrow_state(maker: &Pubkey, offer_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"escrow", maker.as_ref(), &offer_id.to_le_bytes()],
+            &PROGRAM_ID,
+        )
+    }
+
+    /// Vault ATA for token A, owned by the escrow PDA.
+    pub fn vault_ata(escrow_state: &Pubkey, mint_a: &Pubkey) -> Pubkey {
+        get_associated_token_address(escrow_state, mint_a)
+    }
+
+    pub fn config() -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"config"], &PROGRAM_ID)
+    }
+
+    pub fn deny_entry(wallet: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"deny", wallet.as_ref()], &PROGRAM_ID)
+    }
+
+    pub fn rfq(requester: &Pubkey, rfq_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"rfq", requester.as_ref(), &rfq_id.to_le_bytes()],
+            &PROGRAM_ID,
+        )
+    }
+
+    pub fn quote(rfq: &Pubkey, responder: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"quote", rfq.as_ref(), responder.as_ref()], &PROGRAM_ID)
+    }
+
+    /// Quote vault ATA for token A, owned by the quote PDA.
+    pub fn quote_vault(quote: &Pubkey, mint_a: &Pubkey) -> Pubkey {
+        get_associated_token_address(quote, mint_a)
+    }
+
+    pub fn program_data() -> Pubkey {
+        Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &bpf_loader_upgradeable::id()).0
+    }
+}
+
+/// Instruction builders. Every account other than the signers and mints is derived.
+pub mod ix {
+    use super::*;
+    use escrow::{accounts, instruction};
+
+    fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+
+    pub fn create_offer(
+        maker: &Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        offer_id: u64,
+        amount_a: u64,
+        amount_b: u64,
+        client_ref: [u8; 32],
+    ) -> Instruction {
+        let escrow_state = pda::escrow_state(maker, offer_id).0;
+        build(
+            accounts::CreateOffer {
+                maker: *maker,
+                mint_a: *mint_a,
+                mint_b: *mint_b,
+                escrow_state,
+                vault_ata: pda::vault_ata(&escrow_state, mint_a),
+                maker_ata_a: get_associated_token_address(maker, mint_a),
+                maker_deny: pda::deny_entry(maker).0,
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            },
+            instruction::CreateOffer {
+                offer_id,
+                amount_a,
+                amount_b,
+                client_ref,
+            },
+        )
+    }
+
+    pub fn take_offer(
+        taker: &Pubkey,
+        maker: &Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        offer_id: u64,
+    ) -> Instruction {
+        let escrow_state = pda::escrow_state(maker, offer_id).0;
+        build(
+            accounts::TakeOffer {
+                taker: *taker,
+                mint_a: *mint_a,
+                mint_b: *mint_b,
+                escrow_state,
+                maker: *maker,
+                vault_ata: pda::vault_ata(&escrow_state, mint_a),
+                taker_ata_a: get_associated_token_address(taker, mint_a),
+                taker_ata_b: get_associated_token_address(taker, mint_b),
+                maker_ata_b: get_associated_token_address(maker, mint_b),
+                maker_ata_a: get_associated_token_address(maker, mint_a),
+                maker_deny: pda::deny_entry(maker).0,
+                taker_deny: pda::deny_entry(taker).0,
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+            },
+            instruction::TakeOffer {},
+        )
+    }
+
+    pub fn cancel_offer(maker: &Pubkey, mint_a: &Pubkey, offer_id: u64) -> Instruction {
+        let escrow_state = pda::escrow_state(maker, offer_id).0;
+        build(
+            accounts::CancelOffer {
+                maker: *maker,
+                mint_a: *mint_a,
+                escrow_state,
+                vault_ata: pda::vault_ata(&escrow_state, mint_a),
+                maker_ata_a: get_associated_token_address(maker, mint_a),
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+            },
+            instruction::CancelOffer {},
+        )
+    }
+
+    /// Grows a version-1 offer to the current layout; `payer` covers the extra rent.
+    pub fn migrate_offer(payer: &Pubkey, maker: &Pubkey, offer_id: u64) -> Instruction {
+        build(
+            accounts::MigrateOffer {
+                payer: *payer,
+                escrow_state: pda::escrow_state(maker, offer_id).0,
+                system_program: system_program::ID,
+            },
+            instruction::MigrateOffer {},
+        )
+    }
+
+    /// Must be signed by the program's upgrade authority.
+    pub fn init_config(admin: &Pubkey) -> Instruction {
+        build(
+            accounts::InitConfig {
+                admin: *admin,
+                config: pda::config().0,
+                program: PROGRAM_ID,
+                program_data: pda::program_data(),
+                system_program: system_program::ID,
+            },
+            instruction::InitConfig {},
+        )
+    }
+
+    pub fn set_admin(admin: &Pubkey, new_admin: Pubkey) -> Instruction {
+        build(
+            accounts::AdminOnly {
+                admin: *admin,
+                config: pda::config().0,
+            },
+            instruction::SetAdmin { new_admin },
+        )
+    }
+
+    pub fn deny_wallet(admin: &Pubkey, wallet: Pubkey) -> Instruction {
+        build(
+            accounts::DenyWallet {
+                admin: *admin,
+                config: pda::config().0,
+                deny_entry: pda::deny_entry(&wallet).0,
+                system_program: system_program::ID,
+            },
+            instruction::DenyWallet { wallet },
+        )
+    }
+
+    pub fn allow_wallet(admin: &Pubkey, wallet: Pubkey) -> Instruction {
+        build(
+            accounts::AllowWallet {
+                admin: *admin,
+                config: pda::config().0,
+                deny_entry: pda::deny_entry(&wallet).0,
+            },
+            instruction::AllowWallet { wallet },
+        )
+    }
+
+    pub fn force_cancel(
+        admin: &Pubkey,
+        maker: &Pubkey,
+        mint_a: &Pubkey,
+        offer_id: u64,
+    ) -> Instruction {
+        let escrow_state = pda::escrow_state(maker, offer_id).0;
+        build(
+            accounts::ForceCancel {
+                admin: *admin,
+                config: pda::config().0,
+                mint_a: *mint_a,
+                escrow_state,
+                maker: *maker,
+                vault_ata: pda::vault_ata(&escrow_state, mint_a),
+                maker_ata_a: get_associated_token_address(maker, mint_a),
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+            },
+            instruction::ForceCancel {},
+        )
+    }
+
+    pub fn create_rfq(
+        requester: &Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        rfq_id: u64,
+        size: u64,
+    ) -> Instruction {
+        build(
+            accounts::CreateRfq {
+                requester: *requester,
+                mint_a: *mint_a,
+                mint_b: *mint_b,
+                rfq: pda::rfq(requester, rfq_id).0,
+                requester_deny: pda::deny_entry(requester).0,
+                system_program: system_program::ID,
+            },
+            instruction::CreateRfq { rfq_id, size },
+        )
+    }
+
+    pub fn submit_quote(
+        responder: &Pubkey,
+        requester: &Pubkey,
+        mint_a: &Pubkey,
+        rfq_id: u64,
+        amount_b: u64,
+    ) -> Instruction {
+        let rfq = pda::rfq(requester, rfq_id).0;
+        let quote = pda::quote(&rfq, responder).0;
+        build(
+            accounts::SubmitQuote {
+                responder: *responder,
+                rfq,
+                mint_a: *mint_a,
+                quote,
+                quote_vault: pda::quote_vault(&quote, mint_a),
+                responder_ata_a: get_associated_token_address(responder, mint_a),
+                responder_deny: pda::deny_entry(responder).0,
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            },
+            instruction::SubmitQuote { amount_b },
+        )
+    }
+
+    /// Accepts `responder`'s quote and refunds every quote in `refund_responders`
+    /// in the same transaction (mind the account limit; use `refund_quote` for the rest).
+    pub fn accept_quote(
+        requester: &Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        rfq_id: u64,
+        responder: &Pubkey,
+        refund_responders: &[Pubkey],
+    ) -> Instruction {
+        let rfq = pda::rfq(requester, rfq_id).0;
+        let quote = pda::quote(&rfq, responder).0;
+        let mut ix = build(
+            accounts::AcceptQuote {
+                requester: *requester,
+                rfq,
+                mint_a: *mint_a,
+                mint_b: *mint_b,
+                quote,
+                responder: *responder,
+                quote_vault: pda::quote_vault(&quote, mint_a),
+                requester_ata_a: get_associated_token_address(requester, mint_a),
+                requester_ata_b: get_associated_token_address(requester, mint_b),
+                responder_ata_a: get_associated_token_address(responder, mint_a),
+                responder_ata_b: get_associated_token_address(responder, mint_b),
+                requester_deny: pda::deny_entry(requester).0,
+                responder_deny: pda::deny_entry(responder).0,
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+            },
+            instruction::AcceptQuote {},
+        );
+        push_refunds(&mut ix, &rfq, mint_a, refund_responders);
+        ix
+    }
+
+    /// Closes an open RFQ, refunding every quote in `refund_responders` first; the cancel
+    /// fails if any other quote is still open.
+    pub fn cancel_rfq(
+        requester: &Pubkey,
+        mint_a: &Pubkey,
+        rfq_id: u64,
+        refund_responders: &[Pubkey],
+    ) -> Instruction {
+        let rfq = pda::rfq(requester, rfq_id).0;
+        let mut ix = build(
+            accounts::CancelRfq {
+                requester: *requester,
+                rfq,
+                token_program: anchor_spl::token::ID,
+            },
+            instruction::CancelRfq {},
+        );
+        push_refunds(&mut ix, &rfq, mint_a, refund_responders);
+        ix
+    }
+
+    /// `[quote, quote_vault, responder, responder_ata_a]` per responder, as remaining accounts.
+    fn push_refunds(ix: &mut Instruction, rfq: &Pubkey, mint_a: &Pubkey, responders: &[Pubkey]) {
+        for other in responders {
+            let other_quote = pda::quote(rfq, other).0;
+            ix.accounts.extend([
+                AccountMeta::new(other_quote, false),
+                AccountMeta::new(pda::quote_vault(&other_quote, mint_a), false),
+                AccountMeta::new(*other, false),
+                AccountMeta::new(get_associated_token_address(other, mint_a), false),
+            ]);
+        }
+    }
+
+    pub fn refund_quote(
+        caller: &Pubkey,
+        requester: &Pubkey,
+        mint_a: &Pubkey,
+        rfq_id: u64,
+        responder: &Pubkey,
+    ) -> Instruction {
+        let rfq = pda::rfq(requester, rfq_id).0;
+        let quote = pda::quote(&rfq, responder).0;
+        build(
+            accounts::RefundQuote {
+                caller: *caller,
+                rfq,
+                mint_a: *mint_a,
+                quote,
+                responder: *responder,
+                quote_vault: pda::quote_vault(&quote, mint_a),
+                responder_ata_a: get_associated_token_address(responder, mint_a),
+                token_program: anchor_spl::token::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+            },
+            instruction::RefundQuote {},
+        )
+    }
+}
+
+/// An offer instruction recognised from its raw data and account list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OfferIx {
+    Create {
+        escrow_state: Pubkey,
+        offer_id: u64,
+        amount_a: u64,
+        amount_b: u64,
+        client_ref: [u8; 32],
+    },
+    Take {
+        escrow_state: Pubkey,
+        taker: Pubkey,
+    },
+    Cancel {
+        escrow_state: Pubkey,
+    },
+    ForceCancel {
+        escrow_state: Pubkey,
+    },
+}
+
+/// Decode an executed offer instruction (e.g. from `getTransaction`). Account positions are
+/// located in a probe built by [`ix`], so they follow the `Accounts` structs.
+pub fn decode_offer_ix(data: &[u8], accounts: &[Pubkey]) -> Option<OfferIx> {
+    use escrow::instruction;
+
+    let (disc, mut args) = (data.get(..8)?, data.get(8..)?);
+    // placeholder keys; only their positions in the probe instruction matter
+    let key = |b: u8| Pubkey::new_from_array([b; 32]);
+    let (maker, taker, admin, mint) = (key(1), key(2), key(3), key(4));
+    let state = pda::escrow_state(&maker, 0).0;
+    let at = |probe: Instruction, key: &Pubkey| {
+        let i = probe.accounts.iter().position(|m| m.pubkey == *key)?;
+        accounts.get(i).copied()
+    };
+
+    if disc == instruction::CreateOffer::DISCRIMINATOR {
+        let a = instruction::CreateOffer::deserialize(&mut args).ok()?;
+        let probe = ix::create_offer(&maker, &mint, &mint, 0, 0, 0, [0; 32]);
+        Some(OfferIx::Create {
+            escrow_state: at(probe, &state)?,
+            offer_id: a.offer_id,
+            amount_a: a.amount_a,
+            amount_b: a.amount_b,
+            client_ref: a.client_ref,
+        })
+    } else if disc == instruction::TakeOffer::DISCRIMINATOR {
+        let probe = || ix::take_offer(&taker, &maker, &mint, &mint, 0);
+        Some(OfferIx::Take {
+            escrow_state: at(probe(), &state)?,
+            taker: at(probe(), &taker)?,
+        })
+    } else if disc == instruction::CancelOffer::DISCRIMINATOR {
+        let probe = ix::cancel_offer(&maker, &mint, 0);
+        Some(OfferIx::Cancel {
+            escrow_state: at(probe, &state)?,
+        })
+    } else if disc == instruction::ForceCancel::DISCRIMINATOR {
+        let probe = ix::force_cancel(&admin, &maker, &mint, 0);
+        Some(OfferIx::ForceCancel {
+            escrow_state: at(probe, &state)?,
+        })
+    } else {
+        None
+    }
+}
+
+/// Whether the instruction's handler prints an event the listener forwards
+/// (offer and RFQ flows and denylist changes; not the config instructions).
+pub fn emits_event(data: &[u8]) -> bool {
+    use escrow::instruction;
+
+    let Some(disc) = data.get(..8) else {
+        return false;
+    };
+    [
+        instruction::CreateOffer::DISCRIMINATOR,
+        instruction::TakeOffer::DISCRIMINATOR,
+        instruction::CancelOffer::DISCRIMINATOR,
+        instruction::ForceCancel::DISCRIMINATOR,
+        instruction::CreateRfq::DISCRIMINATOR,
+        instruction::SubmitQuote::DISCRIMINATOR,
+        instruction::AcceptQuote::DISCRIMINATOR,
+        instruction::RefundQuote::DISCRIMINATOR,
+        instruction::CancelRfq::DISCRIMINATOR,
+        instruction::DenyWallet::DISCRIMINATOR,
+        instruction::AllowWallet::DISCRIMINATOR,
+    ]
+    .iter()
+    .any(|d| d == disc)
+}
+
+/// Anchor discriminator and size of `EscrowState` accounts, for `getProgramAccounts` /
+/// `programSubscribe` filters. Offers created before `client_ref` are still
+/// `ESCROW_STATE_V1_LEN` bytes until someone sends `migrate_offer`, so filter on the
+/// discriminator rather than the size.
+pub const ESCROW_STATE_DISCRIMINATOR: [u8; 8] = EscrowState::DISCRIMINATOR;
+pub const ESCROW_STATE_LEN: usize = EscrowState::SPACE;
+pub const ESCROW_STATE_V1_LEN: usize = EscrowState::V1_SPACE;
+
+/// Decode raw `EscrowState` account data (checks the Anchor discriminator). Version-1
+/// accounts decode with a zero `client_ref`.
+pub fn decode_escrow_state(data: &[u8]) -> anchor_lang::Result<EscrowState> {
+    if data.len() == ESCROW_STATE_V1_LEN && data.get(8) == Some(&1) {
+        let mut padded = data.to_vec();
+        padded.resize(ESCROW_STATE_LEN, 0);
+        return EscrowState::try_deserialize(&mut padded.as_slice());
+    }
+    let mut buf = data;
+    EscrowState::try_deserialize(&mut buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+
+    fn state(version: u8) -> EscrowState {
+        EscrowState {
+            version,
+            status: EscrowStatus::Created as u8,
+            escrow_bump: 254,
+            _pad: [0; 5],
+            offer_id: 7,
+            maker: Pubkey::new_unique(),
+            taker: Pubkey::default(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            amount_a: 1_000,
+            amount_b: 2_500,
+            created_slot: 100,
+            filled_slot: 0,
+            cancelled_slot: 0,
+            client_ref: [0; 32],
+        }
+    }
+
+    fn keys(ix: &Instruction) -> Vec<Pubkey> {
+        ix.accounts.iter().map(|m| m.pubkey).collect()
+    }
+
+    #[test]
+    fn builders_decode_back() {
+        let (maker, taker, admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let escrow_state = pda::escrow_state(&maker, 42).0;
+
+        let create = ix::create_offer(&maker, &mint_a, &mint_b, 42, 1_000, 2_500, [7; 32]);
+        assert_eq!(
+            decode_offer_ix(&create.data, &keys(&create)),
+            Some(OfferIx::Create {
+                escrow_state,
+                offer_id: 42,
+                amount_a: 1_000,
+                amount_b: 2_500,
+                client_ref: [7; 32],
+            })
+        );
+        let take = ix::take_offer(&taker, &maker, &mint_a, &mint_b, 42);
+        assert_eq!(
+            decode_offer_ix(&take.data, &keys(&take)),
+            Some(OfferIx::Take {
+                escrow_state,
+                taker
+            })
+        );
+        let cancel = ix::cancel_offer(&maker, &mint_a, 42);
+        assert_eq!(
+            decode_offer_ix(&cancel.data, &keys(&cancel)),
+            Some(OfferIx::Cancel { escrow_state })
+        );
+        let force = ix::force_cancel(&admin, &maker, &mint_a, 42);
+        assert_eq!(
+            decode_offer_ix(&force.data, &keys(&force)),
+            Some(OfferIx::ForceCancel { escrow_state })
+        );
+        for ix in [&create, &take, &cancel, &force] {
+            assert!(emits_event(&ix.data));
+        }
+        let cancel_rfq = ix::cancel_rfq(&maker, &mint_a, 3, &[taker]);
+        assert_eq!(decode_offer_ix(&cancel_rfq.data, &keys(&cancel_rfq)), None);
+        assert!(emits_event(&cancel_rfq.data));
+        assert_eq!(cancel_rfq.accounts.len(), 3 + 4);
+
+        // not an offer instruction, or cut short
+        let deny = ix::deny_wallet(&admin, maker);
+        assert_eq!(decode_offer_ix(&deny.data, &keys(&deny)), None);
+        assert!(emits_event(&deny.data));
+        assert!(!emits_event(&ix::set_admin(&admin, maker).data));
+        assert_eq!(decode_offer_ix(&create.data[..20], &keys(&create)), None);
+        assert_eq!(decode_offer_ix(&take.data, &keys(&take)[..2]), None);
+    }
+
+    #[test]
+    fn pdas_match_the_program_seeds() {
+        let (maker, wallet, responder, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        // the seeds as written in the program's `#[account(seeds = ...)]` constraints
+        let check = |(key, bump): (Pubkey, u8), seeds: &[&[u8]]| {
+            let mut seeds = seeds.to_vec();
+            let bump = [bump];
+            seeds.push(&bump);
+            assert_eq!(
+                Pubkey::create_program_address(&seeds, &PROGRAM_ID).unwrap(),
+                key
+            );
+        };
+        let escrow_state = pda::escrow_state(&maker, 9);
+        check(
+            escrow_state,
+            &[b"escrow", maker.as_ref(), &9u64.to_le_bytes()],
+        );
+        check(pda::config(), &[b"config"]);
+        check(pda::deny_entry(&wallet), &[b"deny", wallet.as_ref()]);
+        let rfq = pda::rfq(&maker, 3);
+        check(rfq, &[b"rfq", maker.as_ref(), &3u64.to_le_bytes()]);
+        let quote = pda::quote(&rfq.0, &responder);
+        check(quote, &[b"quote", rfq.0.as_ref(), responder.as_ref()]);
+
+        assert_eq!(
+            pda::vault_ata(&escrow_state.0, &mint),
+            get_associated_token_address(&escrow_state.0, &mint)
+        );
+        assert_eq!(
+            pda::quote_vault(&quote.0, &mint),
+            get_associated_token_address(&quote.0, &mint)
+        );
+
+        // builders put the derived accounts where the `Accounts` structs expect them
+        let take = ix::take_offer(&wallet, &maker, &mint, &mint, 9);
+        assert_eq!(take.accounts[3].pubkey, escrow_state.0);
+        assert_eq!(
+            take.accounts[5].pubkey,
+            pda::vault_ata(&escrow_state.0, &mint)
+        );
+    }
+
+    #[test]
+    fn decodes_current_accounts_and_checks_the_discriminator() {
+        let mut data = Vec::new();
+        let mut st = state(2);
+        st.client_ref = [0xab; 32];
+        st.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), ESCROW_STATE_LEN);
+        let decoded = decode_escrow_state(&data).unwrap();
+        assert_eq!(
+            (decoded.version, decoded.maker, decoded.client_ref),
+            (2, st.maker, [0xab; 32])
+        );
+
+        let mut wrong = data.clone();
+        wrong[..8].copy_from_slice(&Rfq::DISCRIMINATOR);
+        assert!(decode_escrow_state(&wrong).is_err());
+        assert!(decode_escrow_state(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn decodes_version_1_accounts() {
+        let mut data = Vec::new();
+        state(1).try_serialize(&mut data).unwrap();
+        // what a version-1 program wrote: the same fields without client_ref
+        data.truncate(ESCROW_STATE_V1_LEN);
+        let st = decode_escrow_state(&data).unwrap();
+        assert_eq!((st.version, st.offer_id, st.amount_b), (1, 7, 2_500));
+        assert_eq!(st.client_ref, [0; 32]);
+
+        // a truncated current account is not mistaken for version 1
+        let mut data = Vec::new();
+        state(2).try_serialize(&mut data).unwrap();
+        data.truncate(ESCROW_STATE_V1_LEN);
+        assert!(decode_escrow_state(&data).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use anyhow::{Context, Result};
+use escrow_client::{decode_escrow_state, EscrowState, ESCROW_STATE_DISCRIMINATOR};
+use futures::StreamExt;
+use orderflow_common::sink::Sink;
+use orderflow_common::{now_ms, OfferStateEvent, OfferStatus};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::backoff::Backoff;
+use crate::metrics::Metrics;
+use crate::parse::IngestCtx;
+
+/// How often the sink is flushed; the spool replays in the background on its own backoff.
+const SPOOL_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A session that stayed up this long resets the reconnect backoff.
+const STABLE_SESSION: Duration = Duration::from_secs(60);
+
+/// What the per-endpoint `programSubscribe` tasks feed into the account loop.
+#[derive(Debug)]
+enum AccountFeed {
+    Up(usize),
+    Down(usize),
+    Update {
+        slot: u64,
+        address: String,
+        account: Account,
+    },
+}
+
+/// Last status seen per `EscrowState` account.
+#[derive(Debug, Default)]
+pub struct OfferTracker {
+    known: HashMap<String, OfferStatus>,
+}
+
+impl OfferTracker {
+    /// A record when `state` shows the account in a status not reported yet. Statuses only
+    /// move forward (`Created` -> `Filled`/`Cancelled`), so stale, repeated or
+    /// cross-endpoint duplicate notifications yield nothing.
+    pub fn observe(
+        &mut self,
+        ctx: &IngestCtx,
+        address: &str,
+        observed_slot: u64,
+        state: &EscrowState,
+    ) -> Option<OfferStateEvent> {
+        let status = match state.status {
+            0 => OfferStatus::Created,
+            1 => OfferStatus::Filled,
+            2 => OfferStatus::Cancelled,
+            _ => return None,
+        };
+        let previous = self.known.get(address).copied();
+        if previous.is_some_and(|prev| prev >= status) {
+            return None;
+        }
+        self.known.insert(address.to_string(), status);
+
+        let slot = match status {
+            OfferStatus::Created => state.created_slot,
+            OfferStatus::Filled => state.filled_slot,
+            OfferStatus::Cancelled => state.cancelled_slot,
+        };
+        Some(OfferStateEvent {
+            state_id: format!("{address}:{status:?}"),
+            event_type: status.event_type(),
+            status,
+            previous_status: previous,
+            cluster: ctx.cluster.clone(),
+            program_id: ctx.program_id.clone(),
+            escrow_state: address.to_string(),
+            offer_id: state.offer_id.to_string(),
+            maker: state.maker.to_bytes().into(),
+            taker: (state.taker != Pubkey::default()).then(|| state.taker.to_bytes().into()),
+            mint_a: state.mint_a.to_bytes().into(),
+            mint_b: state.mint_b.to_bytes().into(),
+            amount_a: state.amount_a.into(),
+            amount_b: state.amount_b.into(),
+            client_ref: Some(hex32(&state.client_ref)),
+            slot,
+            observed_slot,
+            commitment: ctx.commitment.clone(),
+            ts_ingest_ms: now_ms(),
+        })
+    }
+
+    /// Drops what is known about `address` so the next snapshot reports it again.
+    pub fn forget(&mut self, address: &str) {
+        self.known.remove(address);
+    }
+}
+
+/// Same encoding as the program's log payloads.
+pub fn hex32(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Only `EscrowState` accounts, by Anchor discriminator; not by size, which differs for
+/// version-1 offers that were never migrated.
+fn escrow_state_config(commitment: CommitmentConfig) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            ESCROW_STATE_DISCRIMINATOR.to_vec(),
+        ))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(commitment),
+            ..Default::default()
+        },
+        with_context: Some(true),
+    }
+}
+
+/// Account-state mode: `programSubscribe` on every WS endpoint plus a `getProgramAccounts`
+/// snapshot whenever coverage (re)starts, diffed into `OfferStateEvent`s. Independent of
+/// logs, so it keeps working when logs are truncated.
+pub async fn run(
+    ctx: &IngestCtx,
+    ws_urls: &[String],
+    rpc_url: &str,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    sink: &dyn Sink,
+    metrics: &Metrics,
+) -> Result<()> {
+    let rpc = RpcClient::new_with_commitment(rpc_url.to_string(), commitment);
+    let (feed_tx, mut feed) = mpsc::channel(1024);
+    for (idx, url) in ws_urls.iter().enumerate() {
+        tokio::spawn(run_ws(
+            idx,
+            url.clone(),
+            program_id,
+            commitment,
+            feed_tx.clone(),
+        ));
+    }
+    drop(feed_tx);
+
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    let mut tracker = OfferTracker::default();
+    let mut connected = vec![false; ws_urls.len()];
+    let mut needs_snapshot = true;
+    let mut snapshot_backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(30));
+    let mut snapshot_at = Instant::now();
+    let mut down_since: Option<Instant> = None;
+    let mut spool_flush = tokio::time::interval(SPOOL_FLUSH_INTERVAL);
+    let mut retry = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        let up = connected.iter().filter(|c| **c).count();
+        metrics.ws_connected.store(up as u64, Ordering::Relaxed);
+
+        // subscribed first, snapshot second: a change in between shows up in both and the
+        // tracker reports it once
+        if needs_snapshot && up > 0 && Instant::now() >= snapshot_at {
+            match snapshot(
+                ctx,
+                &rpc,
+                program_id,
+                commitment,
+                &mut tracker,
+                sink,
+                metrics,
+            )
+            .await
+            {
+                Ok(n) => {
+                    eprintln!("account snapshot: {n} escrow accounts");
+                    needs_snapshot = false;
+                    snapshot_backoff.reset();
+                }
+                Err(e) => {
+                    let delay = snapshot_backoff.next_delay();
+                    eprintln!("account snapshot failed, retrying in {delay:?}: {e:?}");
+                    snapshot_at = Instant::now() + delay;
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = &mut shutdown => {
+                eprintln!("shutdown requested");
+                break;
+            }
+            maybe_feed = feed.recv() => {
+                let Some(msg) = maybe_feed else { break; };
+                match msg {
+                    AccountFeed::Up(idx) => {
+                        eprintln!("program subscription up: {}", ws_urls[idx]);
+                        connected[idx] = true;
+                        if let Some(since) = down_since.take() {
+                            metrics.record_reconnect(since.elapsed());
+                        }
+                    }
+                    AccountFeed::Down(idx) => {
+                        eprintln!("program subscription down: {}", ws_urls[idx]);
+                        connected[idx] = false;
+                        if connected.iter().all(|c| !c) {
+                            down_since.get_or_insert_with(Instant::now);
+                            needs_snapshot = true;
+                        }
+                    }
+                    AccountFeed::Update { slot, address, account } => {
+                        metrics.account_updates_total.fetch_add(1, Ordering::Relaxed);
+                        let state = match decode_escrow_state(&account.data) {
+                            Ok(state) => state,
+                            Err(e) => {
+                                eprintln!("undecodable escrow account {address}: {e}");
+                                continue;
+                            }
+                        };
+                        if let Some(ev) = tracker.observe(ctx, &address, slot, &state) {
+                            if let Err(e) = publish(sink, metrics, &ev).await {
+                                eprintln!("publish offer state failed, resyncing: {e:?}");
+                                tracker.forget(&address);
+                                needs_snapshot = true;
+                            }
+                        }
+                    }
+                }
+            }
+            // wakes the loop to retry a failed snapshot
+            _ = retry.tick() => {}
+            _ = spool_flush.tick() => {
+                if let Err(e) = sink.flush().await {
+                    eprintln!("spool flush failed: {e:?}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Full re-read of every escrow account; reports whatever the tracker has not seen.
+async fn snapshot(
+    ctx: &IngestCtx,
+    rpc: &RpcClient,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    tracker: &mut OfferTracker,
+    sink: &dyn Sink,
+    metrics: &Metrics,
+) -> Result<usize> {
+    // the response's own context slot is dropped by the client; this is a lower bound
+    let slot = rpc.get_slot().await.context("getSlot")?;
+    let accounts = rpc
+        .get_program_accounts_with_config(&program_id, escrow_state_config(commitment))
+        .await
+        .context("getProgramAccounts")?;
+    let n = accounts.len();
+    for (address, account) in accounts {
+        let Ok(state) = decode_escrow_state(&account.data) else {
+            continue;
+        };
+        let address = address.to_string();
+        if let Some(ev) = tracker.observe(ctx, &address, slot, &state) {
+            if let Err(e) = publish(sink, metrics, &ev).await {
+                tracker.forget(&address);
+                return Err(e);
+            }
+        }
+    }
+    Ok(n)
+}
+
+async fn publish(sink: &dyn Sink, metrics: &Metrics, ev: &OfferStateEvent) -> Result<()> {
+    let payload = serde_json::to_string(ev).context("serialize offer state")?;
+    sink.send(&ev.offer_id, payload.as_bytes()).await?;
+    metrics
+        .offer_states_published_total
+        .fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// One WS endpoint's `programSubscribe`, reconnected with jittered backoff until the
+/// account loop goes away.
+async fn run_ws(
+    idx: usize,
+    url: String,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    tx: mpsc::Sender<AccountFeed>,
+) {
+    let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(30));
+    while !tx.is_closed() {
+        let started = Instant::now();
+        let mut up = false;
+        match session(idx, &url, program_id, commitment, &tx, &mut up).await {
+            Ok(()) => eprintln!("[{url}] program stream closed"),
+            Err(e) => eprintln!("[{url}] session failed: {e:#}"),
+        }
+        if up {
+            let _ = tx.send(AccountFeed::Down(idx)).await;
+        }
+        if started.elapsed() >= STABLE_SESSION {
+            backoff.reset();
+        }
+        let delay = backoff.next_delay();
+        eprintln!("[{url}] reconnecting in {delay:?}");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn session(
+    idx: usize,
+    url: &str,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    tx: &mpsc::Sender<AccountFeed>,
+    up: &mut bool,
+) -> Result<()> {
+    let client = PubsubClient::new(url).await.context("pubsub connect")?;
+    let (mut updates, _unsub) = client
+        .program_subscribe(&program_id, Some(escrow_state_config(commitment)))
+        .await
+        .context("program_subscribe")?;
+    tx.send(AccountFeed::Up(idx))
+        .await
+        .context("account loop gone")?;
+    *up = true;
+
+    while let Some(resp) = updates.next().await {
+        let Some(account) = resp.value.account.decode::<Account>() else {
+            continue;
+        };
+        let feed = AccountFeed::Update {
+            slot: resp.context.slot,
+            address: resp.value.pubkey,
+            account,
+        };
+        tx.send(feed).await.context("account loop gone")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use escrow_client::ESCROW_STATE_LEN;
+    use orderflow_common::schema;
+
+    fn ctx() -> IngestCtx {
+        IngestCtx {
+            cluster: "localnet".into(),
+            program_id: escrow_client::PROGRAM_ID.to_bytes().into(),
+            commitment: "finalized".into(),
+        }
+    }
+
+    fn state(status: u8) -> EscrowState {
+        EscrowState {
+            version: 2,
+            status,
+            escrow_bump: 255,
+            _pad: [0; 5],
+            offer_id: 7,
+            maker: Pubkey::new_unique(),
+            taker: if status == 1 {
+                Pubkey::new_unique()
+            } else {
+                Pubkey::default()
+            },
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            amount_a: 1_000,
+            amount_b: 2_500,
+            created_slot: 100,
+            filled_slot: if status == 1 { 120 } else { 0 },
+            cancelled_slot: if status == 2 { 130 } else { 0 },
+            client_ref: [0xab; 32],
+        }
+    }
+
+    #[test]
+    fn reports_each_status_once_and_ignores_stale_updates() {
+        let mut tracker = OfferTracker::default();
+        let created = tracker.observe(&ctx(), "A", 101, &state(0)).unwrap();
+        assert_eq!(created.state_id, "A:Created");
+        assert_eq!(created.slot, 100);
+        assert_eq!(created.previous_status, None);
+        assert_eq!(created.taker, None);
+        assert_eq!(
+            created.client_ref.as_deref(),
+            Some("ab".repeat(32).as_str())
+        );
+
+        // the same state from a second endpoint or a resync snapshot
+        assert!(tracker.observe(&ctx(), "A", 102, &state(0)).is_none());
+
+        let filled = tracker.observe(&ctx(), "A", 121, &state(1)).unwrap();
+        assert_eq!(filled.event_type, orderflow_common::EventType::OfferFilled);
+        assert_eq!(filled.previous_status, Some(OfferStatus::Created));
+        assert_eq!((filled.slot, filled.observed_slot), (120, 121));
+        assert!(filled.taker.is_some());
+        for ev in [&created, &filled] {
+            schema::validate(schema::OFFER_STATES, &serde_json::to_value(ev).unwrap()).unwrap();
+        }
+
+        // a notification delayed past the fill cannot move the offer back
+        assert!(tracker.observe(&ctx(), "A", 103, &state(0)).is_none());
+    }
+
+    #[test]
+    fn snapshot_of_settled_offer_reports_final_status_only() {
+        let mut tracker = OfferTracker::default();
+        let ev = tracker.observe(&ctx(), "B", 500, &state(2)).unwrap();
+        assert_eq!(ev.state_id, "B:Cancelled");
+        assert_eq!(ev.previous_status, None);
+        assert_eq!(ev.slot, 130);
+    }
+
+    #[test]
+    fn decodes_account_bytes_matching_the_filters() {
+        use anchor_lang::AccountSerialize;
+        let mut data = Vec::new();
+        state(0).try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), ESCROW_STATE_LEN);
+        assert_eq!(data[..8], ESCROW_STATE_DISCRIMINATOR);
+        assert_eq!(decode_escrow_state(&data).unwrap().offer_id, 7);
+    }
+}
@@ -0,0 +1,384 @@
+use anyhow::{bail, Context, Result};
+use escrow_client::{decode_escrow_state, EscrowState};
+use orderflow_common::{NormalizedEvent, Pubkey58};
+use solana_client::client_error::ClientErrorKind;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::bs58;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedTransaction, UiInstruction, UiMessage, UiTransactionEncoding,
+};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::fees::priority_fee;
+use crate::finality::TxFinality;
+use crate::parse::{Ix, IxLayout, OuterIx};
+
+/// getSignaturesForAddress page size (RPC maximum).
+const PAGE_LIMIT: usize = 1000;
+
+/// getSignatureStatuses accepts at most this many signatures per call.
+const STATUS_LIMIT: usize = 256;
+
+/// How far below a known signature's slot a scan for what followed it may page back.
+/// Bounds the scan when `until` never matches: the signature was on a dropped fork, or
+/// the node has pruned it from its history.
+const CURSOR_SLOT_MARGIN: u64 = 150;
+
+/// Block times remembered, newest slots kept.
+const BLOCK_TIME_CACHE: usize = 4096;
+
+/// HTTP RPC access: getSignaturesForAddress for backfill, getTransaction for every
+/// ingested transaction (the WS notification carries no instruction layout).
+pub struct Backfiller {
+    rpc: RpcClient,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    /// slot -> unix seconds, from getTransaction and getBlockTime
+    block_times: Mutex<BTreeMap<u64, i64>>,
+}
+
+/// What the parser needs from one successful transaction.
+#[derive(Debug, Clone)]
+pub struct FetchedTx {
+    pub slot: u64,
+    pub logs: Vec<String>,
+    pub layout: IxLayout,
+    pub info: TxInfo,
+}
+
+/// Timing and cost of one transaction, stamped onto each of its events.
+#[derive(Debug, Clone, Default)]
+pub struct TxInfo {
+    pub block_time: Option<i64>,
+    pub fee: Option<u64>,
+    pub fee_payer: Option<Pubkey58>,
+    pub compute_units: Option<u64>,
+    pub priority_fee: Option<u64>,
+}
+
+impl TxInfo {
+    /// From transaction metadata; the block time is filled in separately.
+    pub fn new(
+        fee: u64,
+        fee_payer: Option<&String>,
+        compute_units: Option<u64>,
+        layout: &IxLayout,
+    ) -> Self {
+        Self {
+            block_time: None,
+            fee: Some(fee),
+            fee_payer: fee_payer.and_then(|k| k.parse().ok()),
+            compute_units,
+            priority_fee: Some(priority_fee(layout)),
+        }
+    }
+
+    pub fn stamp(&self, ev: &mut NormalizedEvent) {
+        ev.block_time = self.block_time;
+        ev.fee_lamports = self.fee;
+        ev.fee_payer = self.fee_payer.clone();
+        ev.compute_units_consumed = self.compute_units;
+        ev.priority_fee_lamports = self.priority_fee;
+    }
+}
+
+/// Where to stop paging backwards through the program's signature history.
+#[derive(Debug, Clone, Default)]
+pub struct Range {
+    /// exclusive lower bound: the last signature already processed
+    pub until: Option<String>,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+}
+
+impl Range {
+    /// Everything after `signature`, processed at `slot`.
+    pub fn after(signature: String, slot: u64) -> Self {
+        Self {
+            until: Some(signature),
+            from_slot: Some(slot.saturating_sub(CURSOR_SLOT_MARGIN)),
+            to_slot: None,
+        }
+    }
+}
+
+impl Backfiller {
+    pub fn new(rpc_url: &str, program_id: Pubkey, commitment: CommitmentConfig) -> Self {
+        // getSignaturesForAddress/getTransaction reject `processed`
+        let commitment = if commitment.is_at_least_confirmed() {
+            commitment
+        } else {
+            CommitmentConfig::confirmed()
+        };
+        Self {
+            rpc: RpcClient::new_with_commitment(rpc_url.to_string(), commitment),
+            program_id,
+            commitment,
+            block_times: Mutex::default(),
+        }
+    }
+
+    /// Newest signature touching the program, used as the initial checkpoint baseline.
+    pub async fn latest(&self) -> Result<Option<(String, u64)>> {
+        let page = self
+            .rpc
+            .get_signatures_for_address_with_config(
+                &self.program_id,
+                GetConfirmedSignaturesForAddress2Config {
+                    limit: Some(1),
+                    commitment: Some(self.commitment),
+                    ..Default::default()
+                },
+            )
+            .await
+            .context("getSignaturesForAddress")?;
+        Ok(page.into_iter().next().map(|s| (s.signature, s.slot)))
+    }
+
+    /// Successful transactions touching the program within `range`, oldest first.
+    pub async fn signatures(&self, range: &Range) -> Result<Vec<(String, u64)>> {
+        let mut out: Vec<_> = self
+            .history(range)
+            .await?
+            .into_iter()
+            .filter(|s| s.err.is_none())
+            .map(|s| (s.signature, s.slot))
+            .collect();
+        out.reverse();
+        Ok(out)
+    }
+
+    /// Every transaction touching the program within `range`, failed ones included,
+    /// newest first.
+    pub async fn history(
+        &self,
+        range: &Range,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let until = range
+            .until
+            .as_deref()
+            .map(str::parse::<Signature>)
+            .transpose()
+            .context("parse checkpoint signature")?;
+
+        let mut out = Vec::new();
+        let mut before: Option<Signature> = None;
+        'pages: loop {
+            let page = self
+                .rpc
+                .get_signatures_for_address_with_config(
+                    &self.program_id,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(PAGE_LIMIT),
+                        commitment: Some(self.commitment),
+                    },
+                )
+                .await
+                .context("getSignaturesForAddress")?;
+
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(last.signature.parse().context("parse signature")?);
+            let full_page = page.len() == PAGE_LIMIT;
+
+            // newest first
+            for s in page {
+                if range.to_slot.is_some_and(|to| s.slot > to) {
+                    continue;
+                }
+                if range.from_slot.is_some_and(|from| s.slot < from) {
+                    break 'pages;
+                }
+                out.push(s);
+            }
+
+            if !full_page {
+                break;
+            }
+        }
+        Ok(out)
+    }
+
+    /// The cluster's latest root, for sources that cannot subscribe to roots.
+    pub async fn finalized_slot(&self) -> Result<u64> {
+        self.rpc
+            .get_slot_with_commitment(CommitmentConfig::finalized())
+            .await
+            .context("getSlot")
+    }
+
+    /// Logs and instruction layout of one transaction; `None` if it failed or the node no
+    /// longer has its metadata.
+    pub async fn fetch_tx(&self, signature: &str) -> Result<Option<FetchedTx>> {
+        let sig: Signature = signature.parse().context("parse signature")?;
+        let tx = self
+            .rpc
+            .get_transaction_with_config(
+                &sig,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Json),
+                    commitment: Some(self.commitment),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await
+            .with_context(|| format!("getTransaction {signature}"))?;
+
+        let slot = tx.slot;
+        if let Some(block_time) = tx.block_time {
+            self.remember_block_time(slot, block_time);
+        }
+        let Some(meta) = tx.transaction.meta else {
+            return Ok(None);
+        };
+        if meta.err.is_some() {
+            return Ok(None);
+        }
+        // a node that keeps no logs is treated like truncation: events are rebuilt
+        let logs = match meta.log_messages {
+            OptionSerializer::Some(logs) => logs,
+            _ => Vec::new(),
+        };
+        let EncodedTransaction::Json(ui_tx) = tx.transaction.transaction else {
+            bail!("getTransaction {signature}: unexpected encoding");
+        };
+        let UiMessage::Raw(message) = ui_tx.message else {
+            bail!("getTransaction {signature}: unexpected parsed message");
+        };
+
+        let fee_payer = message.account_keys.first().cloned();
+        // v0 messages index into static keys followed by loaded writable, then readonly
+        let mut keys = message.account_keys;
+        if let OptionSerializer::Some(loaded) = meta.loaded_addresses {
+            keys.extend(loaded.writable);
+            keys.extend(loaded.readonly);
+        }
+        let key = |i: u8| {
+            keys.get(i as usize).cloned().with_context(|| {
+                format!("getTransaction {signature}: account index {i} out of range")
+            })
+        };
+        let ix = |program_id_index: u8, accounts: &[u8], data: &str| -> Result<Ix> {
+            Ok(Ix {
+                program_id: key(program_id_index)?,
+                accounts: accounts.iter().map(|&i| key(i)).collect::<Result<_>>()?,
+                data: bs58::decode(data)
+                    .into_vec()
+                    .with_context(|| format!("getTransaction {signature}: instruction data"))?,
+            })
+        };
+
+        let mut outer = message
+            .instructions
+            .iter()
+            .map(|i| {
+                Ok(OuterIx {
+                    ix: ix(i.program_id_index, &i.accounts, &i.data)?,
+                    inner: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let OptionSerializer::Some(inner) = meta.inner_instructions {
+            for group in inner {
+                let Some(parent) = outer.get_mut(group.index as usize) else {
+                    continue;
+                };
+                for i in group.instructions {
+                    let UiInstruction::Compiled(i) = i else {
+                        bail!("getTransaction {signature}: unexpected parsed inner instruction");
+                    };
+                    parent
+                        .inner
+                        .push(ix(i.program_id_index, &i.accounts, &i.data)?);
+                }
+            }
+        }
+
+        let layout = IxLayout { outer };
+        let compute_units = match meta.compute_units_consumed {
+            OptionSerializer::Some(units) => Some(units),
+            _ => None,
+        };
+        let mut info = TxInfo::new(meta.fee, fee_payer.as_ref(), compute_units, &layout);
+        info.block_time = tx.block_time;
+        Ok(Some(FetchedTx {
+            slot,
+            logs,
+            layout,
+            info,
+        }))
+    }
+
+    /// Unix time of `slot`'s block, cached; `None` if the node has no time for it.
+    pub async fn block_time(&self, slot: u64) -> Result<Option<i64>> {
+        if let Some(&t) = self.block_times.lock().unwrap().get(&slot) {
+            return Ok(Some(t));
+        }
+        let t = match self.rpc.get_block_time(slot).await {
+            Ok(t) => t,
+            // skipped slot or not yet available
+            Err(e) if matches!(e.kind(), ClientErrorKind::RpcError(_)) => return Ok(None),
+            Err(e) => return Err(e).context("getBlockTime"),
+        };
+        self.remember_block_time(slot, t);
+        Ok(Some(t))
+    }
+
+    fn remember_block_time(&self, slot: u64, t: i64) {
+        let mut cache = self.block_times.lock().unwrap();
+        cache.insert(slot, t);
+        if cache.len() > BLOCK_TIME_CACHE {
+            cache.pop_first();
+        }
+    }
+
+    /// Current `EscrowState` of each address, in input order; `None` if missing or not one.
+    pub async fn escrow_states(&self, addresses: &[Pubkey]) -> Result<Vec<Option<EscrowState>>> {
+        let accounts = self
+            .rpc
+            .get_multiple_accounts(addresses)
+            .await
+            .context("getMultipleAccounts")?;
+        Ok(accounts
+            .into_iter()
+            .map(|a| a.and_then(|a| decode_escrow_state(&a.data).ok()))
+            .collect())
+    }
+
+    /// Finality of each signature, in input order.
+    pub async fn tx_finality(&self, signatures: &[String]) -> Result<Vec<TxFinality>> {
+        let sigs = signatures
+            .iter()
+            .map(|s| s.parse::<Signature>())
+            .collect::<Result<Vec<_>, _>>()
+            .context("parse signature")?;
+
+        let mut out = Vec::with_capacity(sigs.len());
+        for chunk in sigs.chunks(STATUS_LIMIT) {
+            let statuses = self
+                .rpc
+                .get_signature_statuses_with_history(chunk)
+                .await
+                .context("getSignatureStatuses")?
+                .value;
+            out.extend(statuses.into_iter().map(|status| match status {
+                // `confirmations: None` means rooted
+                Some(st) if st.confirmations.is_none() => TxFinality::Finalized,
+                Some(st) => TxFinality::Pending(st.slot),
+                None => TxFinality::Dropped,
+            }));
+        }
+        Ok(out)
+    }
+}
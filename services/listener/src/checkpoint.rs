@@ -0,0 +1,32 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::finality::PendingEvent;
+
+/// Last transaction the listener fully processed (all its events handed to Kafka).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub slot: u64,
+    pub signature: String,
+    /// events published below `finalized` still awaiting a follow-up; the checkpoint moves
+    /// past their transactions, so a restart resumes tracking them from here
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<PendingEvent>,
+}
+
+pub fn load(path: &Path) -> Result<Option<Checkpoint>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => Ok(Some(serde_json::from_str(&s).context("parse checkpoint")?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("read checkpoint"),
+    }
+}
+
+/// Write-then-rename so a crash never leaves a torn checkpoint behind.
+pub fn store(path: &Path, cp: &Checkpoint) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec(cp)?).context("write checkpoint")?;
+    std::fs::rename(&tmp, path).context("rename checkpoint")?;
+    Ok(())
+}
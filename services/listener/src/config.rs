@@ -0,0 +1,152 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// `--config` file: several independent log pipelines in one process.
+///
+/// ```toml
+/// [[source]]
+/// name = "devnet"
+/// cluster = "devnet"
+/// ws_url = ["wss://api.devnet.solana.com"]
+/// rpc_url = "https://api.devnet.solana.com"
+/// program_id = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk"
+/// commitment = "confirmed"
+/// topic = "escrow.events.devnet.v1"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(rename = "source")]
+    pub sources: Vec<SourceConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceConfig {
+    /// unique; the `source` metrics label and the name of its checkpoint, spool and jsonl
+    /// directory
+    pub name: String,
+    pub cluster: String,
+    pub ws_url: Vec<String>,
+    pub rpc_url: String,
+    #[serde(deserialize_with = "base58_pubkey")]
+    pub program_id: Pubkey,
+    #[serde(default = "default_commitment")]
+    pub commitment: String,
+    /// Kafka topic / NATS subject; defaults to KAFKA_TOPIC / NATS_SUBJECT
+    pub topic: Option<String>,
+}
+
+fn default_commitment() -> String {
+    "finalized".to_string()
+}
+
+fn base58_pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse()
+        .map_err(|e| serde::de::Error::custom(format!("program_id {s:?}: {e}")))
+}
+
+pub fn load(path: &Path) -> Result<Vec<SourceConfig>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let config: Config =
+        toml::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+    validate(&config.sources)?;
+    Ok(config.sources)
+}
+
+fn validate(sources: &[SourceConfig]) -> Result<()> {
+    if sources.is_empty() {
+        bail!("config: no [[source]] entries");
+    }
+    let mut names = HashSet::new();
+    for s in sources {
+        let valid_name = !s.name.is_empty()
+            && s.name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+        if !valid_name {
+            bail!(
+                "config: source name {:?} must be non-empty [A-Za-z0-9._-]",
+                s.name
+            );
+        }
+        if !names.insert(s.name.as_str()) {
+            bail!("config: duplicate source name {:?}", s.name);
+        }
+        if !["processed", "confirmed", "finalized"].contains(&s.commitment.as_str()) {
+            bail!(
+                "config: source {:?}: unknown commitment {:?}",
+                s.name,
+                s.commitment
+            );
+        }
+        if s.ws_url.is_empty() || s.ws_url.len() > 64 {
+            bail!(
+                "config: source {:?}: expected 1..=64 ws_url entries",
+                s.name
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_SOURCES: &str = r#"
+        [[source]]
+        name = "devnet"
+        cluster = "devnet"
+        ws_url = ["wss://a.example", "wss://b.example"]
+        rpc_url = "https://a.example"
+        program_id = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk"
+        topic = "escrow.events.devnet.v1"
+
+        [[source]]
+        name = "mainnet-v2"
+        cluster = "mainnet-beta"
+        ws_url = ["wss://m.example"]
+        rpc_url = "https://m.example"
+        program_id = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        commitment = "confirmed"
+    "#;
+
+    #[test]
+    fn parses_sources_with_defaults() {
+        let config: Config = toml::from_str(TWO_SOURCES).unwrap();
+        validate(&config.sources).unwrap();
+        let [devnet, mainnet] = &config.sources[..] else {
+            panic!("{config:?}")
+        };
+        assert_eq!(devnet.ws_url.len(), 2);
+        assert_eq!(devnet.commitment, "finalized");
+        assert_eq!(devnet.topic.as_deref(), Some("escrow.events.devnet.v1"));
+        assert_eq!(mainnet.commitment, "confirmed");
+        assert_eq!(mainnet.topic, None);
+        assert_eq!(
+            mainnet.program_id.to_string(),
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_names_and_bad_fields() {
+        let config: Config = toml::from_str(&TWO_SOURCES.replace("mainnet-v2", "devnet")).unwrap();
+        assert!(validate(&config.sources).is_err());
+
+        let config: Config =
+            toml::from_str(&TWO_SOURCES.replace("\"confirmed\"", "\"recent\"")).unwrap();
+        assert!(validate(&config.sources).is_err());
+
+        let config: Config = toml::from_str(&TWO_SOURCES.replace("mainnet-v2", "../etc")).unwrap();
+        assert!(validate(&config.sources).is_err());
+
+        assert!(toml::from_str::<Config>(&TWO_SOURCES.replace("topic", "topik")).is_err());
+        assert!(toml::from_str::<Config>(&TWO_SOURCES.replace("Tokenkeg", "0okenkeg")).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Seen {
+    inserted: Instant,
+    /// first live delivery, the reference for provider lag
+    first_live: Option<Instant>,
+    /// bit per provider that delivered this signature
+    providers: u64,
+    /// miss statistics already taken for this entry
+    settled: bool,
+    /// published (live or backfill); later deliveries only feed statistics
+    processed: bool,
+}
+
+/// What one delivery meant relative to earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+    /// no provider had delivered this signature before
+    pub first: bool,
+    /// this provider already delivered it (e.g. after a resubscribe)
+    pub repeat: bool,
+    /// delay behind the first delivery
+    pub lag: Duration,
+}
+
+/// Bounded signature cache shared by all sources. Every event is keyed by
+/// `signature:instruction:log_index`, so one entry per signature dedupes them all.
+#[derive(Debug)]
+pub struct SeenCache {
+    capacity: usize,
+    order: VecDeque<String>,
+    entries: HashMap<String, Seen>,
+}
+
+impl SeenCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            entries: HashMap::with_capacity(capacity),
+        }
+    }
+
+    fn entry(&mut self, signature: &str, at: Instant) -> &mut Seen {
+        if !self.entries.contains_key(signature) {
+            if self.order.len() >= self.capacity {
+                if let Some(old) = self.order.pop_front() {
+                    self.entries.remove(&old);
+                }
+            }
+            self.order.push_back(signature.to_string());
+            self.entries.insert(
+                signature.to_string(),
+                Seen {
+                    inserted: at,
+                    first_live: None,
+                    providers: 0,
+                    settled: false,
+                    processed: false,
+                },
+            );
+        }
+        self.entries.get_mut(signature).expect("inserted above")
+    }
+
+    pub fn observe(&mut self, signature: &str, provider: usize, at: Instant) -> Observation {
+        let bit = 1u64 << provider;
+        let seen = self.entry(signature, at);
+        let repeat = seen.providers & bit != 0;
+        seen.providers |= bit;
+        let first = seen.first_live.is_none();
+        let first_live = *seen.first_live.get_or_insert(at);
+        Observation {
+            first,
+            repeat,
+            lag: at.saturating_duration_since(first_live),
+        }
+    }
+
+    pub fn is_processed(&self, signature: &str) -> bool {
+        self.entries.get(signature).is_some_and(|s| s.processed)
+    }
+
+    pub fn mark_processed(&mut self, signature: &str) {
+        self.entry(signature, Instant::now()).processed = true;
+    }
+
+    /// Provider masks of entries older than `window`, each reported once: whoever is not
+    /// in the mask by then missed that transaction.
+    pub fn settle(&mut self, window: Duration, now: Instant) -> Vec<u64> {
+        let mut out = Vec::new();
+        for sig in &self.order {
+            let seen = self.entries.get_mut(sig).expect("order and entries agree");
+            if now.saturating_duration_since(seen.inserted) < window {
+                // entries are in insertion order
+                break;
+            }
+            if !seen.settled {
+                seen.settled = true;
+                // backfill-only entries were not delivered live by anyone
+                if seen.providers != 0 {
+                    out.push(seen.providers);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_delivery_wins_and_later_ones_lag() {
+        let mut seen = SeenCache::new(16);
+        let t0 = Instant::now();
+        let first = seen.observe("sig", 1, t0);
+        assert_eq!(
+            first,
+            Observation {
+                first: true,
+                repeat: false,
+                lag: Duration::ZERO
+            }
+        );
+
+        let second = seen.observe("sig", 0, t0 + Duration::from_millis(40));
+        assert_eq!(
+            second,
+            Observation {
+                first: false,
+                repeat: false,
+                lag: Duration::from_millis(40)
+            }
+        );
+        // the same provider again, e.g. after a resubscribe
+        assert!(
+            seen.observe("sig", 1, t0 + Duration::from_millis(90))
+                .repeat
+        );
+
+        assert!(!seen.is_processed("sig"));
+        seen.mark_processed("sig");
+        assert!(seen.is_processed("sig"));
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted_at_capacity() {
+        let mut seen = SeenCache::new(2);
+        let t0 = Instant::now();
+        for sig in ["a", "b"] {
+            seen.observe(sig, 0, t0);
+            seen.mark_processed(sig);
+        }
+        seen.observe("c", 0, t0);
+        assert!(!seen.is_processed("a"), "a was evicted");
+        assert!(seen.is_processed("b"));
+        // an evicted signature comes back as new
+        assert!(seen.observe("a", 0, t0).first);
+        assert!(!seen.is_processed("b"), "b was evicted to make room");
+    }
+
+    #[test]
+    fn settle_reports_each_live_entry_once_after_the_window() {
+        let mut seen = SeenCache::new(16);
+        let t0 = Instant::now();
+        let window = Duration::from_secs(10);
+        seen.observe("live", 0, t0);
+        seen.observe("live", 2, t0 + Duration::from_secs(1));
+        // published by backfill only: no provider delivered it
+        seen.mark_processed("backfilled");
+        seen.observe("late", 1, t0 + Duration::from_secs(30));
+
+        assert!(seen.settle(window, t0 + Duration::from_secs(5)).is_empty());
+        let now = t0 + Duration::from_secs(11);
+        assert_eq!(seen.settle(window, now), [0b101]);
+        assert!(seen.settle(window, now).is_empty(), "already settled");
+        assert_eq!(seen.settle(window, t0 + Duration::from_secs(41)), [0b010]);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::parse::IxLayout;
+
+const COMPUTE_BUDGET: &str = "ComputeBudget111111111111111111111111111111";
+
+/// Compute unit limit per instruction when the transaction requests none.
+const DEFAULT_UNITS_PER_IX: u64 = 200_000;
+const MAX_UNITS: u64 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// The priority fee the runtime charged: compute unit price × requested compute unit
+/// limit, rounded up to whole lamports. Zero when no price was set.
+pub fn priority_fee(layout: &IxLayout) -> u64 {
+    let (mut limit, mut price, mut other_ixs) = (None, 0u64, 0u64);
+    for outer in &layout.outer {
+        let ix = &outer.ix;
+        if ix.program_id != COMPUTE_BUDGET {
+            other_ixs += 1;
+            continue;
+        }
+        // borsh enum: 2 = SetComputeUnitLimit(u32), 3 = SetComputeUnitPrice(u64)
+        match ix.data.split_first() {
+            Some((2, arg)) => limit = arg.try_into().ok().map(|b| u32::from_le_bytes(b) as u64),
+            Some((3, arg)) => price = arg.try_into().map_or(0, u64::from_le_bytes),
+            _ => {}
+        }
+    }
+    let limit = limit
+        .unwrap_or(other_ixs * DEFAULT_UNITS_PER_IX)
+        .min(MAX_UNITS);
+    let fee = (price as u128 * limit as u128).div_ceil(MICRO_LAMPORTS_PER_LAMPORT);
+    fee as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{Ix, OuterIx};
+
+    fn layout(ixs: &[(&str, Vec<u8>)]) -> IxLayout {
+        IxLayout {
+            outer: ixs
+                .iter()
+                .map(|(program_id, data)| OuterIx {
+                    ix: Ix {
+                        program_id: program_id.to_string(),
+                        accounts: Vec::new(),
+                        data: data.clone(),
+                    },
+                    inner: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn set_limit(units: u32) -> (&'static str, Vec<u8>) {
+        (COMPUTE_BUDGET, [&[2u8][..], &units.to_le_bytes()].concat())
+    }
+
+    fn set_price(micro_lamports: u64) -> (&'static str, Vec<u8>) {
+        (
+            COMPUTE_BUDGET,
+            [&[3u8][..], &micro_lamports.to_le_bytes()].concat(),
+        )
+    }
+
+    const ESCROW: &str = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk";
+
+    #[test]
+    fn no_price_no_priority_fee() {
+        assert_eq!(
+            priority_fee(&layout(&[set_limit(50_000), (ESCROW, vec![])])),
+            0
+        );
+    }
+
+    #[test]
+    fn price_times_requested_limit_rounded_up() {
+        let l = layout(&[set_limit(50_000), set_price(10_001), (ESCROW, vec![])]);
+        // 50_000 * 10_001 / 1e6 = 500.05
+        assert_eq!(priority_fee(&l), 501);
+    }
+
+    #[test]
+    fn default_limit_counts_other_instructions() {
+        let l = layout(&[set_price(1_000_000), (ESCROW, vec![]), (ESCROW, vec![])]);
+        assert_eq!(priority_fee(&l), 400_000);
+
+        let many: Vec<_> = std::iter::once(set_price(1_000_000))
+            .chain((0..10).map(|_| (ESCROW, vec![])))
+            .collect();
+        assert_eq!(priority_fee(&layout(&many)), MAX_UNITS);
+    }
+}
@@ -0,0 +1,150 @@
+use orderflow_common::NormalizedEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where a tracked transaction stands once its slot is at or below the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxFinality {
+    Finalized,
+    /// landed (again) in a slot that is not rooted yet
+    Pending(u64),
+    /// unknown to the node: its slot was skipped and it did not land elsewhere
+    Dropped,
+}
+
+/// Events published below `finalized`, keyed by slot, awaiting a follow-up.
+#[derive(Debug, Default)]
+pub struct Finality {
+    pending: BTreeMap<u64, Vec<NormalizedEvent>>,
+}
+
+/// One tracked event as stored in the checkpoint; `slot` is where it is tracked, which is
+/// the slot its transaction last landed in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingEvent {
+    pub slot: u64,
+    pub event: NormalizedEvent,
+}
+
+impl Finality {
+    pub fn track(&mut self, slot: u64, events: impl IntoIterator<Item = NormalizedEvent>) {
+        let mut events = events.into_iter().peekable();
+        if events.peek().is_some() {
+            self.pending.entry(slot).or_default().extend(events);
+        }
+    }
+
+    /// Everything tracked, for the checkpoint.
+    pub fn snapshot(&self) -> Vec<PendingEvent> {
+        self.pending
+            .iter()
+            .flat_map(|(&slot, evs)| {
+                evs.iter().map(move |ev| PendingEvent {
+                    slot,
+                    event: ev.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Resumes tracking what a previous run left in its checkpoint.
+    pub fn restore(&mut self, pending: Vec<PendingEvent>) {
+        for p in pending {
+            self.track(p.slot, [p.event]);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    /// Signatures of tracked events at or below `root`.
+    pub fn due_signatures(&self, root: u64) -> Vec<String> {
+        self.pending
+            .range(..=root)
+            .flat_map(|(_, evs)| evs.iter().map(|ev| ev.signature.to_string()))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Removes and returns tracked events at or below `root`.
+    pub fn take_due(&mut self, root: u64) -> Vec<(u64, NormalizedEvent)> {
+        let later = self.pending.split_off(&(root + 1));
+        std::mem::replace(&mut self.pending, later)
+            .into_iter()
+            .flat_map(|(slot, evs)| evs.into_iter().map(move |ev| (slot, ev)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{self, Checkpoint};
+    use serde_json::json;
+
+    fn event(signature: &str, slot: u64) -> NormalizedEvent {
+        serde_json::from_value(json!({
+            "event_id": format!("{signature}:0:2"),
+            "event_type": "OfferCreated",
+            "cluster": "localnet",
+            "program_id": "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk",
+            "offer_id": "7",
+            "maker": "EeFX3g6HePRn5ipNu5ey3jiYWRVmeK3Frxec2ZwqKs6W",
+            "mint_a": "F8LcnTCz2SD2DmZA4Gw3Q9pVsWHqT7csHbecX5rX7h3S",
+            "mint_b": "2SqwuyEsMcrUtJJkJzfQiCYZwMgBUUxrjDD2dawARH5s",
+            "amount_a": "1",
+            "amount_b": "2",
+            "slot": slot,
+            "signature": signature,
+            "commitment": "confirmed",
+            "ts_ingest_ms": 0
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn pending_events_survive_a_restart() {
+        let (a, b) = (
+            solana_sdk::signature::Signature::from([1; 64]).to_string(),
+            solana_sdk::signature::Signature::from([2; 64]).to_string(),
+        );
+        let mut before = Finality::default();
+        before.track(100, [event(&a, 100)]);
+        // re-landed in a later slot: tracked there, not at the event's own slot
+        before.track(105, [event(&b, 101)]);
+
+        // the checkpoint has moved past both transactions
+        let path =
+            std::env::temp_dir().join(format!("listener-finality-{}.json", std::process::id()));
+        let cp = Checkpoint {
+            slot: 110,
+            signature: b.clone(),
+            pending: before.snapshot(),
+        };
+        checkpoint::store(&path, &cp).unwrap();
+
+        let mut after = Finality::default();
+        after.restore(checkpoint::load(&path).unwrap().unwrap().pending);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(after.len(), 2);
+        assert_eq!(after.due_signatures(104), std::slice::from_ref(&a));
+        let due: Vec<_> = after
+            .take_due(110)
+            .into_iter()
+            .map(|(slot, ev)| (slot, ev.event_id))
+            .collect();
+        assert_eq!(due, [(100, format!("{a}:0:2")), (105, format!("{b}:0:2"))]);
+    }
+
+    #[test]
+    fn checkpoints_without_pending_events_still_load() {
+        let path =
+            std::env::temp_dir().join(format!("listener-checkpoint-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"slot":7,"signature":"sig"}"#).unwrap();
+        let cp = checkpoint::load(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(cp.pending.is_empty());
+    }
+}
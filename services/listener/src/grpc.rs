@@ -0,0 +1,599 @@
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel as RpcCommitment};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use yellowstone_grpc_client::GeyserGrpcClient;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest, SubscribeRequestFilterSlots,
+    SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdateTransaction,
+};
+use yellowstone_grpc_proto::tonic::transport::ClientTlsConfig;
+
+use crate::backfill::{FetchedTx, TxInfo};
+use crate::parse::{Ix, IxLayout, OuterIx};
+use crate::provider::{self, Feed, ProviderStats};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// tonic's 4 MiB default rejects the largest transactions (full logs, many inner
+/// instructions) and would end the stream.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// One Yellowstone (Geyser) gRPC endpoint: streams every successful transaction that
+/// references the program, with logs and instructions attached, so the merge loop needs
+/// no getTransaction round trip.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_grpc(
+    idx: usize,
+    program_id: String,
+    commitment: CommitmentConfig,
+    roots: bool,
+    x_token: Option<String>,
+    cooldown: Duration,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+) {
+    let request = subscribe_request(&program_id, commitment, roots);
+    let (session_stats, session_tx) = (stats.clone(), tx.clone());
+    provider::supervise(idx, cooldown, stats, tx, move || {
+        session(
+            idx,
+            request.clone(),
+            x_token.clone(),
+            session_stats.clone(),
+            session_tx.clone(),
+        )
+    })
+    .await
+}
+
+/// Transactions naming the program anywhere (including via lookup tables), plus slot
+/// updates when finality is followed.
+fn subscribe_request(
+    program_id: &str,
+    commitment: CommitmentConfig,
+    roots: bool,
+) -> SubscribeRequest {
+    let level = match commitment.commitment {
+        RpcCommitment::Finalized => CommitmentLevel::Finalized,
+        RpcCommitment::Confirmed => CommitmentLevel::Confirmed,
+        _ => CommitmentLevel::Processed,
+    };
+    let filter = SubscribeRequestFilterTransactions {
+        vote: Some(false),
+        failed: Some(false),
+        account_include: vec![program_id.to_string()],
+        ..Default::default()
+    };
+    let mut slots = HashMap::new();
+    if roots {
+        // every status, not just `level`: the finalized ones are the roots
+        slots.insert(
+            "roots".to_string(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(false),
+            },
+        );
+    }
+    SubscribeRequest {
+        transactions: HashMap::from([("escrow".to_string(), filter)]),
+        slots,
+        commitment: Some(level as i32),
+        ..Default::default()
+    }
+}
+
+async fn session(
+    idx: usize,
+    request: SubscribeRequest,
+    x_token: Option<String>,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+) -> Result<()> {
+    let mut builder = GeyserGrpcClient::build_from_shared(stats.url.clone())?
+        .x_token(x_token)?
+        .connect_timeout(CONNECT_TIMEOUT)
+        .max_decoding_message_size(MAX_MESSAGE_BYTES);
+    if stats.url.starts_with("https://") {
+        builder = builder.tls_config(ClientTlsConfig::new())?;
+    }
+    let mut client = builder.connect().await.context("grpc connect")?;
+    // the request sink must outlive the session: dropping it ends the subscription
+    let (mut requests, mut updates) = client
+        .subscribe_with_request(Some(request))
+        .await
+        .context("grpc subscribe")?;
+
+    stats.mark_up(idx, &tx).await?;
+
+    let mut demotion_check = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            maybe_update = updates.next() => {
+                let Some(update) = maybe_update else { return Ok(()); };
+                let update = update.context("grpc stream")?;
+                match update.update_oneof {
+                    Some(UpdateOneof::Transaction(update)) => {
+                        let Some((signature, fetched)) = decode_tx(update)? else { continue; };
+                        let feed = Feed::Tx {
+                            provider: idx,
+                            signature,
+                            at: Instant::now(),
+                            fetched: Some(fetched),
+                        };
+                        tx.send(feed).await.context("merge loop gone")?;
+                    }
+                    Some(UpdateOneof::Slot(slot)) if slot.status == CommitmentLevel::Finalized as i32 => {
+                        tx.send(Feed::Root(slot.slot)).await.context("merge loop gone")?;
+                    }
+                    // answering server pings keeps idle streams alive through load balancers
+                    Some(UpdateOneof::Ping(_)) => {
+                        let ping = SubscribeRequest {
+                            ping: Some(SubscribeRequestPing { id: 1 }),
+                            ..Default::default()
+                        };
+                        requests.send(ping).await.context("grpc ping")?;
+                    }
+                    _ => {}
+                }
+            }
+            _ = demotion_check.tick() => {
+                if stats.demoted.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Signature, logs and instruction layout of one streamed transaction; `None` if it failed.
+/// Mirrors `Backfiller::fetch_tx` so both paths yield the same events.
+pub fn decode_tx(update: SubscribeUpdateTransaction) -> Result<Option<(String, FetchedTx)>> {
+    let info = update
+        .transaction
+        .context("transaction update without transaction")?;
+    let signature = Signature::try_from(info.signature.as_slice())
+        .map_err(|_| anyhow::anyhow!("malformed signature"))?
+        .to_string();
+    let meta = info
+        .meta
+        .with_context(|| format!("{signature}: transaction without meta"))?;
+    if meta.err.is_some() {
+        return Ok(None);
+    }
+    let message = info
+        .transaction
+        .and_then(|tx| tx.message)
+        .with_context(|| format!("{signature}: transaction without message"))?;
+
+    // v0 messages index into static keys followed by loaded writable, then readonly
+    let keys = message
+        .account_keys
+        .iter()
+        .chain(&meta.loaded_writable_addresses)
+        .chain(&meta.loaded_readonly_addresses)
+        .map(|key| Pubkey::try_from(key.as_slice()).map(|key| key.to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| anyhow::anyhow!("{signature}: malformed account key"))?;
+    let key = |i: u32| {
+        keys.get(i as usize)
+            .cloned()
+            .with_context(|| format!("{signature}: account index {i} out of range"))
+    };
+    let ix = |program_id_index: u32, accounts: &[u8], data: Vec<u8>| -> Result<Ix> {
+        Ok(Ix {
+            program_id: key(program_id_index)?,
+            accounts: accounts
+                .iter()
+                .map(|&i| key(i as u32))
+                .collect::<Result<_>>()?,
+            data,
+        })
+    };
+
+    let mut outer = message
+        .instructions
+        .into_iter()
+        .map(|i| {
+            Ok(OuterIx {
+                ix: ix(i.program_id_index, &i.accounts, i.data)?,
+                inner: Vec::new(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for group in meta.inner_instructions {
+        let Some(parent) = outer.get_mut(group.index as usize) else {
+            continue;
+        };
+        for i in group.instructions {
+            parent
+                .inner
+                .push(ix(i.program_id_index, &i.accounts, i.data)?);
+        }
+    }
+
+    let layout = IxLayout { outer };
+    // geyser updates carry no block time; the merge loop looks it up per slot
+    let info = TxInfo::new(meta.fee, keys.first(), meta.compute_units_consumed, &layout);
+    Ok(Some((
+        signature,
+        FetchedTx {
+            slot: update.slot,
+            logs: meta.log_messages,
+            layout,
+            info,
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_tx, IngestCtx};
+    use futures::Stream;
+    use serde::Deserialize;
+    use std::pin::Pin;
+    use std::str::FromStr;
+    use yellowstone_grpc_proto::prelude::geyser_server::{Geyser, GeyserServer};
+    use yellowstone_grpc_proto::prelude::{
+        GetBlockHeightRequest, GetBlockHeightResponse, GetLatestBlockhashRequest,
+        GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse, GetVersionRequest,
+        GetVersionResponse, InnerInstruction, InnerInstructions, IsBlockhashValidRequest,
+        IsBlockhashValidResponse, Message, PingRequest, PongResponse, SubscribeUpdate,
+        SubscribeUpdatePing, SubscribeUpdateSlot, SubscribeUpdateTransactionInfo, Transaction,
+        TransactionError, TransactionStatusMeta,
+    };
+    use yellowstone_grpc_proto::tonic::transport::server::TcpIncoming;
+    use yellowstone_grpc_proto::tonic::transport::Server;
+    use yellowstone_grpc_proto::tonic::{self, Request, Response, Status, Streaming};
+
+    const ESCROW: &str = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk";
+    const CREATE_SIG: &str =
+        "EVv5vxTN8exmFAGzy5yQ4ySDZEKn3SNyGNJD3WAwWpHCc6KyuuWTjhAe2Uq5xZtyLMfEpm6JPvYzU9p1GbfPPFT";
+    const TAKE_SIG: &str =
+        "2C71wpN5483vsD8Scx3xrBUipfnc6qFTkAXCoYQQBaZhq8PVLsdZKC6L6V2dCU3Lq5WP3SBaKSCzxVtVyFRuQCd8";
+
+    /// One entry of `testdata/geyser_replay.json`, a Geyser session captured as readable JSON.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Recorded {
+        Tx(RecordedTx),
+        Root { finalized_slot: u64 },
+        Ping { ping: bool },
+    }
+
+    #[derive(Deserialize)]
+    struct RecordedTx {
+        slot: u64,
+        signature: String,
+        account_keys: Vec<String>,
+        loaded_writable: Vec<String>,
+        loaded_readonly: Vec<String>,
+        instructions: Vec<u32>,
+        inner_instructions: Vec<RecordedInner>,
+        err: bool,
+        logs: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct RecordedInner {
+        index: u32,
+        program_id_indexes: Vec<u32>,
+    }
+
+    fn keys(keys: &[String]) -> Vec<Vec<u8>> {
+        keys.iter()
+            .map(|k| Pubkey::from_str(k).unwrap().to_bytes().to_vec())
+            .collect()
+    }
+
+    impl RecordedTx {
+        fn into_update(self) -> UpdateOneof {
+            let signature = Signature::from_str(&self.signature)
+                .unwrap()
+                .as_ref()
+                .to_vec();
+            let message = Message {
+                account_keys: keys(&self.account_keys),
+                instructions: self
+                    .instructions
+                    .iter()
+                    .map(
+                        |&program_id_index| yellowstone_grpc_proto::prelude::CompiledInstruction {
+                            program_id_index,
+                            ..Default::default()
+                        },
+                    )
+                    .collect(),
+                versioned: !self.loaded_writable.is_empty() || !self.loaded_readonly.is_empty(),
+                ..Default::default()
+            };
+            let meta = TransactionStatusMeta {
+                err: self.err.then(TransactionError::default),
+                inner_instructions: self
+                    .inner_instructions
+                    .iter()
+                    .map(|group| InnerInstructions {
+                        index: group.index,
+                        instructions: group
+                            .program_id_indexes
+                            .iter()
+                            .map(|&program_id_index| InnerInstruction {
+                                program_id_index,
+                                stack_height: Some(2),
+                                ..Default::default()
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+                log_messages: self.logs,
+                loaded_writable_addresses: keys(&self.loaded_writable),
+                loaded_readonly_addresses: keys(&self.loaded_readonly),
+                ..Default::default()
+            };
+            UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: signature.clone(),
+                    is_vote: false,
+                    transaction: Some(Transaction {
+                        signatures: vec![signature],
+                        message: Some(message),
+                    }),
+                    meta: Some(meta),
+                    index: 0,
+                }),
+                slot: self.slot,
+            })
+        }
+    }
+
+    fn recorded_updates() -> Vec<SubscribeUpdate> {
+        let recorded: Vec<Recorded> =
+            serde_json::from_str(include_str!("../testdata/geyser_replay.json")).unwrap();
+        recorded
+            .into_iter()
+            .map(|r| {
+                let update = match r {
+                    Recorded::Tx(tx) => tx.into_update(),
+                    Recorded::Root { finalized_slot } => UpdateOneof::Slot(SubscribeUpdateSlot {
+                        slot: finalized_slot,
+                        parent: Some(finalized_slot - 1),
+                        status: CommitmentLevel::Finalized as i32,
+                    }),
+                    Recorded::Ping { ping } => {
+                        assert!(ping);
+                        UpdateOneof::Ping(SubscribeUpdatePing {})
+                    }
+                };
+                SubscribeUpdate {
+                    filters: vec!["escrow".into()],
+                    update_oneof: Some(update),
+                }
+            })
+            .collect()
+    }
+
+    /// Replays a fixed update sequence to every subscriber, then holds the stream open.
+    /// Requests arriving on the stream are forwarded with the subscriber's `x-token`.
+    struct MockGeyser {
+        updates: Vec<SubscribeUpdate>,
+        requests: mpsc::UnboundedSender<(Option<String>, SubscribeRequest)>,
+    }
+
+    type UpdateStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+    #[tonic::async_trait]
+    impl Geyser for MockGeyser {
+        type SubscribeStream = UpdateStream;
+
+        async fn subscribe(
+            &self,
+            request: Request<Streaming<SubscribeRequest>>,
+        ) -> Result<Response<UpdateStream>, Status> {
+            let x_token = request
+                .metadata()
+                .get("x-token")
+                .map(|v| v.to_str().unwrap().to_string());
+            let mut inbound = request.into_inner();
+            let requests = self.requests.clone();
+            tokio::spawn(async move {
+                while let Some(Ok(req)) = inbound.next().await {
+                    if requests.send((x_token.clone(), req)).is_err() {
+                        break;
+                    }
+                }
+            });
+            let replay = futures::stream::iter(self.updates.clone().into_iter().map(Ok))
+                .chain(futures::stream::pending());
+            Ok(Response::new(Box::pin(replay)))
+        }
+
+        async fn ping(&self, _: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+            Err(Status::unimplemented("mock"))
+        }
+
+        async fn get_latest_blockhash(
+            &self,
+            _: Request<GetLatestBlockhashRequest>,
+        ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+            Err(Status::unimplemented("mock"))
+        }
+
+        async fn get_block_height(
+            &self,
+            _: Request<GetBlockHeightRequest>,
+        ) -> Result<Response<GetBlockHeightResponse>, Status> {
+            Err(Status::unimplemented("mock"))
+        }
+
+        async fn get_slot(
+            &self,
+            _: Request<GetSlotRequest>,
+        ) -> Result<Response<GetSlotResponse>, Status> {
+            Err(Status::unimplemented("mock"))
+        }
+
+        async fn is_blockhash_valid(
+            &self,
+            _: Request<IsBlockhashValidRequest>,
+        ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+            Err(Status::unimplemented("mock"))
+        }
+
+        async fn get_version(
+            &self,
+            _: Request<GetVersionRequest>,
+        ) -> Result<Response<GetVersionResponse>, Status> {
+            Err(Status::unimplemented("mock"))
+        }
+    }
+
+    async fn serve(
+        updates: Vec<SubscribeUpdate>,
+    ) -> (
+        String,
+        mpsc::UnboundedReceiver<(Option<String>, SubscribeRequest)>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        let (requests, received) = mpsc::unbounded_channel();
+        let service = GeyserServer::new(MockGeyser { updates, requests });
+        tokio::spawn(
+            Server::builder()
+                .add_service(service)
+                .serve_with_incoming(incoming),
+        );
+        (url, received)
+    }
+
+    fn ctx() -> IngestCtx {
+        IngestCtx {
+            cluster: "localnet".into(),
+            program_id: ESCROW.parse().unwrap(),
+            commitment: "confirmed".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_stream_into_events() {
+        let (url, mut requests) = serve(recorded_updates()).await;
+        let stats = Arc::new(ProviderStats::new(&url));
+        let (tx, mut feed) = mpsc::channel(64);
+        let source = tokio::spawn(run_grpc(
+            0,
+            ESCROW.into(),
+            CommitmentConfig::confirmed(),
+            true,
+            Some("secret".into()),
+            Duration::from_secs(60),
+            stats.clone(),
+            tx,
+        ));
+
+        let mut events = Vec::new();
+        let mut roots = Vec::new();
+        let collect = async {
+            while let Some(msg) = feed.recv().await {
+                match msg {
+                    Feed::Up(0) => {}
+                    Feed::Tx {
+                        signature,
+                        fetched: Some(tx),
+                        ..
+                    } => {
+                        events.extend(
+                            parse_tx(&ctx(), &signature, tx.slot, &tx.logs, &tx.layout).events,
+                        );
+                    }
+                    Feed::Root(root) => {
+                        roots.push(root);
+                        if roots.len() == 2 {
+                            break;
+                        }
+                    }
+                    other => panic!("unexpected feed: {other:?}"),
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), collect)
+            .await
+            .unwrap();
+
+        // the failed take and the foreign program's spoofed line yield nothing
+        let got: Vec<_> = events
+            .iter()
+            .map(|e| (e.event_type.clone(), e.event_id.clone(), e.slot))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (
+                    orderflow_common::EventType::OfferCreated,
+                    format!("{CREATE_SIG}:0:12"),
+                    1000
+                ),
+                (
+                    orderflow_common::EventType::OfferFilled,
+                    format!("{TAKE_SIG}:1:13"),
+                    1004
+                ),
+            ]
+        );
+        assert_eq!(roots, vec![1000, 1004]);
+        assert!(stats.connected.load(Ordering::Relaxed));
+
+        let (x_token, subscribe) = requests.recv().await.unwrap();
+        assert_eq!(x_token.as_deref(), Some("secret"));
+        let filter = &subscribe.transactions["escrow"];
+        assert_eq!(filter.account_include, vec![ESCROW.to_string()]);
+        assert_eq!((filter.vote, filter.failed), (Some(false), Some(false)));
+        assert_eq!(
+            subscribe.commitment,
+            Some(CommitmentLevel::Confirmed as i32)
+        );
+        assert!(subscribe.slots.contains_key("roots"));
+
+        let (_, pong) = requests.recv().await.unwrap();
+        assert!(pong.ping.is_some(), "server ping must be answered");
+
+        source.abort();
+    }
+
+    #[test]
+    fn no_slot_updates_at_finalized() {
+        let request = subscribe_request(ESCROW, CommitmentConfig::finalized(), false);
+        assert!(request.slots.is_empty());
+        assert_eq!(request.commitment, Some(CommitmentLevel::Finalized as i32));
+    }
+
+    #[test]
+    fn event_ids_match_rpc_layout_for_lookup_table_keys() {
+        let Some(UpdateOneof::Transaction(update)) = recorded_updates()
+            .into_iter()
+            .filter_map(|u| u.update_oneof)
+            .find(|u| matches!(u, UpdateOneof::Transaction(t) if t.slot == 1004))
+        else {
+            panic!("take transaction missing from recording");
+        };
+        let (signature, tx) = decode_tx(update).unwrap().unwrap();
+        assert_eq!(signature, TAKE_SIG);
+        let programs: Vec<_> = tx
+            .layout
+            .outer
+            .iter()
+            .map(|o| o.ix.program_id.as_str())
+            .collect();
+        assert_eq!(
+            programs,
+            vec!["ComputeBudget111111111111111111111111111111", ESCROW]
+        );
+        assert_eq!(tx.layout.outer[1].inner.len(), 3);
+    }
+}
@@ -1,150 +1,1075 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use futures::StreamExt;
-use orderflow_common::{now_ms, EventType, NormalizedEvent, OnchainLogEvent};
-use rdkafka::config::ClientConfig;
-use rdkafka::producer::{FutureProducer, FutureRecord};
-use solana_client::nonblocking::pubsub_client::PubsubClient;
-use solana_client::rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+mod accounts;
+mod backfill;
+mod backoff;
+mod checkpoint;
+mod config;
+mod dedupe;
+mod fees;
+mod finality;
+mod grpc;
+mod metrics;
+mod parse;
+mod poll;
+mod provider;
+mod recover;
+mod replay;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use orderflow_common::envelope;
+use orderflow_common::signing::EventSigner;
+use orderflow_common::sink::SpoolStats;
+use orderflow_common::sink::{
+    JsonlSink, KafkaSink, NatsSink, SigningSink, Sink, SpoolingSink, StdoutSink,
+};
+use orderflow_common::wire::{Encoding, WireFormat};
+use orderflow_common::{now_ms, EventType, NormalizedEvent, QuarantineRecord};
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use backfill::{Backfiller, FetchedTx, Range};
+use backoff::Backoff;
+use checkpoint::Checkpoint;
+use config::SourceConfig;
+use dedupe::SeenCache;
+use finality::{Finality, PendingEvent, TxFinality};
+use metrics::Metrics;
+use parse::{parse_tx, IngestCtx};
+use provider::{Feed, Health, ProviderStats};
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 struct Args {
-    /// Solana WS endpoint, e.g. ws://127.0.0.1:8900
-    #[arg(long, env = "SOLANA_WS_URL")]
-    solana_ws_url: String,
+    /// Live transaction stream: WS logsSubscribe, Yellowstone gRPC, or `poll` for HTTP-only
+    /// RPC nodes; `accounts` instead publishes EscrowState status changes
+    /// (programSubscribe) to STATE_TOPIC
+    #[arg(long, env = "SOURCE", value_enum, default_value = "ws")]
+    source: SourceKind,
+
+    /// ws source: also append every raw logsSubscribe notification to this JSONL file
+    #[arg(long, env = "RECORD_FILE")]
+    record: Option<PathBuf>,
+
+    /// Instead of a live source, replay a `--record` file and exit; checkpoint untouched
+    #[arg(long, env = "REPLAY_FILE", conflicts_with = "record")]
+    replay: Option<String>,
+
+    /// replay: 1 = recorded pace, 10 = ten times faster, 0 = no gaps
+    #[arg(long, env = "REPLAY_SPEED", default_value_t = 1.0)]
+    replay_speed: f64,
+
+    /// Solana WS endpoints, comma-separated; all are subscribed and merged
+    #[arg(
+        long,
+        env = "SOLANA_WS_URL",
+        value_delimiter = ',',
+        default_value = "ws://127.0.0.1:8900"
+    )]
+    solana_ws_url: Vec<String>,
+
+    /// grpc source: Yellowstone (Geyser) endpoints, comma-separated; merged like WS endpoints
+    #[arg(
+        long,
+        env = "GRPC_URL",
+        value_delimiter = ',',
+        default_value = "http://127.0.0.1:10000"
+    )]
+    grpc_url: Vec<String>,
+
+    /// grpc source: `x-token` auth header, if the provider wants one
+    #[arg(long, env = "GRPC_X_TOKEN")]
+    grpc_x_token: Option<String>,
+
+    /// poll source: HTTP RPC endpoints, comma-separated; defaults to SOLANA_RPC_URL
+    #[arg(long, env = "POLL_URL", value_delimiter = ',')]
+    poll_url: Vec<String>,
+
+    /// poll source: getSignaturesForAddress interval per endpoint
+    #[arg(long, env = "POLL_INTERVAL_MS", default_value_t = 1000)]
+    poll_interval_ms: u64,
+
+    /// Demote a provider whose average delay behind the fastest one exceeds this
+    #[arg(long, env = "PROVIDER_MAX_LAG_MS", default_value_t = 2000)]
+    provider_max_lag_ms: u64,
+
+    /// Demote a provider that misses more than this share of transactions
+    #[arg(long, env = "PROVIDER_MAX_MISS_RATIO", default_value_t = 0.2)]
+    provider_max_miss_ratio: f64,
+
+    /// How long a demoted provider stays unsubscribed before it is retried
+    #[arg(long, env = "PROVIDER_COOLDOWN_SECS", default_value_t = 60)]
+    provider_cooldown_secs: u64,
+
+    /// Solana HTTP RPC endpoint, used for backfill (getSignaturesForAddress/getTransaction)
+    #[arg(long, env = "SOLANA_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    solana_rpc_url: String,
 
-    /// Program id to subscribe
-    #[arg(long, env = "PROGRAM_ID")]
-    program_id: String,
+    /// Program id to subscribe (not with --config)
+    #[arg(long, env = "PROGRAM_ID", required_unless_present = "config")]
+    program_id: Option<Pubkey>,
 
     /// localnet/devnet/mainnet-beta
     #[arg(long, env = "CLUSTER", default_value = "localnet")]
     cluster: String,
 
-    /// processed/confirmed/finalized
+    /// processed/confirmed/finalized; below finalized every event later gets an
+    /// EventFinalized/EventRetracted follow-up
     #[arg(long, env = "COMMITMENT", default_value = "finalized")]
     commitment: String,
 
+    /// Where events go; jsonl/stdout need no broker
+    #[arg(long, env = "SINK", value_enum, default_value = "kafka")]
+    sink: SinkKind,
+
     /// Kafka brokers, e.g. localhost:9092
     #[arg(long, env = "KAFKA_BROKERS", default_value = "localhost:9092")]
     kafka_brokers: String,
 
     #[arg(long, env = "KAFKA_TOPIC", default_value = "escrow.events.v1")]
     kafka_topic: String,
+
+    /// accounts source: Kafka topic / NATS subject for offer state records
+    #[arg(long, env = "STATE_TOPIC", default_value = "escrow.offers.state.v1")]
+    state_topic: String,
+
+    /// JSON event format: 3 = `{schema_version, event_type, data}` envelope with snake_case
+    /// fields, 2 = the same with camelCase fields, 1 = bare camelCase event; 1 and 2 are for
+    /// consumers that predate them
+    #[arg(
+        long,
+        env = "EVENT_SCHEMA_VERSION",
+        default_value_t = envelope::SCHEMA_VERSION,
+        value_parser = clap::value_parser!(u32).range(1..=envelope::SCHEMA_VERSION as i64)
+    )]
+    event_schema_version: u32,
+
+    /// Event payload encoding: json, or protobuf (`common/proto/orderflow/v1/events.proto`,
+    /// always the current envelope); protobuf needs the kafka or nats sink
+    #[arg(long, env = "EVENT_ENCODING", default_value = "json")]
+    encoding: Encoding,
+
+    /// protobuf: register the schema under `<topic>-value` here and frame each payload with
+    /// its id (Confluent wire format)
+    #[arg(long, env = "SCHEMA_REGISTRY_URL")]
+    schema_registry_url: Option<String>,
+
+    /// Kafka topic / NATS subject for transactions the parser could not fully decode
+    #[arg(long, env = "QUARANTINE_TOPIC", default_value = "escrow.quarantine.v1")]
+    quarantine_topic: String,
+
+    /// Kafka topic / NATS subject for the admin's denylist changes (`AdminEvent`s)
+    #[arg(long, env = "ADMIN_TOPIC", default_value = "escrow.admin.v1")]
+    admin_topic: String,
+
+    /// jsonl sink: directory of `events.<unix_ms>.jsonl` files
+    #[arg(long, env = "JSONL_DIR", default_value = "events")]
+    jsonl_dir: PathBuf,
+
+    /// jsonl sink: start a new file once the current one reaches this size
+    #[arg(long, env = "JSONL_MAX_BYTES", default_value_t = 64 * 1024 * 1024)]
+    jsonl_max_bytes: u64,
+
+    /// nats sink: nats://[token@]host:port
+    #[arg(long, env = "NATS_URL", default_value = "nats://127.0.0.1:4222")]
+    nats_url: String,
+
+    /// Records the sink fails to deliver are kept here and replayed in order
+    #[arg(long, env = "SPOOL_DIR", default_value = "spool")]
+    spool_dir: PathBuf,
+
+    /// nats sink: subject bound to a JetStream stream
+    #[arg(long, env = "NATS_SUBJECT", default_value = "escrow.events.v1")]
+    nats_subject: String,
+
+    /// `solana-keygen` JSON keypair; every record is signed with it (kafka/nats headers)
+    #[arg(long, env = "SIGNING_KEY_FILE")]
+    signing_key: Option<PathBuf>,
+
+    /// key id sent with each signature; defaults to the base58 public key
+    #[arg(long, env = "SIGNING_KEY_ID", requires = "signing_key")]
+    signing_key_id: Option<String>,
+
+    /// Last processed slot/signature; on startup the gap since then is backfilled
+    #[arg(
+        long,
+        env = "CHECKPOINT_FILE",
+        default_value = "listener.checkpoint.json"
+    )]
+    checkpoint_file: PathBuf,
+
+    /// Prometheus text endpoint (reconnects, outage time, published events)
+    #[arg(long, env = "METRICS_ADDR", default_value = "0.0.0.0:9101")]
+    metrics_addr: String,
+
+    /// TOML file of `[[source]]` entries (cluster, ws_url, rpc_url, program_id, commitment,
+    /// topic), each run as its own ws pipeline; replaces the per-source flags above
+    #[arg(long, env = "LISTENER_CONFIG")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    /// These args with the pipeline-specific ones taken from a config entry. Checkpoint,
+    /// spool and jsonl paths get the source name so pipelines never share a file.
+    fn for_source(&self, source: &SourceConfig) -> Args {
+        let mut args = self.clone();
+        let name = &source.name;
+        args.config = None;
+        args.cluster = source.cluster.clone();
+        args.solana_ws_url = source.ws_url.clone();
+        args.solana_rpc_url = source.rpc_url.clone();
+        args.program_id = Some(source.program_id);
+        args.commitment = source.commitment.clone();
+        if let Some(topic) = &source.topic {
+            args.kafka_topic = topic.clone();
+            args.nats_subject = topic.clone();
+        }
+        let stem = self
+            .checkpoint_file
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        args.checkpoint_file = self
+            .checkpoint_file
+            .with_file_name(format!("{stem}.{name}.json"));
+        args.spool_dir = self.spool_dir.join(name);
+        args.jsonl_dir = self.jsonl_dir.join(name);
+        args
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Command {
+    /// One-shot re-index of an inclusive slot range via RPC, then exit (checkpoint untouched)
+    Backfill {
+        #[arg(long)]
+        from_slot: u64,
+        #[arg(long)]
+        to_slot: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SourceKind {
+    Ws,
+    Grpc,
+    Poll,
+    Accounts,
+    /// selected by `--replay`
+    #[value(skip)]
+    Replay,
+}
+
+impl SourceKind {
+    fn endpoints(self, args: &Args) -> &[String] {
+        match self {
+            SourceKind::Ws | SourceKind::Accounts => &args.solana_ws_url,
+            SourceKind::Grpc => &args.grpc_url,
+            SourceKind::Poll if args.poll_url.is_empty() => {
+                std::slice::from_ref(&args.solana_rpc_url)
+            }
+            SourceKind::Poll => &args.poll_url,
+            SourceKind::Replay => args.replay.as_slice(),
+        }
+    }
+}
+
+/// Which record stream the sink carries.
+#[derive(Debug, Clone, Copy)]
+enum Output {
+    /// `NormalizedEvent`s
+    Events,
+    /// `OfferStateEvent`s from the accounts source
+    OfferStates,
+    /// `QuarantineRecord`s: transactions (or parts) that yielded no event
+    Quarantine,
+    /// `AdminEvent`s: denylist changes
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SinkKind {
+    Kafka,
+    Jsonl,
+    Stdout,
+    Nats,
+}
+
+impl SinkKind {
+    async fn open(self, args: &Args, output: Output) -> Result<Box<dyn Sink>> {
+        let (topic, subject, prefix) = Self::names(args, output);
+        Ok(match self {
+            SinkKind::Kafka => Box::new(KafkaSink::new(&args.kafka_brokers, topic)?),
+            SinkKind::Jsonl => Box::new(JsonlSink::new(
+                &args.jsonl_dir,
+                prefix,
+                args.jsonl_max_bytes,
+            )?),
+            SinkKind::Stdout => Box::new(StdoutSink),
+            SinkKind::Nats => Box::new(NatsSink::connect(&args.nats_url, subject).await?),
+        })
+    }
+
+    fn describe(self, args: &Args, output: Output) -> String {
+        let (topic, subject, prefix) = Self::names(args, output);
+        match self {
+            SinkKind::Kafka => format!("kafka topic={topic}"),
+            SinkKind::Jsonl => format!("jsonl dir={} prefix={prefix}", args.jsonl_dir.display()),
+            SinkKind::Stdout => "stdout".to_string(),
+            SinkKind::Nats => format!("nats subject={subject}"),
+        }
+    }
+
+    /// Kafka topic, NATS subject and jsonl file prefix for `output`.
+    fn names(args: &Args, output: Output) -> (&str, &str, &'static str) {
+        match output {
+            Output::Events => (&args.kafka_topic, &args.nats_subject, "events"),
+            Output::OfferStates => (&args.state_topic, &args.state_topic, "offer-states"),
+            Output::Quarantine => (&args.quarantine_topic, &args.quarantine_topic, "quarantine"),
+            Output::Admin => (&args.admin_topic, &args.admin_topic, "admin"),
+        }
+    }
+}
+
+/// A live notification can arrive before getTransaction serves it at `confirmed`.
+const LIVE_FETCH_ATTEMPTS: u32 = 20;
+const LIVE_FETCH_DELAY: Duration = Duration::from_millis(250);
+
+/// A dropped transaction is retracted only once the root is this far past its slot,
+/// so a lagging status cache cannot roll back a fill that did land.
+const RETRACT_MARGIN: u64 = 32;
+
+/// How often the sinks are flushed; the spool replays in the background on its own backoff.
+const SPOOL_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Signatures remembered for cross-provider dedupe.
+const DEDUPE_CAPACITY: usize = 100_000;
+
+/// A provider that has not delivered a signature this long after the first one missed it.
+const MISS_WINDOW: Duration = Duration::from_secs(10);
+
+/// Cadence of provider health checks and catch-up retries.
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A `--config` pipeline that ran this long before failing restarts without delay growth.
+const PIPELINE_STABLE: Duration = Duration::from_secs(300);
+
+struct Publisher {
+    sink: Box<dyn Sink>,
+    /// parse failures, with the transaction's raw logs
+    quarantine: Box<dyn Sink>,
+    /// denylist changes
+    admin: Box<dyn Sink>,
+    metrics: Arc<Metrics>,
+    /// `None` when ingesting at `finalized`: nothing to follow up on
+    finality: Option<Mutex<Finality>>,
+    /// encoding of events; see `orderflow_common::wire`
+    wire: WireFormat,
+}
+
+impl Publisher {
+    async fn publish(&self, ev: &NormalizedEvent) -> Result<()> {
+        let payload = self.wire.encode_event(ev)?;
+
+        // key per offer / RFQ, to keep same order per offer in Kafka partitioning
+        // at-least-once: we don't de-dupe here; consumers handle idempotency via event_id
+        self.sink.send(&ev.key(), &payload).await?;
+        self.metrics
+            .events_published_total
+            .fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Publishes the transaction's events. Events cut off by log truncation are rebuilt from
+    /// instruction data and account state; whatever cannot be decoded goes to quarantine.
+    async fn publish_tx(
+        &self,
+        ctx: &IngestCtx,
+        signature: &str,
+        tx: &FetchedTx,
+        backfiller: &Backfiller,
+    ) -> Result<()> {
+        let parsed = parse_tx(ctx, signature, tx.slot, &tx.logs, &tx.layout);
+        let (mut events, mut failures) = (parsed.events, parsed.failures);
+        if !parsed.unlogged.is_empty() {
+            self.metrics
+                .truncated_txs_total
+                .fetch_add(1, Ordering::Relaxed);
+            let (recovered, lost) =
+                recover::recover(ctx, backfiller, signature, tx.slot, &parsed.unlogged).await?;
+            self.metrics
+                .truncated_recovered_total
+                .fetch_add(recovered.len() as u64, Ordering::Relaxed);
+            events.extend(recovered);
+            failures.extend(lost);
+        }
+
+        for ev in &mut events {
+            tx.info.stamp(ev);
+            self.publish(ev).await?;
+        }
+        for ev in &parsed.admin {
+            let payload = serde_json::to_string(ev).context("serialize admin event")?;
+            self.admin
+                .send(ev.wallet.as_str(), payload.as_bytes())
+                .await?;
+            self.metrics
+                .admin_events_published_total
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        for failure in failures {
+            // a log line pins the failure down more precisely than an instruction
+            let at = match (failure.log_index, &failure.instruction) {
+                (Some(i), _) => i.to_string(),
+                (None, Some(position)) => position.clone(),
+                (None, None) => String::new(),
+            };
+            let record = QuarantineRecord {
+                quarantine_id: format!("{signature}:{:?}:{at}", failure.reason),
+                reason: failure.reason,
+                cluster: ctx.cluster.clone(),
+                program_id: ctx.program_id.to_string(),
+                signature: signature.to_string(),
+                slot: tx.slot,
+                instruction: failure.instruction,
+                log_index: failure.log_index.map(|i| i as u32),
+                detail: failure.detail,
+                logs: tx.logs.clone(),
+                ts_ingest_ms: now_ms(),
+            };
+            let payload = serde_json::to_string(&record).context("serialize quarantine record")?;
+            self.quarantine.send(signature, payload.as_bytes()).await?;
+            self.metrics
+                .parse_failures_total
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(finality) = &self.finality {
+            finality.lock().unwrap().track(tx.slot, events);
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.sink.flush().await?;
+        self.quarantine.flush().await?;
+        self.admin.flush().await
+    }
+
+    /// Events awaiting a follow-up, to store with the checkpoint.
+    fn pending(&self) -> Vec<PendingEvent> {
+        self.finality
+            .as_ref()
+            .map_or_else(Vec::new, |finality| finality.lock().unwrap().snapshot())
+    }
+
+    /// Stores the checkpoint together with the events still awaiting a follow-up.
+    fn store_checkpoint(&self, path: &Path, slot: u64, signature: String) -> Result<()> {
+        let pending = self.pending();
+        checkpoint::store(
+            path,
+            &Checkpoint {
+                slot,
+                signature,
+                pending,
+            },
+        )
+    }
+
+    /// On a new root, follows up every tracked event at or below it: `EventFinalized` if its
+    /// transaction is rooted, `EventRetracted` if the node no longer knows it. Returns
+    /// whether any were due.
+    async fn on_root(&self, root: u64, backfiller: &Backfiller) -> Result<bool> {
+        let Some(finality) = &self.finality else {
+            return Ok(false);
+        };
+        let sigs = finality.lock().unwrap().due_signatures(root);
+        if sigs.is_empty() {
+            return Ok(false);
+        }
+        let statuses = backfiller.tx_finality(&sigs).await?;
+        let due = finality.lock().unwrap().take_due(root);
+
+        let mut retrack = Vec::new();
+        for (slot, ev) in due {
+            let status = sigs
+                .binary_search_by(|s| s.as_str().cmp(&ev.signature))
+                .map_or(TxFinality::Dropped, |i| statuses[i]);
+            let follow_up = match status {
+                TxFinality::Finalized => ev.follow_up(EventType::EventFinalized),
+                TxFinality::Dropped if root >= slot + RETRACT_MARGIN => {
+                    ev.follow_up(EventType::EventRetracted)
+                }
+                TxFinality::Dropped => {
+                    retrack.push((slot, ev));
+                    continue;
+                }
+                TxFinality::Pending(landed) => {
+                    retrack.push((landed.max(slot), ev));
+                    continue;
+                }
+            };
+            self.publish(&follow_up).await?;
+            let counter = match follow_up.event_type {
+                EventType::EventFinalized => &self.metrics.events_finalized_total,
+                _ => &self.metrics.events_retracted_total,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut finality = finality.lock().unwrap();
+        for (slot, ev) in retrack {
+            finality.track(slot, [ev]);
+        }
+        self.metrics
+            .events_pending_finality
+            .store(finality.len() as u64, Ordering::Relaxed);
+        Ok(true)
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if args.replay.is_some() {
+        args.source = SourceKind::Replay;
+    }
+    if args.record.is_some() && !matches!(args.source, SourceKind::Ws) {
+        bail!("--record captures logsSubscribe frames; it needs the ws source");
+    }
+    if let Some(path) = &args.config {
+        return run_config(&args, path).await;
+    }
+
+    let metrics = Arc::new(Metrics::default());
+    if args.command.is_none() {
+        spawn_metrics_endpoint(&args, vec![metrics.clone()]);
+    }
+    run(args, metrics).await
+}
 
-    let producer: FutureProducer = ClientConfig::new()
-        .set("bootstrap.servers", &args.kafka_brokers)
-        .set("message.timeout.ms", "5000")
-        .create()
-        .context("create kafka producer")?;
+/// `--config`: every source runs as its own pipeline, restarted with backoff if it fails,
+/// sharing the sink settings and the metrics endpoint.
+async fn run_config(args: &Args, path: &Path) -> Result<()> {
+    if !matches!(args.source, SourceKind::Ws) || args.record.is_some() || args.command.is_some() {
+        bail!("--config runs ws pipelines; --source, --record, --replay and backfill do not apply");
+    }
+    let pipelines: Vec<(Args, Arc<Metrics>)> = config::load(path)?
+        .iter()
+        .map(|source| {
+            let metrics = Metrics {
+                source: Some(source.name.clone()),
+                ..Default::default()
+            };
+            (args.for_source(source), Arc::new(metrics))
+        })
+        .collect();
+    spawn_metrics_endpoint(args, pipelines.iter().map(|(_, m)| m.clone()).collect());
+
+    let mut tasks = JoinSet::new();
+    for (args, metrics) in pipelines {
+        tasks.spawn(supervise_pipeline(args, metrics));
+    }
+    while tasks.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn supervise_pipeline(args: Args, metrics: Arc<Metrics>) {
+    let name = metrics.source.clone().unwrap_or_default();
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+    loop {
+        let started = Instant::now();
+        let Err(e) = run(args.clone(), metrics.clone()).await else {
+            return;
+        };
+        if started.elapsed() >= PIPELINE_STABLE {
+            backoff.reset();
+        }
+        let delay = backoff.next_delay();
+        eprintln!("[{name}] pipeline failed, restarting in {delay:?}: {e:?}");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return,
+            _ = tokio::time::sleep(delay) => {}
+        }
+    }
+}
 
+/// One pipeline: a program on one cluster, until shutdown (or, for a replay or a backfill
+/// run, until the input is exhausted).
+async fn run(args: Args, metrics: Arc<Metrics>) -> Result<()> {
     let commitment = match args.commitment.as_str() {
         "processed" => CommitmentConfig::processed(),
         "confirmed" => CommitmentConfig::confirmed(),
         _ => CommitmentConfig::finalized(),
     };
+    let program_id = args
+        .program_id
+        .context("--program-id is required without --config")?;
+    let ctx = IngestCtx {
+        cluster: args.cluster.clone(),
+        program_id: program_id.to_bytes().into(),
+        commitment: args.commitment.clone(),
+    };
 
-    // Solana 1.18.x: logs_subscribe is an instance method on PubsubClient.
-    let client = PubsubClient::new(&args.solana_ws_url)
-        .await
-        .context("pubsub connect")?;
+    if let SourceKind::Accounts = args.source {
+        if args.command.is_some() {
+            bail!("backfill replays logs; it does not apply to the accounts source");
+        }
+        let sink = open_sink(
+            &args,
+            Output::OfferStates,
+            &args.spool_dir,
+            metrics.spool.clone(),
+        )
+        .await?;
+        let endpoints = args.source.endpoints(&args);
+        log_started(&args, endpoints, Output::OfferStates)?;
+        return accounts::run(
+            &ctx,
+            endpoints,
+            &args.solana_rpc_url,
+            program_id,
+            commitment,
+            sink.as_ref(),
+            &metrics,
+        )
+        .await;
+    }
 
-    let (mut stream, _unsub) = client
-        .logs_subscribe(
-            RpcTransactionLogsFilter::Mentions(vec![args.program_id.clone()]),
-            RpcTransactionLogsConfig {
-                commitment: Some(commitment),
-            },
+    let publisher = Publisher {
+        sink: open_sink(
+            &args,
+            Output::Events,
+            &args.spool_dir,
+            metrics.spool.clone(),
         )
-        .await
-        .context("logs_subscribe")?;
+        .await?,
+        quarantine: open_sink(
+            &args,
+            Output::Quarantine,
+            &args.spool_dir.join("quarantine"),
+            metrics.quarantine_spool.clone(),
+        )
+        .await?,
+        admin: open_sink(
+            &args,
+            Output::Admin,
+            &args.spool_dir.join("admin"),
+            metrics.admin_spool.clone(),
+        )
+        .await?,
+        metrics: metrics.clone(),
+        finality: (!commitment.is_finalized()).then(Mutex::default),
+        wire: wire_format(&args).await?,
+    };
+    let backfiller = Backfiller::new(&args.solana_rpc_url, program_id, commitment);
 
-    eprintln!(
-        "listener started: program_id={} ws={} topic={} commitment={}",
-        args.program_id, args.solana_ws_url, args.kafka_topic, args.commitment
-    );
+    if let (Some(_), SourceKind::Replay) = (&args.command, args.source) {
+        bail!("backfill reads the RPC node; it does not apply to a replay");
+    }
+    if let Some(Command::Backfill { from_slot, to_slot }) = args.command {
+        let range = Range {
+            from_slot: Some(from_slot),
+            to_slot: Some(to_slot),
+            ..Default::default()
+        };
+        let n = backfill(&backfiller, &publisher, &ctx, &range, None).await?;
+        publisher.flush().await?;
+        let spooled = metrics.spool.spooled.load(Ordering::Relaxed);
+        if spooled > 0 {
+            eprintln!(
+                "warning: {spooled} records left in {}; rerun to deliver",
+                args.spool_dir.display()
+            );
+        }
+        eprintln!("backfill done: slots {from_slot}..={to_slot} txs={n}");
+        return Ok(());
+    }
+
+    if let (Some(finality), false) = (
+        &publisher.finality,
+        matches!(args.source, SourceKind::Replay),
+    ) {
+        if let Some(cp) = checkpoint::load(&args.checkpoint_file)? {
+            let mut finality = finality.lock().unwrap();
+            finality.restore(cp.pending);
+            metrics
+                .events_pending_finality
+                .store(finality.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    let endpoints = args.source.endpoints(&args);
+    log_started(&args, endpoints, Output::Events)?;
+
+    let providers: Vec<Arc<ProviderStats>> = endpoints
+        .iter()
+        .map(|url| Arc::new(ProviderStats::new(url)))
+        .collect();
+    *metrics.providers.lock().unwrap() = providers.clone();
+
+    let (feed_tx, mut feed) = mpsc::channel(1024);
+    let cooldown = Duration::from_secs(args.provider_cooldown_secs);
+    let roots = publisher.finality.is_some();
+    let recorder = match &args.record {
+        Some(path) => Some(Arc::new(replay::Recorder::create(path)?)),
+        None => None,
+    };
+    for (idx, stats) in providers.iter().enumerate() {
+        let program = program_id.to_string();
+        let (stats, feed_tx) = (stats.clone(), feed_tx.clone());
+        match args.source {
+            SourceKind::Ws => {
+                let recorder = recorder.clone();
+                tokio::spawn(provider::run_ws(
+                    idx, program, commitment, roots, recorder, cooldown, stats, feed_tx,
+                ));
+            }
+            SourceKind::Grpc => {
+                let x_token = args.grpc_x_token.clone();
+                tokio::spawn(grpc::run_grpc(
+                    idx, program, commitment, roots, x_token, cooldown, stats, feed_tx,
+                ));
+            }
+            SourceKind::Poll => {
+                let interval = Duration::from_millis(args.poll_interval_ms);
+                tokio::spawn(poll::run_poll(
+                    idx, program_id, commitment, roots, interval, cooldown, stats, feed_tx,
+                ));
+            }
+            SourceKind::Replay => {
+                let speed = args.replay_speed;
+                tokio::spawn(async move {
+                    if let Err(e) = replay::run_replay(idx, speed, stats, feed_tx).await {
+                        eprintln!("replay failed: {e:?}");
+                    }
+                });
+            }
+            SourceKind::Accounts => unreachable!("accounts source returns early"),
+        }
+    }
+    drop(feed_tx);
+
+    let health = Health {
+        max_lag_ms: args.provider_max_lag_ms,
+        max_miss_ratio: args.provider_max_miss_ratio,
+        cooldown,
+    };
 
     // graceful shutdown on ctrl-c
     let shutdown = tokio::signal::ctrl_c();
     tokio::pin!(shutdown);
 
+    // Merge loop. Whenever live coverage may have a hole (startup, every provider down, a
+    // failed fetch) live notifications are parked and the next catch-up backfills from
+    // the checkpoint; providers subscribe before that so nothing falls in between.
+    let mut seen = SeenCache::new(DEDUPE_CAPACITY);
+    // a replay is self-contained: nothing to catch up on
+    let mut needs_catch_up = !matches!(args.source, SourceKind::Replay);
+    let mut catch_up_backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(30));
+    let mut catch_up_at = Instant::now();
+    let mut down_since: Option<Instant> = None;
+    let mut last_root = 0u64;
+    let mut housekeeping = tokio::time::interval(HOUSEKEEPING_INTERVAL);
+    let mut spool_flush = tokio::time::interval(SPOOL_FLUSH_INTERVAL);
+
     loop {
+        let connected = providers
+            .iter()
+            .filter(|p| p.connected.load(Ordering::Relaxed))
+            .count();
+        metrics
+            .ws_connected
+            .store(connected as u64, Ordering::Relaxed);
+
+        if needs_catch_up && connected > 0 && Instant::now() >= catch_up_at {
+            match catch_up(&args, &backfiller, &publisher, &ctx, &mut seen).await {
+                Ok(()) => {
+                    needs_catch_up = false;
+                    catch_up_backoff.reset();
+                }
+                Err(e) => {
+                    let delay = catch_up_backoff.next_delay();
+                    eprintln!("catch-up failed, retrying in {delay:?}: {e:?}");
+                    catch_up_at = Instant::now() + delay;
+                }
+            }
+        }
+
         tokio::select! {
             _ = &mut shutdown => {
                 eprintln!("shutdown requested");
                 break;
             }
-            maybe_msg = stream.next() => {
-                let Some(resp) = maybe_msg else { break; };
-                let value = resp.value;
-                if value.err.is_some() {
-                    continue;
+            maybe_feed = feed.recv() => {
+                let Some(msg) = maybe_feed else { break; };
+                match msg {
+                    Feed::Up(idx) => {
+                        eprintln!("provider up: {}", providers[idx].url);
+                        if let Some(since) = down_since.take() {
+                            let down_for = since.elapsed();
+                            metrics.record_reconnect(down_for);
+                            eprintln!("live coverage restored after {down_for:?}");
+                        }
+                    }
+                    Feed::Down(idx) => {
+                        eprintln!("provider down: {}", providers[idx].url);
+                        if providers.iter().all(|p| !p.connected.load(Ordering::Relaxed)) {
+                            down_since.get_or_insert_with(Instant::now);
+                            needs_catch_up = true;
+                        }
+                    }
+                    Feed::Tx { provider, signature, at, fetched } => {
+                        let obs = seen.observe(&signature, provider, at);
+                        if !obs.repeat {
+                            providers[provider].record_seen(obs.first, obs.lag);
+                        }
+                        if needs_catch_up || seen.is_processed(&signature) {
+                            continue;
+                        }
+                        match ingest_live(&args, &backfiller, &publisher, &ctx, &signature, fetched).await {
+                            Ok(()) => seen.mark_processed(&signature),
+                            Err(e) => {
+                                eprintln!("live ingest failed, falling back to backfill: {e:?}");
+                                needs_catch_up = true;
+                            }
+                        }
+                    }
+                    Feed::Root(root) => {
+                        if root > last_root {
+                            last_root = root;
+                            match publisher.on_root(root, &backfiller).await {
+                                Ok(true) if !matches!(args.source, SourceKind::Replay) => {
+                                    if let Err(e) = store_pending(&args.checkpoint_file, &publisher) {
+                                        eprintln!("checkpoint update at root {root} failed: {e:?}");
+                                    }
+                                }
+                                Ok(_) => {}
+                                Err(e) => eprintln!("finality check at root {root} failed: {e:?}"),
+                            }
+                        }
+                    }
                 }
-
-                let slot = resp.context.slot;
-                let sig = value.signature.clone();
-                for (log_index, line) in value.logs.iter().enumerate() {
-                    // `msg!()` becomes: "Program log: <payload>"
-                    const PREFIX: &str = "Program log: ";
-                    let Some(json) = line.strip_prefix(PREFIX) else { continue; };
-                    if !json.contains(r#""event":"#) {
-                        continue;
+            }
+            _ = housekeeping.tick() => {
+                for mask in seen.settle(MISS_WINDOW, Instant::now()) {
+                    for (idx, p) in providers.iter().enumerate() {
+                        if mask & (1 << idx) == 0 && p.active() {
+                            p.record_missed();
+                        }
                     }
-
-                    let parsed: OnchainLogEvent = match serde_json::from_str(json) {
-                        Ok(v) => v,
-                        Err(_) => continue,
-                    };
-
-                    let event_type = match parsed.event.as_str() {
-                        "OfferCreated" => EventType::OfferCreated,
-                        "OfferFilled" => EventType::OfferFilled,
-                        "OfferCancelled" => EventType::OfferCancelled,
-                        _ => continue,
-                    };
-
-                    // For demo we don't have instruction_index from logsSubscribe response.
-                    let instruction_index = 0u32;
-                    let event_id = format!("{}:{}:{}", sig, instruction_index, log_index);
-
-                    let ev = NormalizedEvent {
-                        event_id,
-                        event_type,
-                        cluster: args.cluster.clone(),
-                        slot,
-                        signature: sig.clone(),
-                        program_id: args.program_id.clone(),
-                        offer_id: parsed.offer_id.clone(),
-                        maker: parsed.maker.clone(),
-                        taker: parsed.taker.clone(),
-                        mint_a: parsed.mint_a.clone(),
-                        mint_b: parsed.mint_b.clone(),
-                        amount_a: parsed.amount_a.to_string(),
-                        amount_b: parsed.amount_b.to_string(),
-                        commitment: args.commitment.clone(),
-                        ts_ingest_ms: now_ms(),
-                    };
-
-                    let payload = serde_json::to_string(&ev).context("serialize event")?;
-
-                    // key = offer_id, to keep same order per offer in Kafka partitioning
-                    let record = FutureRecord::to(&args.kafka_topic)
-                        .key(&ev.offer_id)
-                        .payload(&payload);
-
-                    // at-least-once: we don't de-dupe here; consumers handle idempotency via event_id
-                    let _ = producer.send(record, std::time::Duration::from_secs(5)).await;
                 }
+                health.evaluate(&providers);
+            }
+            _ = spool_flush.tick() => {
+                if let Err(e) = publisher.flush().await {
+                    eprintln!("spool flush failed: {e:?}");
+                }
+            }
+        }
+    }
+
+    if let SourceKind::Replay = args.source {
+        publisher.flush().await?;
+        eprintln!("replay done");
+    }
+    Ok(())
+}
+
+async fn open_sink(
+    args: &Args,
+    output: Output,
+    spool_dir: &Path,
+    stats: Arc<SpoolStats>,
+) -> Result<Box<dyn Sink>> {
+    let sink = args.sink.open(args, output).await?;
+    let sink = Box::new(SpoolingSink::open(sink, spool_dir, stats)?);
+    match &args.signing_key {
+        Some(path) => {
+            let signer = EventSigner::from_keypair_file(path, args.signing_key_id.as_deref())?;
+            let (topic, subject, _) = SinkKind::names(args, output);
+            let topic = match args.sink {
+                SinkKind::Nats => subject,
+                _ => topic,
+            };
+            Ok(Box::new(SigningSink::new(sink, signer, topic)))
+        }
+        None => Ok(sink),
+    }
+}
+
+/// Quarantine records and offer states stay JSON; only the events topic has a schema.
+async fn wire_format(args: &Args) -> Result<WireFormat> {
+    match args.encoding {
+        Encoding::Json => Ok(WireFormat::json(args.event_schema_version)),
+        Encoding::Protobuf => {
+            if matches!(args.sink, SinkKind::Jsonl | SinkKind::Stdout) {
+                bail!("protobuf encoding is binary; use the kafka or nats sink");
             }
+            let topic = match args.sink {
+                SinkKind::Nats => &args.nats_subject,
+                _ => &args.kafka_topic,
+            };
+            let registry = args
+                .schema_registry_url
+                .as_deref()
+                .map(|url| (url, topic.as_str()));
+            WireFormat::protobuf(registry).await
         }
     }
+}
+
+fn log_started(args: &Args, endpoints: &[String], output: Output) -> Result<()> {
+    if endpoints.is_empty() || endpoints.len() > 64 {
+        bail!("{:?} source: expected 1..=64 endpoints", args.source);
+    }
+    eprintln!(
+        "listener started: program_id={} source={:?} endpoints={} sink={} commitment={} metrics={}",
+        args.program_id.map(|p| p.to_string()).unwrap_or_default(),
+        args.source,
+        endpoints.join(","),
+        args.sink.describe(args, output),
+        args.commitment,
+        args.metrics_addr
+    );
+    Ok(())
+}
+
+fn spawn_metrics_endpoint(args: &Args, metrics: Vec<Arc<Metrics>>) {
+    let addr = args.metrics_addr.clone();
+    tokio::spawn(async move {
+        if let Err(e) = metrics::serve(&addr, metrics).await {
+            eprintln!("metrics endpoint failed: {e:?}");
+        }
+    });
+}
 
+/// Backfills from the checkpoint and marks what it published as seen. Without a
+/// checkpoint, pins the current tip so a later outage has a lower bound to backfill from.
+async fn catch_up(
+    args: &Args,
+    backfiller: &Backfiller,
+    publisher: &Publisher,
+    ctx: &IngestCtx,
+    seen: &mut SeenCache,
+) -> Result<()> {
+    match checkpoint::load(&args.checkpoint_file)? {
+        Some(cp) => {
+            let range = Range::after(cp.signature.clone(), cp.slot);
+            let mut done = Vec::new();
+            let n = backfill(
+                backfiller,
+                publisher,
+                ctx,
+                &range,
+                Some((&args.checkpoint_file, &mut done)),
+            )
+            .await?;
+            for (sig, _) in done {
+                seen.mark_processed(&sig);
+            }
+            eprintln!(
+                "backfill: from slot={} sig={} txs={n}",
+                cp.slot, cp.signature
+            );
+        }
+        None => {
+            if let Some((signature, slot)) = backfiller.latest().await? {
+                publisher.store_checkpoint(&args.checkpoint_file, slot, signature)?;
+            }
+        }
+    }
     Ok(())
 }
 
+async fn ingest_live(
+    args: &Args,
+    backfiller: &Backfiller,
+    publisher: &Publisher,
+    ctx: &IngestCtx,
+    signature: &str,
+    fetched: Option<FetchedTx>,
+) -> Result<()> {
+    let tx = match fetched {
+        Some(tx) if matches!(args.source, SourceKind::Replay) => tx,
+        Some(tx) if tx.info.block_time.is_some() => tx,
+        Some(mut tx) => {
+            // gRPC: the update carries everything but the block time
+            match backfiller.block_time(tx.slot).await {
+                Ok(t) => tx.info.block_time = t,
+                Err(e) => eprintln!("block time for slot {} unavailable: {e:?}", tx.slot),
+            }
+            tx
+        }
+        None => match fetch_live(backfiller, signature).await? {
+            Some(tx) => tx,
+            None => return Ok(()),
+        },
+    };
+    publisher
+        .publish_tx(ctx, signature, &tx, backfiller)
+        .await?;
+    if let SourceKind::Replay = args.source {
+        return Ok(());
+    }
+    publisher.store_checkpoint(&args.checkpoint_file, tx.slot, signature.to_string())
+}
+
+/// Rewrites the checkpoint's pending events after a root followed some of them up.
+fn store_pending(path: &Path, publisher: &Publisher) -> Result<()> {
+    let Some(cp) = checkpoint::load(path)? else {
+        return Ok(());
+    };
+    publisher.store_checkpoint(path, cp.slot, cp.signature)
+}
+
+/// getTransaction for a just-notified signature, retried until the node has it.
+async fn fetch_live(backfiller: &Backfiller, signature: &str) -> Result<Option<FetchedTx>> {
+    let mut attempt = 1;
+    loop {
+        match backfiller.fetch_tx(signature).await {
+            Ok(tx) => return Ok(tx),
+            Err(e) if attempt >= LIVE_FETCH_ATTEMPTS => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(LIVE_FETCH_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Replays historical transactions in chronological order. With `track`, the checkpoint
+/// is advanced per transaction and processed signatures are reported back for dedupe.
+async fn backfill(
+    backfiller: &Backfiller,
+    publisher: &Publisher,
+    ctx: &IngestCtx,
+    range: &Range,
+    mut track: Option<(&PathBuf, &mut Vec<(String, u64)>)>,
+) -> Result<usize> {
+    let sigs = backfiller.signatures(range).await?;
+    let n = sigs.len();
+    for (sig, _) in sigs {
+        let Some(tx) = backfiller.fetch_tx(&sig).await? else {
+            continue;
+        };
+        let slot = tx.slot;
+        publisher.publish_tx(ctx, &sig, &tx, backfiller).await?;
+        publisher
+            .metrics
+            .backfilled_txs_total
+            .fetch_add(1, Ordering::Relaxed);
+
+        if let Some((path, seen)) = track.as_mut() {
+            publisher.store_checkpoint(path, slot, sig.clone())?;
+            seen.push((sig, slot));
+        }
+    }
+    Ok(n)
+}
@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use orderflow_common::sink::SpoolStats;
+
+use crate::provider::ProviderStats;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// name, type, help, value of one per-pipeline metric family
+type Family = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&Metrics) -> f64,
+);
+
+/// name, type, help, value of one per-provider metric family
+type ProviderFamily = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&ProviderStats) -> u64,
+);
+
+/// Counters of one ingest pipeline, exposed in Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub ws_connected: AtomicU64,
+    pub ws_reconnects_total: AtomicU64,
+    /// cumulative time spent without a live subscription
+    pub ws_disconnected_ms_total: AtomicU64,
+    pub ws_last_reconnect_ms: AtomicU64,
+    pub backfilled_txs_total: AtomicU64,
+    pub events_published_total: AtomicU64,
+    pub events_finalized_total: AtomicU64,
+    pub events_retracted_total: AtomicU64,
+    pub events_pending_finality: AtomicU64,
+    pub account_updates_total: AtomicU64,
+    pub offer_states_published_total: AtomicU64,
+    pub parse_failures_total: AtomicU64,
+    pub truncated_txs_total: AtomicU64,
+    pub truncated_recovered_total: AtomicU64,
+    pub spool: Arc<SpoolStats>,
+    pub quarantine_spool: Arc<SpoolStats>,
+    pub admin_events_published_total: AtomicU64,
+    pub admin_spool: Arc<SpoolStats>,
+    /// replaced whenever the pipeline (re)starts
+    pub providers: Mutex<Vec<Arc<ProviderStats>>>,
+    /// pipeline name from `--config`; becomes the `source` label
+    pub source: Option<String>,
+}
+
+impl Metrics {
+    pub fn record_reconnect(&self, down_for: Duration) {
+        let ms = down_for.as_millis() as u64;
+        self.ws_reconnects_total.fetch_add(1, Ordering::Relaxed);
+        self.ws_disconnected_ms_total
+            .fetch_add(ms, Ordering::Relaxed);
+        self.ws_last_reconnect_ms.store(ms, Ordering::Relaxed);
+    }
+
+    /// `{source="<name>"}` when several pipelines share the endpoint, else nothing.
+    fn labels(&self, extra: Option<(&str, &str)>) -> String {
+        let pairs: Vec<String> = self
+            .source
+            .iter()
+            .map(|s| ("source", s.as_str()))
+            .chain(extra)
+            .map(|(k, v)| format!("{k}=\"{v}\""))
+            .collect();
+        match pairs.is_empty() {
+            true => String::new(),
+            false => format!("{{{}}}", pairs.join(",")),
+        }
+    }
+}
+
+fn get(a: &AtomicU64) -> u64 {
+    a.load(Ordering::Relaxed)
+}
+
+/// Prometheus text for every pipeline, one HELP/TYPE block per family.
+pub fn render(all: &[Arc<Metrics>]) -> String {
+    let mut out = String::new();
+    let families: [Family; 20] = [
+        (
+            "listener_ws_connected",
+            "gauge",
+            "WS providers with a live logs subscription",
+            |m| get(&m.ws_connected) as f64,
+        ),
+        (
+            "listener_ws_reconnects_total",
+            "counter",
+            "live coverage restored after every provider was down",
+            |m| get(&m.ws_reconnects_total) as f64,
+        ),
+        (
+            "listener_ws_disconnected_seconds_total",
+            "counter",
+            "cumulative time without any live provider",
+            |m| get(&m.ws_disconnected_ms_total) as f64 / 1000.0,
+        ),
+        (
+            "listener_ws_last_reconnect_seconds",
+            "gauge",
+            "duration of the most recent outage",
+            |m| get(&m.ws_last_reconnect_ms) as f64 / 1000.0,
+        ),
+        (
+            "listener_backfilled_txs_total",
+            "counter",
+            "transactions replayed via RPC backfill",
+            |m| get(&m.backfilled_txs_total) as f64,
+        ),
+        (
+            "listener_events_published_total",
+            "counter",
+            "normalized events handed to the producer",
+            |m| get(&m.events_published_total) as f64,
+        ),
+        (
+            "listener_events_finalized_total",
+            "counter",
+            "EventFinalized follow-ups published",
+            |m| get(&m.events_finalized_total) as f64,
+        ),
+        (
+            "listener_events_retracted_total",
+            "counter",
+            "EventRetracted follow-ups published (confirmed events that never finalized)",
+            |m| get(&m.events_retracted_total) as f64,
+        ),
+        (
+            "listener_events_pending_finality",
+            "gauge",
+            "confirmed events still waiting for a follow-up",
+            |m| get(&m.events_pending_finality) as f64,
+        ),
+        (
+            "listener_account_updates_total",
+            "counter",
+            "accounts source: EscrowState notifications received",
+            |m| get(&m.account_updates_total) as f64,
+        ),
+        (
+            "listener_offer_states_published_total",
+            "counter",
+            "accounts source: offer status changes handed to the producer",
+            |m| get(&m.offer_states_published_total) as f64,
+        ),
+        (
+            "listener_parse_failures_total",
+            "counter",
+            "transaction parts that yielded no event, published to the quarantine topic",
+            |m| get(&m.parse_failures_total) as f64,
+        ),
+        (
+            "listener_truncated_txs_total",
+            "counter",
+            "transactions whose logs were truncated before all their events",
+            |m| get(&m.truncated_txs_total) as f64,
+        ),
+        (
+            "listener_truncated_recovered_total",
+            "counter",
+            "events rebuilt from instruction data and account state after truncation",
+            |m| get(&m.truncated_recovered_total) as f64,
+        ),
+        (
+            "listener_quarantine_spooled_records",
+            "gauge",
+            "quarantine records waiting in the on-disk spool",
+            |m| get(&m.quarantine_spool.spooled) as f64,
+        ),
+        (
+            "listener_admin_events_published_total",
+            "counter",
+            "denylist changes handed to the admin topic's producer",
+            |m| get(&m.admin_events_published_total) as f64,
+        ),
+        (
+            "listener_admin_spooled_records",
+            "gauge",
+            "admin events waiting in the on-disk spool",
+            |m| get(&m.admin_spool.spooled) as f64,
+        ),
+        (
+            "listener_sink_delivery_failures_total",
+            "counter",
+            "failed sink deliveries (record spooled or replay stalled)",
+            |m| get(&m.spool.delivery_failures_total) as f64,
+        ),
+        (
+            "listener_sink_spooled_records",
+            "gauge",
+            "records waiting in the on-disk spool; alert when this grows",
+            |m| get(&m.spool.spooled) as f64,
+        ),
+        (
+            "listener_sink_replayed_total",
+            "counter",
+            "spooled records delivered after the sink recovered",
+            |m| get(&m.spool.replayed_total) as f64,
+        ),
+    ];
+    for (name, kind, help, value) in families {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+        for m in all {
+            let _ = writeln!(out, "{name}{} {}", m.labels(None), value(m));
+        }
+    }
+    render_providers(all, &mut out);
+    out
+}
+
+fn render_providers(all: &[Arc<Metrics>], out: &mut String) {
+    let providers: Vec<_> = all
+        .iter()
+        .map(|m| (m, m.providers.lock().unwrap().clone()))
+        .collect();
+    if providers.iter().all(|(_, p)| p.is_empty()) {
+        return;
+    }
+    let families: [ProviderFamily; 8] = [
+        (
+            "listener_provider_connected",
+            "gauge",
+            "1 while subscribed",
+            |p| p.connected.load(Ordering::Relaxed) as u64,
+        ),
+        (
+            "listener_provider_demoted",
+            "gauge",
+            "1 while sitting out a demotion",
+            |p| p.demoted.load(Ordering::Relaxed) as u64,
+        ),
+        (
+            "listener_provider_notifications_total",
+            "counter",
+            "notifications delivered",
+            |p| p.notifications_total.load(Ordering::Relaxed),
+        ),
+        (
+            "listener_provider_first_total",
+            "counter",
+            "notifications delivered before any other provider",
+            |p| p.first_total.load(Ordering::Relaxed),
+        ),
+        (
+            "listener_provider_missed_total",
+            "counter",
+            "transactions other providers delivered and this one did not",
+            |p| p.missed_total.load(Ordering::Relaxed),
+        ),
+        (
+            "listener_provider_lag_ms",
+            "gauge",
+            "moving average delay behind the first delivery",
+            |p| p.lag_ms.load(Ordering::Relaxed),
+        ),
+        (
+            "listener_provider_reconnects_total",
+            "counter",
+            "resubscriptions of this provider",
+            |p| p.reconnects_total.load(Ordering::Relaxed),
+        ),
+        (
+            "listener_provider_demotions_total",
+            "counter",
+            "times this provider was demoted",
+            |p| p.demotions_total.load(Ordering::Relaxed),
+        ),
+    ];
+    for (name, kind, help, value) in families {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+        for (m, providers) in &providers {
+            for p in providers {
+                let _ = writeln!(
+                    out,
+                    "{name}{} {}",
+                    m.labels(Some(("provider", &p.url))),
+                    value(p)
+                );
+            }
+        }
+    }
+}
+
+/// Minimal `/metrics` endpoint: every request gets the current snapshot.
+pub async fn serve(addr: &str, metrics: Vec<Arc<Metrics>>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("bind metrics {addr}"))?;
+    loop {
+        let (mut sock, _) = listener.accept().await.context("accept metrics")?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let _ = sock.read(&mut buf).await;
+            let body = render(&metrics);
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = sock.write_all(resp.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipelines_share_one_family_block() {
+        let pipeline = |name: &str, published: u64| {
+            let m = Metrics {
+                source: Some(name.to_string()),
+                ..Default::default()
+            };
+            m.events_published_total.store(published, Ordering::Relaxed);
+            *m.providers.lock().unwrap() = vec![Arc::new(ProviderStats::new("wss://a"))];
+            Arc::new(m)
+        };
+        let out = render(&[pipeline("devnet", 3), pipeline("mainnet", 5)]);
+
+        assert_eq!(
+            out.matches("# HELP listener_events_published_total ")
+                .count(),
+            1
+        );
+        assert!(out.contains("listener_events_published_total{source=\"devnet\"} 3\n"));
+        assert!(out.contains("listener_events_published_total{source=\"mainnet\"} 5\n"));
+        assert!(out
+            .contains("listener_provider_connected{source=\"mainnet\",provider=\"wss://a\"} 0\n"));
+
+        let single = render(&[Arc::new(Metrics::default())]);
+        assert!(single.contains("\nlistener_events_published_total 0\n"));
+        assert!(!single.contains("listener_provider_connected"));
+    }
+}
@@ -0,0 +1,743 @@
+use orderflow_common::{
+    now_ms, AdminEvent, EventType, NormalizedEvent, OnchainAdminLogEvent, OnchainLogEvent,
+    OnchainRfqLogEvent, Pubkey58, QuarantineReason, Signature58, U64String,
+};
+use solana_sdk::pubkey::Pubkey;
+
+/// Per-source constants stamped onto every event.
+#[derive(Debug, Clone)]
+pub struct IngestCtx {
+    pub cluster: String,
+    pub program_id: Pubkey58,
+    pub commitment: String,
+}
+
+/// One transaction's instructions, from the message and `meta.innerInstructions`.
+#[derive(Debug, Clone, Default)]
+pub struct IxLayout {
+    /// top-level instructions in message order
+    pub outer: Vec<OuterIx>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OuterIx {
+    pub ix: Ix,
+    /// CPIs made while executing this instruction, in invocation order
+    pub inner: Vec<Ix>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Ix {
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+/// Runtime marker replacing everything past the per-transaction log byte limit.
+const LOG_TRUNCATED: &str = "Log truncated";
+
+/// What one transaction's logs yielded.
+#[derive(Debug, Default)]
+pub struct ParsedTx {
+    pub events: Vec<NormalizedEvent>,
+    /// denylist changes, for the admin topic
+    pub admin: Vec<AdminEvent>,
+    pub failures: Vec<ParseFailure>,
+    /// Logs were cut off (or the node kept none): our instructions that would have logged
+    /// an event but did not, with their positions. Rebuilt by `recover`.
+    pub unlogged: Vec<(String, Ix)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseFailure {
+    pub reason: QuarantineReason,
+    pub instruction: Option<String>,
+    pub log_index: Option<usize>,
+    pub detail: String,
+}
+
+/// Maps `invoke` frames onto instruction positions as the logs are walked.
+struct IxCursor<'a> {
+    layout: &'a IxLayout,
+    next_outer: usize,
+    outer: Option<usize>,
+    next_inner: usize,
+}
+
+impl<'a> IxCursor<'a> {
+    fn new(layout: &'a IxLayout) -> Self {
+        Self {
+            layout,
+            next_outer: 0,
+            outer: None,
+            next_inner: 0,
+        }
+    }
+
+    /// Position label for a new frame: `"<outer>"` at depth 1, `"<outer>.<inner>"` below.
+    /// Precompiles execute without logging, so top-level frames may skip ahead.
+    fn enter(&mut self, id: &str, depth: usize) -> Option<String> {
+        if depth == 1 {
+            let i = (self.next_outer..self.layout.outer.len())
+                .find(|&i| self.layout.outer[i].ix.program_id == id)?;
+            self.next_outer = i + 1;
+            self.outer = Some(i);
+            self.next_inner = 0;
+            return Some(i.to_string());
+        }
+        let outer = self.outer?;
+        let j = self.next_inner;
+        if self.layout.outer[outer].inner.get(j)?.program_id != id {
+            return None;
+        }
+        self.next_inner += 1;
+        Some(format!("{outer}.{j}"))
+    }
+}
+
+/// Runtime-emitted frame lines that bracket every instruction and CPI.
+#[derive(Debug, PartialEq, Eq)]
+enum Frame<'a> {
+    /// `Program <id> invoke [<depth>]`
+    Invoke(&'a str, usize),
+    /// `Program <id> success` / `Program <id> failed: ...`
+    Exit(&'a str),
+}
+
+/// Only the runtime writes these; program output always carries a `Program log:`-style
+/// prefix, which can never parse as a pubkey in the id position.
+fn frame_line(line: &str) -> Option<Frame<'_>> {
+    let (id, tail) = line.strip_prefix("Program ")?.split_once(' ')?;
+    id.parse::<Pubkey>().ok()?;
+    if let Some(depth) = tail
+        .strip_prefix("invoke [")
+        .and_then(|t| t.strip_suffix(']'))
+    {
+        return Some(Frame::Invoke(id, depth.parse().ok()?));
+    }
+    if tail == "success" || tail.starts_with("failed: ") {
+        return Some(Frame::Exit(id));
+    }
+    None
+}
+
+/// The instruction layout implied by the frame lines alone, for logs that arrive without
+/// their transaction (replayed WS frames). Instruction data and accounts are unknown, and
+/// instructions that never log an invoke line (precompiles) are missing, which shifts the
+/// top-level indexes after them.
+pub fn layout_from_logs(logs: &[String]) -> IxLayout {
+    let mut layout = IxLayout::default();
+    for line in logs {
+        let Some(Frame::Invoke(id, depth)) = frame_line(line) else {
+            continue;
+        };
+        let ix = Ix {
+            program_id: id.to_string(),
+            ..Default::default()
+        };
+        match (depth, layout.outer.last_mut()) {
+            (1, _) => layout.outer.push(OuterIx {
+                ix,
+                inner: Vec::new(),
+            }),
+            (_, Some(outer)) => outer.inner.push(ix),
+            (_, None) => {}
+        }
+    }
+    layout
+}
+
+/// Turn the log lines of one successful transaction into normalized events.
+/// Shared by every ingest path so all of them produce identical `event_id`s:
+/// `signature:<instruction>:log_index`, where `<instruction>` is the top-level instruction
+/// index, or `outer.inner` for events emitted from a CPI.
+///
+/// A transaction only has to *mention* the program to be delivered, so any other program
+/// in it could print an escrow-shaped JSON line. Events are accepted only while our
+/// program is the innermost executing frame; if the invoke/exit frames stop nesting
+/// properly the rest of the transaction is ignored (and reported as a failure).
+///
+/// Besides events (offer and RFQ events, and the admin's denylist changes), returns every
+/// line that should have been an event and was not, and the instructions whose events
+/// truncation cut off.
+pub fn parse_tx(
+    ctx: &IngestCtx,
+    signature: &str,
+    slot: u64,
+    logs: &[String],
+    layout: &IxLayout,
+) -> ParsedTx {
+    let mut out = ParsedTx::default();
+    let signature: Signature58 = match signature.parse() {
+        Ok(signature) => signature,
+        Err(e) => {
+            out.failures.push(ParseFailure {
+                reason: QuarantineReason::UndecodablePayload,
+                instruction: None,
+                log_index: None,
+                detail: format!("{e:#}"),
+            });
+            return out;
+        }
+    };
+    let mut cursor = IxCursor::new(layout);
+    let mut stack: Vec<(&str, String)> = Vec::new();
+    let mut logged = Vec::new();
+    for (log_index, line) in logs.iter().enumerate() {
+        // `msg!()` becomes: "Program log: <payload>"
+        const PREFIX: &str = "Program log: ";
+        let Some(json) = line.strip_prefix(PREFIX) else {
+            let position = match frame_line(line) {
+                Some(Frame::Invoke(id, depth)) if depth == stack.len() + 1 => {
+                    match cursor.enter(id, depth) {
+                        Some(position) => {
+                            stack.push((id, position));
+                            continue;
+                        }
+                        None => None,
+                    }
+                }
+                Some(Frame::Exit(id)) if stack.last().is_some_and(|(top, _)| *top == id) => {
+                    stack.pop();
+                    continue;
+                }
+                Some(_) => stack.last().map(|(_, position)| position.clone()),
+                None => continue,
+            };
+            out.failures.push(ParseFailure {
+                reason: QuarantineReason::InconsistentFrames,
+                instruction: position,
+                log_index: Some(log_index),
+                detail: format!("unexpected frame: {line}"),
+            });
+            break;
+        };
+        let Some((_, position)) = stack.last().filter(|(top, _)| ctx.program_id == *top) else {
+            continue;
+        };
+        if !json.contains(r#""event":"#) {
+            continue;
+        }
+
+        let event_id = format!("{}:{}:{}", signature, position, log_index);
+        let Some((event_type, parsed)) = decode_log_payload(json) else {
+            match serde_json::from_str::<OnchainAdminLogEvent>(json) {
+                Ok(parsed) => {
+                    logged.push(position.clone());
+                    out.admin.push(AdminEvent {
+                        event_id,
+                        event_type: parsed.event,
+                        cluster: ctx.cluster.clone(),
+                        slot,
+                        signature: signature.clone(),
+                        program_id: ctx.program_id.clone(),
+                        wallet: parsed.wallet,
+                        admin: parsed.admin,
+                        commitment: ctx.commitment.clone(),
+                        ts_ingest_ms: now_ms(),
+                    });
+                }
+                Err(_) => out.failures.push(ParseFailure {
+                    reason: QuarantineReason::UndecodablePayload,
+                    instruction: Some(position.clone()),
+                    log_index: Some(log_index),
+                    detail: "event payload does not decode".to_string(),
+                }),
+            }
+            continue;
+        };
+
+        logged.push(position.clone());
+
+        out.events.push(NormalizedEvent {
+            event_id,
+            event_type,
+            cluster: ctx.cluster.clone(),
+            slot,
+            signature: signature.clone(),
+            program_id: ctx.program_id.clone(),
+            offer_id: parsed.offer_id,
+            rfq_id: parsed.rfq_id,
+            maker: parsed.maker,
+            taker: parsed.taker,
+            mint_a: parsed.mint_a,
+            mint_b: parsed.mint_b,
+            amount_a: parsed.amount_a.into(),
+            amount_b: parsed.amount_b.into(),
+            client_ref: parsed.client_ref,
+            vault_amount: parsed.vault_amount.map(U64String::from),
+            block_time: None,
+            fee_lamports: None,
+            fee_payer: None,
+            compute_units_consumed: None,
+            priority_fee_lamports: None,
+            commitment: ctx.commitment.clone(),
+            ts_ingest_ms: now_ms(),
+            ref_event_id: None,
+        });
+    }
+
+    if logs.is_empty() || logs.last().is_some_and(|l| l == LOG_TRUNCATED) {
+        out.unlogged = program_ixs(layout, &ctx.program_id)
+            .filter(|(position, ix)| {
+                !logged.contains(position) && escrow_client::emits_event(&ix.data)
+            })
+            .map(|(position, ix)| (position, ix.clone()))
+            .collect();
+    }
+    out
+}
+
+/// Our program's instructions in the transaction, top-level and CPI, with their positions.
+fn program_ixs<'a>(
+    layout: &'a IxLayout,
+    program_id: &'a str,
+) -> impl Iterator<Item = (String, &'a Ix)> {
+    layout.outer.iter().enumerate().flat_map(move |(i, outer)| {
+        let top = (outer.ix.program_id == program_id).then(|| (i.to_string(), &outer.ix));
+        let inner = outer
+            .inner
+            .iter()
+            .enumerate()
+            .filter(move |(_, ix)| ix.program_id == program_id)
+            .map(move |(j, ix)| (format!("{i}.{j}"), ix));
+        top.into_iter().chain(inner)
+    })
+}
+
+/// Decode one `Program log:` JSON payload; RFQ payloads are mapped onto the offer shape.
+pub fn decode_log_payload(json: &str) -> Option<(EventType, OnchainLogEvent)> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let event_type = match value.get("event")?.as_str()? {
+        "OfferCreated" => EventType::OfferCreated,
+        "OfferFilled" => EventType::OfferFilled,
+        "OfferCancelled" => EventType::OfferCancelled,
+        "OfferForceCancelled" => EventType::OfferForceCancelled,
+        "RfqCreated" => EventType::RfqCreated,
+        "QuoteSubmitted" => EventType::QuoteSubmitted,
+        "QuoteAccepted" => EventType::QuoteAccepted,
+        "QuoteRefunded" => EventType::QuoteRefunded,
+        "RfqCancelled" => EventType::RfqCancelled,
+        _ => return None,
+    };
+
+    let parsed = match event_type {
+        EventType::RfqCreated
+        | EventType::QuoteSubmitted
+        | EventType::QuoteAccepted
+        | EventType::QuoteRefunded
+        | EventType::RfqCancelled => serde_json::from_value::<OnchainRfqLogEvent>(value)
+            .ok()?
+            .into(),
+        _ => serde_json::from_value::<OnchainLogEvent>(value).ok()?,
+    };
+    Some((event_type, parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orderflow_common::AdminEventType;
+
+    const ESCROW: &str = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk";
+    const EVIL: &str = "Stake11111111111111111111111111111111111111";
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const SIG: &str =
+        "4FpiGQPEkuh3GqVkbqievxrpE8w9mZkvVVmh8CksP2GQuedKf7xbmPaXTv31EnPqixdMxZjkvVuemDbBM9qHttoo";
+    const MAKER: &str = "EeFX3g6HePRn5ipNu5ey3jiYWRVmeK3Frxec2ZwqKs6W";
+    const TAKER: &str = "7Np41oeYqPefeNQEHSv1UDhYrehxin3NStELsSKCT4K2";
+    const MINT: &str = "So11111111111111111111111111111111111111112";
+
+    fn ctx() -> IngestCtx {
+        IngestCtx {
+            cluster: "localnet".into(),
+            program_id: ESCROW.parse().unwrap(),
+            commitment: "confirmed".into(),
+        }
+    }
+
+    fn filled(offer_id: &str) -> String {
+        format!(
+            r#"Program log: {{"event":"OfferFilled","offer_id":"{offer_id}","maker":"{MAKER}","taker":"{TAKER}","mint_a":"{MINT}","amount_a":1,"mint_b":"{MINT}","amount_b":2}}"#
+        )
+    }
+
+    fn ix(program_id: &str) -> Ix {
+        Ix {
+            program_id: program_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn layout(outer: &[(&str, &[&str])]) -> IxLayout {
+        IxLayout {
+            outer: outer
+                .iter()
+                .map(|(program_id, inner)| OuterIx {
+                    ix: ix(program_id),
+                    inner: inner.iter().map(|p| ix(p)).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn run(logs: &[String], layout: &IxLayout) -> Vec<String> {
+        parse_tx(&ctx(), SIG, 1, logs, layout)
+            .events
+            .into_iter()
+            .map(|e| e.offer_id)
+            .collect()
+    }
+
+    fn ids(logs: &[String], layout: &IxLayout) -> Vec<String> {
+        parse_tx(&ctx(), SIG, 1, logs, layout)
+            .events
+            .into_iter()
+            .map(|e| e.event_id)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_event_inside_own_frame() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            "Program log: Instruction: TakeOffer".into(),
+            format!("Program {TOKEN} invoke [2]"),
+            "Program log: Instruction: Transfer".into(),
+            format!("Program {TOKEN} success"),
+            filled("1"),
+            format!("Program {ESCROW} consumed 5000 of 200000 compute units"),
+            format!("Program {ESCROW} success"),
+        ];
+        assert_eq!(run(&logs, &layout(&[(ESCROW, &[TOKEN])])), vec!["1"]);
+    }
+
+    #[test]
+    fn rejects_event_from_other_top_level_program() {
+        let logs = [
+            format!("Program {EVIL} invoke [1]"),
+            filled("spoofed"),
+            format!("Program {EVIL} success"),
+            format!("Program {ESCROW} invoke [1]"),
+            filled("real"),
+            format!("Program {ESCROW} success"),
+        ];
+        assert_eq!(
+            run(&logs, &layout(&[(EVIL, &[]), (ESCROW, &[])])),
+            vec!["real"]
+        );
+    }
+
+    #[test]
+    fn rejects_event_from_cpi_callee() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            format!("Program {EVIL} invoke [2]"),
+            filled("spoofed"),
+            format!("Program {EVIL} success"),
+            filled("real"),
+            format!("Program {ESCROW} success"),
+        ];
+        assert_eq!(run(&logs, &layout(&[(ESCROW, &[EVIL])])), vec!["real"]);
+    }
+
+    #[test]
+    fn rejects_event_from_program_that_cpis_into_escrow() {
+        let logs = [
+            format!("Program {EVIL} invoke [1]"),
+            format!("Program {ESCROW} invoke [2]"),
+            filled("real"),
+            format!("Program {ESCROW} success"),
+            filled("spoofed"),
+            format!("Program {EVIL} success"),
+        ];
+        assert_eq!(run(&logs, &layout(&[(EVIL, &[ESCROW])])), vec!["real"]);
+    }
+
+    #[test]
+    fn ignores_frames_printed_as_program_logs() {
+        let logs = [
+            format!("Program {EVIL} invoke [1]"),
+            format!("Program log: Program {ESCROW} invoke [2]"),
+            format!("Program log: {ESCROW} invoke [2]"),
+            "Program log: invoke [2]".into(),
+            filled("spoofed"),
+            format!("Program {EVIL} success"),
+        ];
+        assert!(run(&logs, &layout(&[(EVIL, &[])])).is_empty());
+    }
+
+    #[test]
+    fn rejects_events_without_any_frame() {
+        assert!(run(&[filled("orphan")], &layout(&[(ESCROW, &[])])).is_empty());
+    }
+
+    #[test]
+    fn stops_at_inconsistent_frames() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            filled("1"),
+            format!("Program {ESCROW} invoke [3]"),
+            filled("2"),
+        ];
+        assert_eq!(run(&logs, &layout(&[(ESCROW, &[])])), vec!["1"]);
+
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            format!("Program {EVIL} success"),
+            filled("2"),
+        ];
+        assert!(run(&logs, &layout(&[(ESCROW, &[])])).is_empty());
+    }
+
+    #[test]
+    fn failed_frame_pops() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            format!("Program {TOKEN} invoke [2]"),
+            format!("Program {TOKEN} failed: custom program error: 0x1"),
+            filled("1"),
+            format!("Program {ESCROW} success"),
+        ];
+        assert_eq!(run(&logs, &layout(&[(ESCROW, &[TOKEN])])), vec!["1"]);
+    }
+
+    #[test]
+    fn event_id_uses_top_level_instruction_index() {
+        const BUDGET: &str = "ComputeBudget111111111111111111111111111111";
+        const ED25519: &str = "Ed25519SigVerify111111111111111111111111111";
+        let logs = [
+            format!("Program {BUDGET} invoke [1]"),
+            format!("Program {BUDGET} success"),
+            format!("Program {ESCROW} invoke [1]"),
+            "Program log: Instruction: CreateOffer".into(),
+            filled("9"),
+            format!("Program {ESCROW} success"),
+        ];
+        // the precompile at index 1 executes without logging
+        let l = layout(&[(BUDGET, &[]), (ED25519, &[]), (ESCROW, &[])]);
+        assert_eq!(ids(&logs, &l), vec![format!("{SIG}:2:4")]);
+    }
+
+    #[test]
+    fn event_id_uses_inner_index_for_cpi() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            filled("a"),
+            format!("Program {ESCROW} success"),
+            format!("Program {EVIL} invoke [1]"),
+            format!("Program {TOKEN} invoke [2]"),
+            format!("Program {TOKEN} success"),
+            format!("Program {ESCROW} invoke [2]"),
+            format!("Program {TOKEN} invoke [3]"),
+            format!("Program {TOKEN} success"),
+            filled("b"),
+            format!("Program {ESCROW} success"),
+            format!("Program {EVIL} success"),
+        ];
+        let l = layout(&[(ESCROW, &[]), (EVIL, &[TOKEN, ESCROW, TOKEN])]);
+        assert_eq!(
+            ids(&logs, &l),
+            vec![format!("{SIG}:0:1"), format!("{SIG}:1.1:9")]
+        );
+    }
+
+    #[test]
+    fn stops_when_frames_disagree_with_layout() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            filled("a"),
+            format!("Program {TOKEN} invoke [2]"),
+            filled("b"),
+        ];
+        let l = layout(&[(ESCROW, &[EVIL])]);
+        assert_eq!(ids(&logs, &l), vec![format!("{SIG}:0:1")]);
+
+        let l = layout(&[(EVIL, &[])]);
+        assert!(ids(&logs, &l).is_empty());
+    }
+
+    #[test]
+    fn inconsistent_frames_are_reported() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            filled("1"),
+            format!("Program {ESCROW} invoke [3]"),
+            filled("2"),
+        ];
+        let parsed = parse_tx(&ctx(), SIG, 1, &logs, &layout(&[(ESCROW, &[])]));
+        assert_eq!(parsed.events.len(), 1);
+        let [failure] = &parsed.failures[..] else {
+            panic!("{:?}", parsed.failures)
+        };
+        assert_eq!(failure.reason, QuarantineReason::InconsistentFrames);
+        assert_eq!(failure.instruction.as_deref(), Some("0"));
+        assert_eq!(failure.log_index, Some(2));
+    }
+
+    #[test]
+    fn undecodable_payload_is_reported() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            r#"Program log: {"event":"OfferFilled","offer_id":"1"}"#.into(),
+            format!(
+                r#"Program log: {{"event":"WalletDenied","wallet":"{TAKER}","admin":"{MAKER}"}}"#
+            ),
+            filled("2").replace(MAKER, "maker"),
+            format!("Program {ESCROW} success"),
+            // foreign programs may print whatever they like
+            format!("Program {EVIL} invoke [1]"),
+            r#"Program log: {"event":"garbage"}"#.into(),
+            format!("Program {EVIL} success"),
+        ];
+        let parsed = parse_tx(
+            &ctx(),
+            SIG,
+            1,
+            &logs,
+            &layout(&[(ESCROW, &[]), (EVIL, &[])]),
+        );
+        assert!(parsed.events.is_empty());
+        let [failure, bad_key] = &parsed.failures[..] else {
+            panic!("{:?}", parsed.failures)
+        };
+        assert_eq!(failure.reason, QuarantineReason::UndecodablePayload);
+        assert_eq!(failure.log_index, Some(1));
+        assert_eq!(bad_key.reason, QuarantineReason::UndecodablePayload);
+        assert_eq!(bad_key.log_index, Some(3));
+        assert_eq!(parsed.admin.len(), 1);
+        assert!(parsed.unlogged.is_empty());
+
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            filled("1"),
+            format!("Program {ESCROW} success"),
+        ];
+        let parsed = parse_tx(&ctx(), "sig", 1, &logs, &layout(&[(ESCROW, &[])]));
+        assert!(parsed.events.is_empty());
+        assert_eq!(
+            parsed.failures[0].reason,
+            QuarantineReason::UndecodablePayload
+        );
+    }
+
+    #[test]
+    fn denylist_changes_are_admin_events() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            format!(
+                r#"Program log: {{"event":"WalletDenied","wallet":"{TAKER}","admin":"{MAKER}"}}"#
+            ),
+            format!("Program {ESCROW} success"),
+            format!("Program {ESCROW} invoke [1]"),
+            format!(
+                r#"Program log: {{"event":"WalletAllowed","wallet":"{TAKER}","admin":"{MAKER}"}}"#
+            ),
+            format!("Program {ESCROW} success"),
+            format!("Program {ESCROW} invoke [1]"),
+            r#"Program log: {"event":"WalletAllowed","wallet":"W"}"#.into(),
+            format!("Program {ESCROW} success"),
+        ];
+        let parsed = parse_tx(
+            &ctx(),
+            SIG,
+            1,
+            &logs,
+            &layout(&[(ESCROW, &[]), (ESCROW, &[]), (ESCROW, &[])]),
+        );
+        assert!(parsed.events.is_empty());
+        let [denied, allowed] = &parsed.admin[..] else {
+            panic!("{:?}", parsed.admin)
+        };
+        assert_eq!(denied.event_id, format!("{SIG}:0:1"));
+        assert_eq!(denied.event_type, AdminEventType::WalletDenied);
+        assert_eq!(denied.wallet.as_str(), TAKER);
+        assert_eq!(denied.admin.as_str(), MAKER);
+        assert_eq!(allowed.event_id, format!("{SIG}:1:4"));
+        assert_eq!(allowed.event_type, AdminEventType::WalletAllowed);
+        // a malformed one is quarantined like any other event
+        let [failure] = &parsed.failures[..] else {
+            panic!("{:?}", parsed.failures)
+        };
+        assert_eq!(failure.reason, QuarantineReason::UndecodablePayload);
+        assert_eq!(failure.log_index, Some(7));
+    }
+
+    #[test]
+    fn truncated_logs_leave_unlogged_instructions() {
+        let key = |b: u8| Pubkey::new_from_array([b; 32]);
+        let (maker, taker, mint_a, mint_b) = (key(1), key(2), key(3), key(4));
+        let as_ix = |i: solana_sdk::instruction::Instruction| Ix {
+            program_id: ESCROW.into(),
+            accounts: i.accounts.iter().map(|m| m.pubkey.to_string()).collect(),
+            data: i.data,
+        };
+        let take = |offer_id| {
+            as_ix(escrow_client::ix::take_offer(
+                &taker, &maker, &mint_a, &mint_b, offer_id,
+            ))
+        };
+        let l = IxLayout {
+            outer: vec![
+                OuterIx {
+                    ix: take(1),
+                    inner: Vec::new(),
+                },
+                OuterIx {
+                    ix: take(2),
+                    inner: Vec::new(),
+                },
+            ],
+        };
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            filled("1"),
+            format!("Program {ESCROW} success"),
+            format!("Program {ESCROW} invoke [1]"),
+            "Log truncated".into(),
+        ];
+
+        let parsed = parse_tx(&ctx(), SIG, 1, &logs, &l);
+        assert_eq!(parsed.events.len(), 1);
+        assert!(parsed.failures.is_empty());
+        let [(position, ix)] = &parsed.unlogged[..] else {
+            panic!("{:?}", parsed.unlogged)
+        };
+        assert_eq!(position, "1");
+
+        let accounts: Vec<Pubkey> = ix.accounts.iter().map(|a| a.parse().unwrap()).collect();
+        assert_eq!(
+            escrow_client::decode_offer_ix(&ix.data, &accounts),
+            Some(escrow_client::OfferIx::Take {
+                escrow_state: escrow_client::pda::escrow_state(&maker, 2).0,
+                taker,
+            })
+        );
+
+        // complete logs: nothing to recover
+        let parsed = parse_tx(&ctx(), SIG, 1, &logs[..3], &l);
+        assert!(parsed.unlogged.is_empty());
+    }
+
+    #[test]
+    fn rfq_cancellations_are_rfq_events() {
+        let logs = [
+            format!("Program {ESCROW} invoke [1]"),
+            format!(
+                r#"Program log: {{"event":"RfqCancelled","rfq_id":"7","requester":"{MAKER}","mint_a":"{MINT}","amount_a":5,"mint_b":"{MINT}" }}"#
+            ),
+            format!("Program {ESCROW} success"),
+        ];
+        let parsed = parse_tx(&ctx(), SIG, 1, &logs, &layout(&[(ESCROW, &[])]));
+        let [ev] = &parsed.events[..] else {
+            panic!("{:?}", parsed.failures)
+        };
+        assert_eq!(ev.event_type, EventType::RfqCancelled);
+        assert_eq!(
+            (ev.rfq_id.as_deref(), ev.offer_id.as_str()),
+            (Some("7"), "7")
+        );
+        assert_eq!((ev.maker.as_str(), ev.taker.as_ref()), (MAKER, None));
+        assert_eq!((ev.amount_a.get(), ev.amount_b.get()), (5, 0));
+    }
+}
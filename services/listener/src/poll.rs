@@ -0,0 +1,329 @@
+use anyhow::{Context, Result};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::backfill::{Backfiller, Range};
+use crate::provider::{supervise, Feed, ProviderStats};
+
+/// Newest signature already handed to the merge loop, and its slot.
+type Cursor = Option<(String, u64)>;
+
+/// One HTTP RPC endpoint for nodes without PubSub: polls getSignaturesForAddress every
+/// `interval` and fetches each new transaction, so events come out exactly as on the WS
+/// path. The cursor survives reconnects; the first session starts at the current tip and
+/// leaves older history to the merge loop's catch-up.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_poll(
+    idx: usize,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    roots: bool,
+    interval: Duration,
+    cooldown: Duration,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+) {
+    let rpc = Arc::new(Backfiller::new(&stats.url, program_id, commitment));
+    let cursor = Arc::new(Mutex::new(None));
+    let (session_stats, session_tx) = (stats.clone(), tx.clone());
+    supervise(idx, cooldown, stats, tx, move || {
+        let (rpc, cursor) = (rpc.clone(), cursor.clone());
+        let (stats, tx) = (session_stats.clone(), session_tx.clone());
+        async move { session(idx, &rpc, &cursor, roots, interval, &stats, &tx).await }
+    })
+    .await
+}
+
+async fn session(
+    idx: usize,
+    rpc: &Backfiller,
+    cursor: &Mutex<Cursor>,
+    roots: bool,
+    interval: Duration,
+    stats: &ProviderStats,
+    tx: &mpsc::Sender<Feed>,
+) -> Result<()> {
+    if cursor.lock().unwrap().is_none() {
+        *cursor.lock().unwrap() = rpc.latest().await?;
+    }
+    stats.mark_up(idx, tx).await?;
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if stats.demoted.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        poll_once(idx, rpc, cursor, tx).await?;
+        if roots {
+            let root = rpc.finalized_slot().await?;
+            tx.send(Feed::Root(root)).await.context("merge loop gone")?;
+        }
+    }
+}
+
+/// Forwards every successful transaction after the cursor, oldest first, then moves the
+/// cursor to the newest one listed (failed ones included, so they are not listed again).
+async fn poll_once(
+    idx: usize,
+    rpc: &Backfiller,
+    cursor: &Mutex<Cursor>,
+    tx: &mpsc::Sender<Feed>,
+) -> Result<()> {
+    let range = match cursor.lock().unwrap().clone() {
+        Some((signature, slot)) => Range::after(signature, slot),
+        None => Range::default(),
+    };
+    let history = rpc.history(&range).await?;
+    let Some(newest) = history.first().map(|s| (s.signature.clone(), s.slot)) else {
+        return Ok(());
+    };
+
+    for s in history.into_iter().rev().filter(|s| s.err.is_none()) {
+        let Some(fetched) = rpc.fetch_tx(&s.signature).await? else {
+            continue;
+        };
+        let feed = Feed::Tx {
+            provider: idx,
+            signature: s.signature,
+            at: Instant::now(),
+            fetched: Some(fetched),
+        };
+        tx.send(feed).await.context("merge loop gone")?;
+    }
+    *cursor.lock().unwrap() = Some(newest);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_tx, IngestCtx};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const PROGRAM: &str = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk";
+    const PAYER: &str = "EeFX3g6HePRn5ipNu5ey3jiYWRVmeK3Frxec2ZwqKs6W";
+
+    fn sig(b: u8) -> String {
+        solana_sdk::signature::Signature::from([b; 64]).to_string()
+    }
+
+    fn created(offer_id: &str) -> String {
+        format!(
+            r#"Program log: {{"event":"OfferCreated","offer_id":"{offer_id}","maker":"{PAYER}","mint_a":"{PROGRAM}","amount_a":1,"mint_b":"{PROGRAM}","amount_b":2}}"#
+        )
+    }
+
+    /// getTransaction result for a one-instruction call into the program.
+    fn transaction(signature: &str, slot: u64, offer_id: &str) -> Value {
+        json!({
+            "slot": slot,
+            "blockTime": 1_730_000_000 + slot as i64,
+            "meta": {
+                "err": null,
+                "status": {"Ok": null},
+                "fee": 5000,
+                "preBalances": [], "postBalances": [],
+                "innerInstructions": [],
+                "logMessages": [
+                    format!("Program {PROGRAM} invoke [1]"),
+                    "Program log: Instruction: CreateOffer",
+                    created(offer_id),
+                    format!("Program {PROGRAM} success"),
+                ],
+                "loadedAddresses": {"writable": [], "readonly": []},
+                "computeUnitsConsumed": 41023
+            },
+            "transaction": {
+                "signatures": [signature],
+                "message": {
+                    "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1},
+                    "accountKeys": [PAYER, PROGRAM],
+                    "recentBlockhash": "11111111111111111111111111111111",
+                    "instructions": [{"programIdIndex": 1, "accounts": [0], "data": "", "stackHeight": null}]
+                }
+            }
+        })
+    }
+
+    /// Minimal JSON-RPC node: serves `history` (newest first) to getSignaturesForAddress,
+    /// honouring `until`, and `txs` to getTransaction. The client asks getVersion first.
+    async fn mock_rpc(history: Arc<Mutex<Vec<Value>>>, txs: HashMap<String, Value>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                let (history, txs) = (history.clone(), txs.clone());
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let body = loop {
+                        let mut chunk = [0u8; 4096];
+                        let n = sock.read(&mut chunk).await.unwrap();
+                        buf.extend_from_slice(&chunk[..n]);
+                        let text = String::from_utf8_lossy(&buf);
+                        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                            continue;
+                        };
+                        let len = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(str::to_string)
+                            })
+                            .and_then(|l| l.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if body.len() >= len || n == 0 {
+                            break body.to_string();
+                        }
+                    };
+                    let req: Value = serde_json::from_str(&body).unwrap();
+                    let result = match req["method"].as_str().unwrap() {
+                        "getSignaturesForAddress" => {
+                            let until = req["params"][1]["until"].as_str().map(str::to_string);
+                            let page: Vec<Value> = history
+                                .lock()
+                                .unwrap()
+                                .iter()
+                                .take_while(|s| {
+                                    Some(s["signature"].as_str().unwrap().to_string()) != until
+                                })
+                                .cloned()
+                                .collect();
+                            json!(page)
+                        }
+                        "getTransaction" => txs[req["params"][0].as_str().unwrap()].clone(),
+                        "getSlot" => json!(900),
+                        "getVersion" => json!({"solana-core": "1.18.26", "feature-set": 0}),
+                        other => panic!("unexpected {other}"),
+                    };
+                    let body =
+                        json!({"jsonrpc": "2.0", "id": req["id"], "result": result}).to_string();
+                    let resp = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = sock.write_all(resp.as_bytes()).await;
+                });
+            }
+        });
+        url
+    }
+
+    fn listed(signature: &str, slot: u64, failed: bool) -> Value {
+        let err = if failed {
+            json!({"InstructionError": [0, {"Custom": 6001}]})
+        } else {
+            Value::Null
+        };
+        json!({"signature": signature, "slot": slot, "err": err, "memo": null, "blockTime": null, "confirmationStatus": "finalized"})
+    }
+
+    #[tokio::test]
+    async fn polls_new_transactions_once_in_order() {
+        let (old, a, failed, b, c) = (sig(1), sig(2), sig(3), sig(4), sig(5));
+        let history = Arc::new(Mutex::new(vec![listed(&old, 100, false)]));
+        let txs = HashMap::from([
+            (a.clone(), transaction(&a, 101, "a")),
+            (b.clone(), transaction(&b, 102, "b")),
+            (c.clone(), transaction(&c, 103, "c")),
+        ]);
+        let url = mock_rpc(history.clone(), txs).await;
+
+        let stats = Arc::new(ProviderStats::new(&url));
+        let (tx, mut rx) = mpsc::channel(16);
+        let program_id: Pubkey = PROGRAM.parse().unwrap();
+        tokio::spawn(run_poll(
+            0,
+            program_id,
+            CommitmentConfig::finalized(),
+            true,
+            Duration::from_millis(20),
+            Duration::from_secs(60),
+            stats,
+            tx,
+        ));
+
+        // the tip at startup is the baseline: `old` is never forwarded
+        assert!(matches!(recv(&mut rx).await, Feed::Up(0)));
+        history.lock().unwrap().splice(
+            0..0,
+            [
+                listed(&b, 102, false),
+                listed(&failed, 101, true),
+                listed(&a, 101, false),
+            ],
+        );
+        assert_eq!(
+            next_tx(&mut rx).await,
+            (a.clone(), Some(1_730_000_101), vec![format!("{a}:0:2")])
+        );
+        assert_eq!(
+            next_tx(&mut rx).await,
+            (b.clone(), Some(1_730_000_102), vec![format!("{b}:0:2")])
+        );
+
+        history.lock().unwrap().insert(0, listed(&c, 103, false));
+        assert_eq!(next_tx(&mut rx).await.0, c);
+    }
+
+    #[tokio::test]
+    async fn history_stops_near_a_pruned_cursor() {
+        // the node no longer lists the cursor signature, so `until` never matches
+        let history: Vec<_> = (1..=10u8)
+            .rev()
+            .map(|i| listed(&sig(i), u64::from(i) * 50, false))
+            .collect();
+        let url = mock_rpc(Arc::new(Mutex::new(history)), HashMap::new()).await;
+        let rpc = Backfiller::new(
+            &url,
+            PROGRAM.parse().unwrap(),
+            CommitmentConfig::finalized(),
+        );
+
+        let listed = rpc.history(&Range::after(sig(99), 400)).await.unwrap();
+        let slots: Vec<_> = listed.iter().map(|s| s.slot).collect();
+        assert_eq!(slots, [500, 450, 400, 350, 300, 250]);
+    }
+
+    async fn recv(rx: &mut mpsc::Receiver<Feed>) -> Feed {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Next forwarded transaction: signature, block time and the event ids it parses to.
+    async fn next_tx(rx: &mut mpsc::Receiver<Feed>) -> (String, Option<i64>, Vec<String>) {
+        let ctx = IngestCtx {
+            cluster: "localnet".into(),
+            program_id: PROGRAM.parse().unwrap(),
+            commitment: "finalized".into(),
+        };
+        loop {
+            match recv(rx).await {
+                Feed::Tx {
+                    signature,
+                    fetched: Some(tx),
+                    ..
+                } => {
+                    let parsed = parse_tx(&ctx, &signature, tx.slot, &tx.logs, &tx.layout);
+                    let ids = parsed.events.into_iter().map(|e| e.event_id).collect();
+                    return (signature, tx.info.block_time, ids);
+                }
+                Feed::Tx { .. } => panic!("poll source must deliver the fetched transaction"),
+                Feed::Root(_) => continue,
+                Feed::Up(_) | Feed::Down(_) => panic!("session restarted"),
+            }
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::backfill::FetchedTx;
+use crate::backoff::Backoff;
+use crate::replay::Recorder;
+
+/// A session that stayed up this long resets the reconnect backoff.
+const STABLE_SESSION: Duration = Duration::from_secs(60);
+
+/// Below this many observations in the current window the miss ratio is not judged.
+const MIN_HEALTH_SAMPLES: u64 = 20;
+
+/// What every source feeds into the merge loop.
+#[derive(Debug)]
+pub enum Feed {
+    Up(usize),
+    Down(usize),
+    Tx {
+        provider: usize,
+        signature: String,
+        at: Instant,
+        /// gRPC delivers the whole transaction; WS only the signature, so it is fetched later
+        fetched: Option<FetchedTx>,
+    },
+    Root(u64),
+}
+
+/// Per-endpoint delivery statistics, exported as labelled metrics.
+#[derive(Debug)]
+pub struct ProviderStats {
+    pub url: String,
+    pub connected: AtomicBool,
+    pub reconnects_total: AtomicU64,
+    pub notifications_total: AtomicU64,
+    /// notifications this provider delivered before any other
+    pub first_total: AtomicU64,
+    /// transactions other providers delivered and this one never did
+    pub missed_total: AtomicU64,
+    /// moving average of how far behind the first delivery this provider is
+    pub lag_ms: AtomicU64,
+    pub demoted: AtomicBool,
+    pub demotions_total: AtomicU64,
+    window_seen: AtomicU64,
+    window_missed: AtomicU64,
+    sessions: AtomicU64,
+}
+
+impl ProviderStats {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            connected: AtomicBool::new(false),
+            reconnects_total: AtomicU64::new(0),
+            notifications_total: AtomicU64::new(0),
+            first_total: AtomicU64::new(0),
+            missed_total: AtomicU64::new(0),
+            lag_ms: AtomicU64::new(0),
+            demoted: AtomicBool::new(false),
+            demotions_total: AtomicU64::new(0),
+            window_seen: AtomicU64::new(0),
+            window_missed: AtomicU64::new(0),
+            sessions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.connected.load(Ordering::Relaxed) && !self.demoted.load(Ordering::Relaxed)
+    }
+
+    /// Called by a session once it is subscribed; every session after the first is a reconnect.
+    pub async fn mark_up(&self, idx: usize, tx: &mpsc::Sender<Feed>) -> Result<()> {
+        if self.sessions.fetch_add(1, Ordering::Relaxed) > 0 {
+            self.reconnects_total.fetch_add(1, Ordering::Relaxed);
+        }
+        self.connected.store(true, Ordering::Relaxed);
+        tx.send(Feed::Up(idx)).await.context("merge loop gone")
+    }
+
+    pub fn record_seen(&self, first: bool, lag: Duration) {
+        self.notifications_total.fetch_add(1, Ordering::Relaxed);
+        self.window_seen.fetch_add(1, Ordering::Relaxed);
+        if first {
+            self.first_total.fetch_add(1, Ordering::Relaxed);
+        }
+        // EWMA with alpha = 1/8
+        let sample = lag.as_millis() as u64;
+        let old = self.lag_ms.load(Ordering::Relaxed);
+        self.lag_ms.store((old * 7 + sample) / 8, Ordering::Relaxed);
+    }
+
+    pub fn record_missed(&self) {
+        self.missed_total.fetch_add(1, Ordering::Relaxed);
+        self.window_missed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Clears the judged state after a cool-down so the provider starts on probation.
+    fn promote(&self) {
+        self.lag_ms.store(0, Ordering::Relaxed);
+        self.window_seen.store(0, Ordering::Relaxed);
+        self.window_missed.store(0, Ordering::Relaxed);
+        self.demoted.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Thresholds past which a provider is demoted: unsubscribed for `cooldown`, then retried.
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    pub max_lag_ms: u64,
+    pub max_miss_ratio: f64,
+    pub cooldown: Duration,
+}
+
+impl Health {
+    /// Demotes lagging or lossy providers, never the last active one.
+    pub fn evaluate(&self, providers: &[Arc<ProviderStats>]) {
+        let mut active = providers.iter().filter(|p| p.active()).count();
+        for p in providers.iter().filter(|p| p.active()) {
+            let seen = p.window_seen.load(Ordering::Relaxed);
+            let missed = p.window_missed.load(Ordering::Relaxed);
+            let miss_ratio = if seen + missed >= MIN_HEALTH_SAMPLES {
+                p.window_seen.store(0, Ordering::Relaxed);
+                p.window_missed.store(0, Ordering::Relaxed);
+                missed as f64 / (seen + missed) as f64
+            } else {
+                0.0
+            };
+            let lag = p.lag_ms.load(Ordering::Relaxed);
+
+            if (lag > self.max_lag_ms || miss_ratio > self.max_miss_ratio) && active > 1 {
+                p.demoted.store(true, Ordering::Relaxed);
+                p.demotions_total.fetch_add(1, Ordering::Relaxed);
+                active -= 1;
+                eprintln!(
+                    "provider demoted: {} lag_ms={lag} miss_ratio={miss_ratio:.2} cooldown={:?}",
+                    p.url, self.cooldown
+                );
+            }
+        }
+    }
+}
+
+/// One WS endpoint: subscribes to the program's logs (and roots) and forwards them.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_ws(
+    idx: usize,
+    program_id: String,
+    commitment: CommitmentConfig,
+    roots: bool,
+    recorder: Option<Arc<Recorder>>,
+    cooldown: Duration,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+) {
+    let (session_stats, session_tx) = (stats.clone(), tx.clone());
+    supervise(idx, cooldown, stats, tx, move || {
+        let recorder = recorder.clone();
+        session(
+            idx,
+            program_id.clone(),
+            commitment,
+            roots,
+            recorder,
+            session_stats.clone(),
+            session_tx.clone(),
+        )
+    })
+    .await
+}
+
+/// Runs `session` over and over: reconnects with jittered backoff and sits out `cooldown`
+/// when demoted. Runs until the merge loop goes away.
+pub async fn supervise<F, Fut>(
+    idx: usize,
+    cooldown: Duration,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+    mut session: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(30));
+    while !tx.is_closed() {
+        let started = Instant::now();
+        match session().await {
+            Ok(()) => eprintln!("[{}] stream closed", stats.url),
+            Err(e) => eprintln!("[{}] session failed: {e:#}", stats.url),
+        }
+        if stats.connected.swap(false, Ordering::Relaxed) {
+            let _ = tx.send(Feed::Down(idx)).await;
+        }
+
+        if stats.demoted.load(Ordering::Relaxed) {
+            tokio::time::sleep(cooldown).await;
+            stats.promote();
+            backoff.reset();
+            eprintln!("[{}] cool-down over, resubscribing", stats.url);
+            continue;
+        }
+        if started.elapsed() >= STABLE_SESSION {
+            backoff.reset();
+        }
+        let delay = backoff.next_delay();
+        eprintln!("[{}] reconnecting in {delay:?}", stats.url);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn session(
+    idx: usize,
+    program_id: String,
+    commitment: CommitmentConfig,
+    with_roots: bool,
+    recorder: Option<Arc<Recorder>>,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+) -> Result<()> {
+    // Solana 1.18.x: logs_subscribe is an instance method on PubsubClient.
+    let client = PubsubClient::new(&stats.url)
+        .await
+        .context("pubsub connect")?;
+    let (mut logs, _unsub) = client
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![program_id]),
+            RpcTransactionLogsConfig {
+                commitment: Some(commitment),
+            },
+        )
+        .await
+        .context("logs_subscribe")?;
+    let mut roots = match with_roots {
+        true => Some(client.root_subscribe().await.context("root_subscribe")?.0),
+        false => None,
+    };
+
+    stats.mark_up(idx, &tx).await?;
+
+    let mut demotion_check = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        let next_root = async {
+            match roots.as_mut() {
+                Some(roots) => roots.next().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            maybe_msg = logs.next() => {
+                let Some(resp) = maybe_msg else { return Ok(()); };
+                if let Some(recorder) = &recorder {
+                    recorder.record(&resp)?;
+                }
+                if resp.value.err.is_some() {
+                    continue;
+                }
+                let feed = Feed::Tx {
+                    provider: idx,
+                    signature: resp.value.signature,
+                    at: Instant::now(),
+                    fetched: None,
+                };
+                tx.send(feed).await.context("merge loop gone")?;
+            }
+            maybe_root = next_root => {
+                let Some(root) = maybe_root else { return Ok(()); };
+                tx.send(Feed::Root(root)).await.context("merge loop gone")?;
+            }
+            _ = demotion_check.tick() => {
+                if stats.demoted.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn up(url: &str) -> Arc<ProviderStats> {
+        let p = Arc::new(ProviderStats::new(url));
+        p.connected.store(true, Ordering::Relaxed);
+        p
+    }
+
+    const HEALTH: Health = Health {
+        max_lag_ms: 2000,
+        max_miss_ratio: 0.2,
+        cooldown: Duration::from_secs(60),
+    };
+
+    #[test]
+    fn demotes_once_the_lag_average_crosses_the_threshold() {
+        let (fast, slow) = (up("ws://fast"), up("ws://slow"));
+        let providers = [fast.clone(), slow.clone()];
+
+        // the average moves an eighth of the way per sample: a single late delivery is not enough
+        slow.record_seen(false, Duration::from_millis(5000));
+        HEALTH.evaluate(&providers);
+        assert!(slow.active());
+        assert_eq!(slow.lag_ms.load(Ordering::Relaxed), 625);
+
+        for _ in 0..10 {
+            fast.record_seen(true, Duration::ZERO);
+            slow.record_seen(false, Duration::from_millis(5000));
+        }
+        assert!(slow.lag_ms.load(Ordering::Relaxed) > HEALTH.max_lag_ms);
+        HEALTH.evaluate(&providers);
+        assert!(!slow.active());
+        assert!(fast.active());
+        assert_eq!(slow.demotions_total.load(Ordering::Relaxed), 1);
+
+        // after the cool-down it starts over on probation
+        slow.promote();
+        assert!(slow.active());
+        assert_eq!(slow.lag_ms.load(Ordering::Relaxed), 0);
+        slow.record_seen(false, Duration::from_millis(100));
+        HEALTH.evaluate(&providers);
+        assert!(slow.active());
+    }
+
+    #[test]
+    fn demotes_lossy_providers_but_never_the_last_active_one() {
+        let (a, b) = (up("ws://a"), up("ws://b"));
+        let providers = [a.clone(), b.clone()];
+        for _ in 0..MIN_HEALTH_SAMPLES {
+            a.record_missed();
+            b.record_missed();
+        }
+        HEALTH.evaluate(&providers);
+        assert_eq!(providers.iter().filter(|p| p.active()).count(), 1);
+        assert!(!a.active(), "the first lossy provider goes");
+        assert!(b.active(), "the last one stays");
+    }
+}
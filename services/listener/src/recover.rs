@@ -0,0 +1,121 @@
+use anyhow::Result;
+use escrow_client::{decode_offer_ix, EscrowState, OfferIx};
+use orderflow_common::{now_ms, EventType, NormalizedEvent, QuarantineReason, Signature58};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::hex32;
+use crate::backfill::Backfiller;
+use crate::parse::{IngestCtx, Ix, ParseFailure};
+
+/// Rebuilds the offer events that truncated logs lost, from each instruction's data and
+/// accounts plus the offer's current `EscrowState` (maker, mints, amounts and `client_ref`
+/// never change after creation). `vault_amount` is not recoverable and stays `None`.
+///
+/// The `event_id` is `signature:<instruction>:truncated`: the log index is gone, but the
+/// position is still fixed by the transaction, so every source agrees. RFQ and denylist
+/// instructions and offers whose account is gone are reported as failures.
+pub async fn recover(
+    ctx: &IngestCtx,
+    backfiller: &Backfiller,
+    signature: &str,
+    slot: u64,
+    unlogged: &[(String, Ix)],
+) -> Result<(Vec<NormalizedEvent>, Vec<ParseFailure>)> {
+    let signature: Signature58 = signature.parse()?;
+    let decoded: Vec<_> = unlogged
+        .iter()
+        .map(|(position, ix)| {
+            let accounts: Option<Vec<Pubkey>> =
+                ix.accounts.iter().map(|a| a.parse().ok()).collect();
+            (
+                position,
+                accounts.and_then(|accounts| decode_offer_ix(&ix.data, &accounts)),
+            )
+        })
+        .collect();
+    let addresses: Vec<Pubkey> = decoded
+        .iter()
+        .filter_map(|(_, ix)| ix.as_ref().map(escrow_state_of))
+        .collect();
+    let mut states = match addresses.is_empty() {
+        true => Vec::new(),
+        false => backfiller.escrow_states(&addresses).await?,
+    }
+    .into_iter();
+
+    let mut events = Vec::new();
+    let mut failures = Vec::new();
+    for (position, ix) in decoded {
+        let failure = |detail: &str| ParseFailure {
+            reason: QuarantineReason::LogTruncated,
+            instruction: Some(position.clone()),
+            log_index: None,
+            detail: detail.to_string(),
+        };
+        let Some(ix) = ix else {
+            failures.push(failure(
+                "instruction's event not in logs and not an offer instruction",
+            ));
+            continue;
+        };
+        let Some(state) = states.next().flatten() else {
+            failures.push(failure(
+                "instruction's event not in logs and escrow account not found",
+            ));
+            continue;
+        };
+        events.push(offer_event(ctx, &signature, slot, position, &ix, &state));
+    }
+    Ok((events, failures))
+}
+
+fn escrow_state_of(ix: &OfferIx) -> Pubkey {
+    match ix {
+        OfferIx::Create { escrow_state, .. }
+        | OfferIx::Take { escrow_state, .. }
+        | OfferIx::Cancel { escrow_state }
+        | OfferIx::ForceCancel { escrow_state } => *escrow_state,
+    }
+}
+
+fn offer_event(
+    ctx: &IngestCtx,
+    signature: &Signature58,
+    slot: u64,
+    position: &str,
+    ix: &OfferIx,
+    state: &EscrowState,
+) -> NormalizedEvent {
+    let (event_type, taker) = match ix {
+        OfferIx::Create { .. } => (EventType::OfferCreated, None),
+        OfferIx::Take { taker, .. } => (EventType::OfferFilled, Some(taker.to_bytes().into())),
+        OfferIx::Cancel { .. } => (EventType::OfferCancelled, None),
+        OfferIx::ForceCancel { .. } => (EventType::OfferForceCancelled, None),
+    };
+    NormalizedEvent {
+        event_id: format!("{signature}:{position}:truncated"),
+        event_type,
+        cluster: ctx.cluster.clone(),
+        slot,
+        signature: signature.clone(),
+        program_id: ctx.program_id.clone(),
+        offer_id: state.offer_id.to_string(),
+        rfq_id: None,
+        maker: state.maker.to_bytes().into(),
+        taker,
+        mint_a: state.mint_a.to_bytes().into(),
+        mint_b: state.mint_b.to_bytes().into(),
+        amount_a: state.amount_a.into(),
+        amount_b: state.amount_b.into(),
+        client_ref: Some(hex32(&state.client_ref)),
+        vault_amount: None,
+        block_time: None,
+        fee_lamports: None,
+        fee_payer: None,
+        compute_units_consumed: None,
+        priority_fee_lamports: None,
+        commitment: ctx.commitment.clone(),
+        ts_ingest_ms: now_ms(),
+        ref_event_id: None,
+    }
+}
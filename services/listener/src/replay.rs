@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_response::{Response, RpcLogsResponse};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::backfill::{FetchedTx, TxInfo};
+use crate::parse::layout_from_logs;
+use crate::provider::{Feed, ProviderStats};
+
+/// One line of a recording: a raw logsSubscribe notification and when it arrived.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recorded {
+    /// milliseconds since the recording started
+    pub at_ms: u64,
+    pub frame: Response<RpcLogsResponse>,
+}
+
+/// Appends every WS notification, failed transactions included, to a JSONL file.
+pub struct Recorder {
+    file: Mutex<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&self, frame: &Response<RpcLogsResponse>) -> Result<()> {
+        let line = Recorded {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            frame: frame.clone(),
+        };
+        let mut line = serde_json::to_string(&line).context("serialize frame")?;
+        line.push('\n');
+        // one write per line, so a crash leaves at most a torn last line
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .context("write recording")
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Recorded>> {
+    let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut out = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("read {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let rec =
+            serde_json::from_str(&line).with_context(|| format!("{}:{}", path.display(), i + 1))?;
+        out.push(rec);
+    }
+    Ok(out)
+}
+
+/// Feeds a recording back into the merge loop, in order, keeping the recorded gaps
+/// divided by `speed` (`0` = no gaps). Failed transactions are dropped like in the live
+/// session. Logs come without their transaction, so the layout is rebuilt from the frame
+/// lines and nothing is fetched. Returns once the recording is exhausted.
+pub async fn run_replay(
+    idx: usize,
+    speed: f64,
+    stats: Arc<ProviderStats>,
+    tx: mpsc::Sender<Feed>,
+) -> Result<()> {
+    let frames = load(Path::new(&stats.url))?;
+    stats.mark_up(idx, &tx).await?;
+
+    let mut prev_ms = frames.first().map_or(0, |r| r.at_ms);
+    for Recorded { at_ms, frame } in frames {
+        if speed > 0.0 && at_ms > prev_ms {
+            tokio::time::sleep(Duration::from_millis(at_ms - prev_ms).div_f64(speed)).await;
+        }
+        prev_ms = at_ms;
+
+        if frame.value.err.is_some() {
+            continue;
+        }
+        let layout = layout_from_logs(&frame.value.logs);
+        let feed = Feed::Tx {
+            provider: idx,
+            signature: frame.value.signature,
+            at: Instant::now(),
+            fetched: Some(FetchedTx {
+                slot: frame.context.slot,
+                logs: frame.value.logs,
+                layout,
+                info: TxInfo::default(),
+            }),
+        };
+        tx.send(feed).await.context("merge loop gone")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_tx, IngestCtx};
+    use orderflow_common::{envelope, schema};
+    use std::collections::HashSet;
+
+    const PROGRAM: &str = "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk";
+
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    /// Replays a recording and parses it the way the merge loop does (first delivery of a
+    /// signature wins), with the ingest timestamp zeroed.
+    async fn replay(path: &str) -> Vec<serde_json::Value> {
+        let (tx, mut rx) = mpsc::channel(16);
+        let stats = Arc::new(ProviderStats::new(path));
+        tokio::spawn(run_replay(0, 0.0, stats, tx));
+
+        let ctx = IngestCtx {
+            cluster: "localnet".into(),
+            program_id: PROGRAM.parse().unwrap(),
+            commitment: "confirmed".into(),
+        };
+        let mut seen = HashSet::new();
+        let mut events = Vec::new();
+        while let Some(feed) = rx.recv().await {
+            let Feed::Tx {
+                signature,
+                fetched: Some(tx),
+                ..
+            } = feed
+            else {
+                continue;
+            };
+            if !seen.insert(signature.clone()) {
+                continue;
+            }
+            let parsed = parse_tx(&ctx, &signature, tx.slot, &tx.logs, &tx.layout);
+            assert!(
+                parsed.failures.is_empty(),
+                "{signature}: {:?}",
+                parsed.failures
+            );
+            for mut ev in parsed.events {
+                ev.ts_ingest_ms = 0;
+                events.push(serde_json::to_value(ev).unwrap());
+            }
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn replays_scenarios_to_golden_events() {
+        let events = replay(&testdata("logs_scenarios.jsonl")).await;
+        let golden: Vec<serde_json::Value> = serde_json::from_str(
+            &std::fs::read_to_string(testdata("logs_scenarios.golden.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            golden,
+            "{}",
+            serde_json::to_string_pretty(&events).unwrap()
+        );
+
+        // what the JSON sink publishes for them is what the published schema describes
+        for ev in golden {
+            let ev = serde_json::from_value(ev).unwrap();
+            let payload = envelope::encode_event(&ev, envelope::SCHEMA_VERSION).unwrap();
+            let value = serde_json::from_str(&payload).unwrap();
+            schema::validate(schema::EVENTS, &value).unwrap_or_else(|e| panic!("{e:#}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn recording_round_trips() {
+        let frames = load(Path::new(&testdata("logs_scenarios.jsonl"))).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("listener-record-{}.jsonl", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        for rec in &frames {
+            recorder.record(&rec.frame).unwrap();
+        }
+        let reloaded = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let frames: Vec<_> = frames.into_iter().map(|r| r.frame).collect();
+        let reloaded: Vec<_> = reloaded.into_iter().map(|r| r.frame).collect();
+        assert_eq!(frames, reloaded);
+        // the failed take is kept in the recording, only the replay drops it
+        assert!(reloaded.iter().any(|f| f.value.err.is_some()));
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use futures::StreamExt;
-use orderflow_common::{AlertEvent, NormalizedEvent};
+use orderflow_common::envelope::Decoded;
+use orderflow_common::signing::TrustedKeys;
+use orderflow_common::wire;
 use rdkafka::config::ClientConfig;
 use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
 use rdkafka::message::Message;
@@ -19,11 +21,17 @@ struct Args {
 
     #[arg(long, env = "KAFKA_GROUP_ID", default_value = "notifier-v1")]
     kafka_group_id: String,
+
+    /// Only accept events and alerts signed by one of these keys (`key_id=<base58 pubkey>` or a
+    /// bare pubkey), comma-separated; empty accepts unsigned records
+    #[arg(long, env = "TRUSTED_SIGNING_KEYS", value_delimiter = ',')]
+    trusted_signing_keys: Vec<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    let trusted = TrustedKeys::parse(&args.trusted_signing_keys)?;
 
     let consumer: StreamConsumer = ClientConfig::new()
         .set("bootstrap.servers", &args.kafka_brokers)
@@ -40,8 +48,12 @@ async fn main() -> Result<()> {
         .context("subscribe topics")?;
 
     eprintln!(
-        "notifier started: brokers={} events_topic={} alerts_topic={} group={}",
-        args.kafka_brokers, args.events_topic, args.alerts_topic, args.kafka_group_id
+        "notifier started: brokers={} events_topic={} alerts_topic={} group={} trusted_keys={}",
+        args.kafka_brokers,
+        args.events_topic,
+        args.alerts_topic,
+        args.kafka_group_id,
+        trusted.len()
     );
 
     let shutdown = tokio::signal::ctrl_c();
@@ -65,24 +77,34 @@ async fn main() -> Result<()> {
                 };
 
                 let topic = msg.topic().to_string();
-                let Some(payload) = msg.payload_view::<str>().and_then(|p| p.ok()) else {
+                let Some(payload) = msg.payload() else {
                     let _ = consumer.commit_message(&msg, CommitMode::Async);
                     continue;
                 };
 
+                if let Err(e) = trusted.verify_message(&msg) {
+                    eprintln!("rejected record: {e:#} topic={} offset={}", msg.topic(), msg.offset());
+                    let _ = consumer.commit_message(&msg, CommitMode::Async);
+                    continue;
+                }
+
                 if topic == args.events_topic {
-                    if let Ok(ev) = serde_json::from_str::<NormalizedEvent>(payload) {
+                    if let Ok(Decoded::Event(ev)) = wire::decode_event(payload) {
                         eprintln!(
-                            "EVENT {:?}: offer_id={} maker={} taker={:?} a={} b={} slot={}",
-                            ev.event_type, ev.offer_id, ev.maker, ev.taker, ev.amount_a, ev.amount_b, ev.slot
+                            "EVENT {}: offer_id={} maker={} taker={:?} a={} b={} slot={}",
+                            ev.event_type.as_str(), ev.offer_id, ev.maker, ev.taker.as_deref(), ev.amount_a, ev.amount_b, ev.slot
                         );
                     }
                 } else if topic == args.alerts_topic {
-                    if let Ok(al) = serde_json::from_str::<AlertEvent>(payload) {
-                        eprintln!(
-                            "ALERT severity={} rule={} maker={} offer_id={:?} alert_id={}",
-                            al.severity, al.rule_id, al.maker, al.offer_id, al.alert_id
-                        );
+                    if let Ok(al) = wire::decode_alert(payload) {
+                        if let Some(withdrawn) = &al.withdraws {
+                            eprintln!("ALERT WITHDRAWN rule={} maker={} alert_id={withdrawn}", al.rule_id, al.maker);
+                        } else {
+                            eprintln!(
+                                "ALERT severity={} rule={} maker={} offer_id={:?} alert_id={}",
+                                al.severity, al.rule_id, al.maker, al.offer_id, al.alert_id
+                            );
+                        }
                     }
                 }
 
@@ -93,4 +115,3 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
-
@@ -1,13 +1,20 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use futures::StreamExt;
-use orderflow_common::{now_ms, parse_u64_str, AlertEvent, EventType, NormalizedEvent};
+use orderflow_common::envelope::{self, Decoded};
+use orderflow_common::signing::{EventSigner, TrustedKeys};
+use orderflow_common::sink::{KafkaSink, SigningSink, Sink, SpoolStats, SpoolingSink};
+use orderflow_common::wire::{self, Encoding, WireFormat};
+use orderflow_common::{now_ms, AlertEvent, EventType, NormalizedEvent};
 use rdkafka::config::ClientConfig;
 use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
 use rdkafka::message::Message;
-use rdkafka::producer::{FutureProducer, FutureRecord};
 use serde_json::json;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -34,11 +41,46 @@ struct Args {
     /// amount threshold (either amount_a or amount_b) => alert
     #[arg(long, env = "LARGE_AMOUNT_THRESHOLD", default_value_t = 1_000_000_000)]
     large_amount_threshold: u64,
+
+    /// Alerts from events below finalized are kept this long for a retraction; the listener
+    /// sends the follow-up once the root passes the event slot, normally well within a minute
+    #[arg(long, env = "FINALITY_WINDOW_SECS", default_value_t = 600)]
+    finality_window_secs: u64,
+
+    /// Alerts Kafka fails to accept are kept here and replayed in order
+    #[arg(long, env = "SPOOL_DIR", default_value = "risk-engine-spool")]
+    spool_dir: String,
+
+    /// `solana-keygen` JSON keypair; alerts are signed with it like the listener's events
+    #[arg(long, env = "SIGNING_KEY_FILE")]
+    signing_key: Option<PathBuf>,
+
+    /// key id sent with each signature; defaults to the base58 public key
+    #[arg(long, env = "SIGNING_KEY_ID", requires = "signing_key")]
+    signing_key_id: Option<String>,
+
+    /// Only accept records signed by one of these keys (`key_id=<base58 pubkey>` or a
+    /// bare pubkey), comma-separated; empty accepts unsigned records
+    #[arg(long, env = "TRUSTED_SIGNING_KEYS", value_delimiter = ',')]
+    trusted_signing_keys: Vec<String>,
+
+    /// Alert payload encoding: json, or protobuf (`AlertEvent` in `events.proto`)
+    #[arg(long, env = "ALERT_ENCODING", default_value = "json")]
+    encoding: Encoding,
+
+    /// protobuf: register the schema under `<alerts topic>-value` and frame alerts with its id
+    #[arg(long, env = "SCHEMA_REGISTRY_URL")]
+    schema_registry_url: Option<String>,
 }
 
+/// How often the alerts sink is flushed, a non-empty spool reported and unsettled alerts
+/// past the finality window dropped.
+const SPOOL_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    let trusted = TrustedKeys::parse(&args.trusted_signing_keys)?;
 
     let consumer: StreamConsumer = ClientConfig::new()
         .set("bootstrap.servers", &args.kafka_brokers)
@@ -54,24 +96,55 @@ async fn main() -> Result<()> {
         .subscribe(&[&args.events_topic])
         .context("subscribe events")?;
 
-    let producer: FutureProducer = ClientConfig::new()
-        .set("bootstrap.servers", &args.kafka_brokers)
-        .set("message.timeout.ms", "5000")
-        .create()
-        .context("create kafka producer")?;
+    let spool_stats = Arc::new(SpoolStats::default());
+    let producer: Box<dyn Sink> = Box::new(SpoolingSink::open(
+        Box::new(KafkaSink::new(&args.kafka_brokers, &args.alerts_topic)?),
+        &args.spool_dir,
+        spool_stats.clone(),
+    )?);
+    let producer = match &args.signing_key {
+        Some(path) => {
+            let signer = EventSigner::from_keypair_file(path, args.signing_key_id.as_deref())?;
+            Box::new(SigningSink::new(producer, signer, &args.alerts_topic))
+        }
+        None => producer,
+    };
+
+    let wire = match args.encoding {
+        Encoding::Json => WireFormat::json(envelope::SCHEMA_VERSION),
+        Encoding::Protobuf => {
+            WireFormat::protobuf(
+                args.schema_registry_url
+                    .as_deref()
+                    .map(|url| (url, args.alerts_topic.as_str())),
+            )
+            .await?
+        }
+    };
 
     eprintln!(
-        "risk-engine started: brokers={} events_topic={} alerts_topic={} group={}",
-        args.kafka_brokers, args.events_topic, args.alerts_topic, args.kafka_group_id
+        "risk-engine started: brokers={} events_topic={} alerts_topic={} group={} trusted_keys={}",
+        args.kafka_brokers,
+        args.events_topic,
+        args.alerts_topic,
+        args.kafka_group_id,
+        trusted.len()
     );
 
-    let mut cancels: HashMap<String, VecDeque<u64>> = HashMap::new();
-    let mut emitted_alerts: HashSet<String> = HashSet::new(); // demo: in-mem de-dupe
+    // (ts_ms, event_id) per maker, so a retracted cancel can be taken back out
+    let mut cancels: HashMap<String, VecDeque<(u64, String)>> = HashMap::new();
+    // demo: in-mem de-dupe
+    let mut emitted_alerts: HashSet<String> = HashSet::new();
+    // alerts raised by events ingested below finalized, with when they were raised, until
+    // their follow-up arrives or the finality window passes
+    let mut unsettled: HashMap<String, (u64, Vec<AlertEvent>)> = HashMap::new();
     let shutdown = tokio::signal::ctrl_c();
     tokio::pin!(shutdown);
     let mut stream = consumer.stream();
 
     let window_ms = args.cancel_window_min * 60_000;
+    let finality_window_ms = args.finality_window_secs * 1000;
+    let mut spool_flush = tokio::time::interval(SPOOL_FLUSH_INTERVAL);
 
     loop {
         tokio::select! {
@@ -79,6 +152,23 @@ async fn main() -> Result<()> {
                 eprintln!("shutdown requested");
                 break;
             }
+            _ = spool_flush.tick() => {
+                // undelivered alerts stay spooled; a broker outage must not stop the engine
+                if let Err(e) = producer.flush().await {
+                    eprintln!("alerts flush failed: {e:?}");
+                }
+                let expired = evict_unsettled(&mut unsettled, now_ms(), finality_window_ms);
+                if expired > 0 {
+                    eprintln!("dropped {expired} unsettled alert sets: no follow-up within the finality window");
+                }
+                let spooled = spool_stats.spooled.load(Ordering::Relaxed);
+                if spooled > 0 {
+                    eprintln!(
+                        "alerts spool: {spooled} pending, {} delivery failures so far",
+                        spool_stats.delivery_failures_total.load(Ordering::Relaxed)
+                    );
+                }
+            }
             maybe = stream.next() => {
                 let Some(msg) = maybe else { break; };
                 let msg = match msg {
@@ -88,31 +178,72 @@ async fn main() -> Result<()> {
                         continue;
                     }
                 };
-                let Some(payload) = msg.payload_view::<str>().and_then(|p| p.ok()) else {
+                let Some(payload) = msg.payload() else {
                     let _ = consumer.commit_message(&msg, CommitMode::Async);
                     continue;
                 };
 
-                let ev: NormalizedEvent = match serde_json::from_str(payload) {
-                    Ok(v) => v,
+                if let Err(e) = trusted.verify_message(&msg) {
+                    eprintln!("rejected record: {e:#} topic={} offset={}", msg.topic(), msg.offset());
+                    let _ = consumer.commit_message(&msg, CommitMode::Async);
+                    continue;
+                }
+
+                let ev = match wire::decode_event(payload) {
+                    Ok(Decoded::Event(ev)) => ev,
+                    Ok(Decoded::Unsupported { schema_version, event_type }) => {
+                        eprintln!(
+                            "skipping v{schema_version} {} event: newer than this build, offset={}",
+                            event_type.as_str(),
+                            msg.offset()
+                        );
+                        let _ = consumer.commit_message(&msg, CommitMode::Async);
+                        continue;
+                    }
                     Err(e) => {
-                        eprintln!("bad event json: {e:?} payload={payload}");
+                        eprintln!("bad event: {e:?} offset={} payload={}", msg.offset(), String::from_utf8_lossy(payload));
                         let _ = consumer.commit_message(&msg, CommitMode::Async);
                         continue;
                     }
                 };
 
+                // a type added after this build: its rules do not know what it means
+                if ev.event_type.is_unknown() {
+                    let _ = consumer.commit_message(&msg, CommitMode::Async);
+                    continue;
+                }
+
+                if let Some(ref_id) = ev.ref_event_id.as_deref() {
+                    let raised = unsettled.remove(ref_id).map(|(_, raised)| raised).unwrap_or_default();
+                    if ev.event_type == EventType::EventRetracted {
+                        if let Some(q) = cancels.get_mut(ev.maker.as_str()) {
+                            q.retain(|(_, id)| id != ref_id);
+                        }
+                        for alert in raised {
+                            let withdrawal = withdraw_alert(&alert, ref_id);
+                            emitted_alerts.remove(&alert.alert_id);
+                            emit_alert(producer.as_ref(), &wire, &mut emitted_alerts, withdrawal).await?;
+                        }
+                    }
+                    let _ = consumer.commit_message(&msg, CommitMode::Async);
+                    continue;
+                }
+
+                let mut raised = Vec::new();
+
                 // rule 1: large amount
                 if let Some(alert) = large_amount_rule(&ev, args.large_amount_threshold) {
-                    emit_alert(&producer, &args.alerts_topic, &mut emitted_alerts, alert).await?;
+                    if emit_alert(producer.as_ref(), &wire, &mut emitted_alerts, alert.clone()).await? {
+                        raised.push(alert);
+                    }
                 }
 
                 // rule 2: frequent cancel
                 if ev.event_type == EventType::OfferCancelled {
                     let now = now_ms();
-                    let q = cancels.entry(ev.maker.clone()).or_default();
-                    q.push_back(now);
-                    while let Some(front) = q.front().copied() {
+                    let q = cancels.entry(ev.maker.to_string()).or_default();
+                    q.push_back((now, ev.event_id.clone()));
+                    while let Some(&(front, _)) = q.front() {
                         if now.saturating_sub(front) > window_ms {
                             q.pop_front();
                         } else {
@@ -121,13 +252,13 @@ async fn main() -> Result<()> {
                     }
 
                     if q.len() >= args.cancel_threshold {
-                        let window_start = q.front().copied().unwrap_or(now);
+                        let window_start = q.front().map_or(now, |(ts, _)| *ts);
                         let alert_id = format!("freq_cancel:{}:{}:{}", ev.maker, window_start, args.cancel_threshold);
                         let alert = AlertEvent {
                             alert_id,
                             rule_id: "freq_cancel".to_string(),
                             severity: "medium".to_string(),
-                            maker: ev.maker.clone(),
+                            maker: ev.maker.to_string(),
                             offer_id: Some(ev.offer_id.clone()),
                             ts_ms: now,
                             details: json!({
@@ -135,11 +266,18 @@ async fn main() -> Result<()> {
                                 "cancel_count": q.len(),
                                 "threshold": args.cancel_threshold
                             }),
+                            withdraws: None,
                         };
-                        emit_alert(&producer, &args.alerts_topic, &mut emitted_alerts, alert).await?;
+                        if emit_alert(producer.as_ref(), &wire, &mut emitted_alerts, alert.clone()).await? {
+                            raised.push(alert);
+                        }
                     }
                 }
 
+                if ev.commitment != "finalized" && !raised.is_empty() {
+                    unsettled.insert(ev.event_id.clone(), (now_ms(), raised));
+                }
+
                 let _ = consumer.commit_message(&msg, CommitMode::Async);
             }
         }
@@ -149,45 +287,122 @@ async fn main() -> Result<()> {
 }
 
 fn large_amount_rule(ev: &NormalizedEvent, threshold: u64) -> Option<AlertEvent> {
-    let a = parse_u64_str(&ev.amount_a).unwrap_or(0);
-    let b = parse_u64_str(&ev.amount_b).unwrap_or(0);
-    if a < threshold && b < threshold {
+    if ev.amount_a.get() < threshold && ev.amount_b.get() < threshold {
         return None;
     }
     Some(AlertEvent {
-        alert_id: format!("large_amount:{}:{}:{}", ev.offer_id, ev.signature, ev.slot),
+        alert_id: format!("large_amount:{}:{}:{}", ev.key(), ev.signature, ev.slot),
         rule_id: "large_amount".to_string(),
         severity: "high".to_string(),
-        maker: ev.maker.clone(),
-        offer_id: Some(ev.offer_id.clone()),
+        maker: ev.maker.to_string(),
+        // an RFQ id is not an offer id
+        offer_id: ev.rfq_id.is_none().then(|| ev.offer_id.clone()),
         ts_ms: now_ms(),
         details: json!({
+            "rfq_id": ev.rfq_id,
             "amount_a": ev.amount_a,
             "amount_b": ev.amount_b,
             "threshold": threshold,
-            "event_type": format!("{:?}", ev.event_type)
+            "event_type": ev.event_type.as_str()
         }),
+        withdraws: None,
     })
 }
 
+/// Drops unsettled entries raised more than `window_ms` before `now`: their follow-up was
+/// lost (e.g. the listener restarted), and without a bound the map only grows. Returns how
+/// many were dropped.
+fn evict_unsettled(
+    unsettled: &mut HashMap<String, (u64, Vec<AlertEvent>)>,
+    now: u64,
+    window_ms: u64,
+) -> usize {
+    let before = unsettled.len();
+    unsettled.retain(|_, (raised_at, _)| now.saturating_sub(*raised_at) <= window_ms);
+    before - unsettled.len()
+}
+
+/// Retraction notice for an alert whose triggering event was on a fork that never finalized.
+fn withdraw_alert(alert: &AlertEvent, event_id: &str) -> AlertEvent {
+    AlertEvent {
+        alert_id: format!("withdraw:{}", alert.alert_id),
+        rule_id: alert.rule_id.clone(),
+        severity: alert.severity.clone(),
+        maker: alert.maker.clone(),
+        offer_id: alert.offer_id.clone(),
+        ts_ms: now_ms(),
+        details: json!({
+            "reason": "event_retracted",
+            "event_id": event_id
+        }),
+        withdraws: Some(alert.alert_id.clone()),
+    }
+}
+
 async fn emit_alert(
-    producer: &FutureProducer,
-    alerts_topic: &str,
+    producer: &dyn Sink,
+    wire: &WireFormat,
     emitted_alerts: &mut HashSet<String>,
     alert: AlertEvent,
-) -> Result<()> {
+) -> Result<bool> {
     if !emitted_alerts.insert(alert.alert_id.clone()) {
-        return Ok(());
+        return Ok(false);
     }
-    let payload = serde_json::to_string(&alert).context("serialize alert")?;
-
-    // key by maker for ordering
-    let record = FutureRecord::to(alerts_topic)
-        .key(&alert.maker)
-        .payload(&payload);
+    let payload = wire.encode_alert(&alert)?;
 
-    let _ = producer.send(record, std::time::Duration::from_secs(5)).await;
+    // key by maker for ordering; undeliverable alerts are spooled, not dropped
+    producer.send(&alert.maker, &payload).await?;
     eprintln!("ALERT: {} rule={}", alert.alert_id, alert.rule_id);
-    Ok(())
+    Ok(true)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orderflow_common::schema;
+
+    #[test]
+    fn alerts_conform_to_the_published_schema() {
+        let ev: NormalizedEvent = serde_json::from_value(json!({
+            "event_id": "4FpiGQPEkuh3GqVkbqievxrpE8w9mZkvVVmh8CksP2GQuedKf7xbmPaXTv31EnPqixdMxZjkvVuemDbBM9qHttoo:0:12",
+            "event_type": "OfferCreated",
+            "cluster": "localnet",
+            "program_id": "FSZLWNceNe6DJRFkw2kvSTsSMjdTeBP2n2iaMfn3Uhxk",
+            "offer_id": "7",
+            "maker": "EeFX3g6HePRn5ipNu5ey3jiYWRVmeK3Frxec2ZwqKs6W",
+            "mint_a": "F8LcnTCz2SD2DmZA4Gw3Q9pVsWHqT7csHbecX5rX7h3S",
+            "mint_b": "2SqwuyEsMcrUtJJkJzfQiCYZwMgBUUxrjDD2dawARH5s",
+            "amount_a": "5000000",
+            "amount_b": "1",
+            "slot": 2000,
+            "signature": "4FpiGQPEkuh3GqVkbqievxrpE8w9mZkvVVmh8CksP2GQuedKf7xbmPaXTv31EnPqixdMxZjkvVuemDbBM9qHttoo",
+            "commitment": "confirmed",
+            "ts_ingest_ms": 0
+        }))
+        .unwrap();
+        let alert = large_amount_rule(&ev, 1_000_000).unwrap();
+        let withdrawn = withdraw_alert(&alert, &ev.event_id);
+        let wire = WireFormat::json(envelope::SCHEMA_VERSION);
+        for alert in [&alert, &withdrawn] {
+            let value = serde_json::from_slice(&wire.encode_alert(alert).unwrap()).unwrap();
+            schema::validate(schema::ALERTS, &value).unwrap_or_else(|e| panic!("{e:#}"));
+        }
+    }
+
+    #[test]
+    fn unsettled_alerts_expire_after_the_finality_window() {
+        let mut unsettled = HashMap::from([
+            ("old".to_string(), (1_000, Vec::new())),
+            ("edge".to_string(), (5_000, Vec::new())),
+            ("new".to_string(), (9_000, Vec::new())),
+        ]);
+        assert_eq!(evict_unsettled(&mut unsettled, 10_000, 5_000), 1);
+        let mut left: Vec<_> = unsettled.keys().map(String::as_str).collect();
+        left.sort_unstable();
+        assert_eq!(left, ["edge", "new"]);
+
+        assert_eq!(evict_unsettled(&mut unsettled, 10_000, 5_000), 0);
+        assert_eq!(evict_unsettled(&mut unsettled, 20_000, 5_000), 2);
+        assert!(unsettled.is_empty());
+    }
+}
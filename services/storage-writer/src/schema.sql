@@ -6,11 +6,34 @@ create table if not exists events (
   slot bigint not null,
   offer_id text not null,
   payload_json jsonb not null,
-  ingested_at timestamptz not null default now()
+  ingested_at timestamptz not null default now(),
+  -- from the transaction, null where the listener could not tell
+  block_time timestamptz,
+  fee_lamports bigint,
+  fee_payer text,
+  compute_units_consumed bigint,
+  priority_fee_lamports bigint,
+  -- confirmed-commitment ingest: set by EventFinalized / EventRetracted follow-ups
+  finalized_at timestamptz,
+  retracted_at timestamptz
 );
 
+alter table events add column if not exists finalized_at timestamptz;
+alter table events add column if not exists retracted_at timestamptz;
+alter table events add column if not exists block_time timestamptz;
+alter table events add column if not exists fee_lamports bigint;
+alter table events add column if not exists fee_payer text;
+alter table events add column if not exists compute_units_consumed bigint;
+alter table events add column if not exists priority_fee_lamports bigint;
+-- RFQ events only; offer_id repeats it for older readers, but offers and RFQs are separate id spaces
+alter table events add column if not exists rfq_id text;
+update events set rfq_id = offer_id
+where rfq_id is null and event_type in ('RfqCreated', 'QuoteSubmitted', 'QuoteAccepted', 'QuoteRefunded');
+
 create index if not exists idx_events_offer_id on events (offer_id);
+create index if not exists idx_events_rfq_id on events (rfq_id) where rfq_id is not null;
 create index if not exists idx_events_slot on events (slot);
+create index if not exists idx_events_block_time on events (block_time);
 
 -- offers: latest snapshot (rebuildable from events)
 create table if not exists offers (
@@ -20,14 +43,96 @@ create table if not exists offers (
   taker text,
   mint_a text not null,
   mint_b text not null,
-  amount_a bigint not null,
-  amount_b bigint not null,
+  amount_a numeric(20,0) not null,
+  amount_b numeric(20,0) not null,
+  client_ref text,
+  vault_amount numeric(20,0),
   created_slot bigint,
   updated_slot bigint not null,
   created_at timestamptz not null default now(),
   updated_at timestamptz not null default now()
 );
 
+-- databases created before these columns existed
+alter table offers add column if not exists client_ref text;
+alter table offers add column if not exists vault_amount numeric(20,0);
+-- token amounts are u64; bigint held them clamped at i64::MAX. The rewrite takes an
+-- ACCESS EXCLUSIVE lock, so it only runs while a bigint column is left.
+do $$
+begin
+  if exists (
+    select 1 from information_schema.columns
+    where table_schema = current_schema() and table_name = 'offers'
+      and column_name in ('amount_a', 'amount_b', 'vault_amount') and data_type = 'bigint'
+  ) then
+    alter table offers
+      alter column amount_a type numeric(20,0),
+      alter column amount_b type numeric(20,0),
+      alter column vault_amount type numeric(20,0);
+  end if;
+end $$;
+
 create index if not exists idx_offers_maker on offers (maker);
 create index if not exists idx_offers_updated_slot on offers (updated_slot);
+create index if not exists idx_offers_client_ref on offers (client_ref);
+
+-- rfqs: latest RFQ snapshot (rfq ids are scoped per requester)
+create table if not exists rfqs (
+  requester text not null,
+  rfq_id text not null,
+  status text not null,
+  mint_a text not null,
+  mint_b text not null,
+  size numeric(20,0) not null,
+  accepted_responder text,
+  created_slot bigint,
+  updated_slot bigint not null,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now(),
+  primary key (requester, rfq_id)
+);
+
+do $$
+begin
+  if exists (
+    select 1 from information_schema.columns
+    where table_schema = current_schema() and table_name = 'rfqs'
+      and column_name in ('size') and data_type = 'bigint'
+  ) then
+    alter table rfqs alter column size type numeric(20,0);
+  end if;
+end $$;
+
+create index if not exists idx_rfqs_updated_slot on rfqs (updated_slot);
+
+-- rfq_quotes: one row per responder quote on an RFQ
+create table if not exists rfq_quotes (
+  requester text not null,
+  rfq_id text not null,
+  responder text not null,
+  status text not null,
+  amount_a numeric(20,0) not null,
+  amount_b numeric(20,0) not null,
+  vault_amount numeric(20,0),
+  created_slot bigint,
+  updated_slot bigint not null,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now(),
+  primary key (requester, rfq_id, responder)
+);
+
+do $$
+begin
+  if exists (
+    select 1 from information_schema.columns
+    where table_schema = current_schema() and table_name = 'rfq_quotes'
+      and column_name in ('amount_a', 'amount_b', 'vault_amount') and data_type = 'bigint'
+  ) then
+    alter table rfq_quotes
+      alter column amount_a type numeric(20,0),
+      alter column amount_b type numeric(20,0),
+      alter column vault_amount type numeric(20,0);
+  end if;
+end $$;
 
+create index if not exists idx_rfq_quotes_responder on rfq_quotes (responder);
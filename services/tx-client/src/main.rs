@@ -1,10 +1,8 @@
 use anyhow::{Context, Result};
-use borsh::BorshSerialize;
 use clap::Parser;
-use sha2::{Digest, Sha256};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::commitment_config::CommitmentConfig;
-use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::instruction::Instruction;
 use solana_sdk::native_token::LAMPORTS_PER_SOL;
 use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
@@ -22,7 +20,11 @@ struct Args {
     program_id: String,
 
     /// Wallet keypair path (will act as maker)
-    #[arg(long, env = "MAKER_KEYPAIR", default_value = "/home/zhejian/.config/solana/id.json")]
+    #[arg(
+        long,
+        env = "MAKER_KEYPAIR",
+        default_value = "/home/zhejian/.config/solana/id.json"
+    )]
     maker_keypair: String,
 
     /// Optional taker keypair; if not provided, a random keypair is generated and airdropped
@@ -38,6 +40,10 @@ struct Args {
     #[arg(long, default_value_t = 2_000)]
     amount_b: u64,
 
+    /// Client order reference stored on the offer (UTF-8, max 32 bytes, zero-padded)
+    #[arg(long, default_value = "")]
+    client_ref: String,
+
     /// cancel | take
     #[arg(long, default_value = "cancel")]
     action: String,
@@ -47,6 +53,13 @@ struct Args {
 async fn main() -> Result<()> {
     let args = Args::parse();
     let program_id: Pubkey = args.program_id.parse().context("parse program_id")?;
+    // Anchor rejects instructions whose program id differs from `declare_id!`
+    anyhow::ensure!(
+        program_id == escrow_client::PROGRAM_ID,
+        "program_id {program_id} does not match the escrow-client build ({})",
+        escrow_client::PROGRAM_ID
+    );
+    let client_ref = client_ref_bytes(&args.client_ref)?;
 
     let rpc = RpcClient::new_with_commitment(args.rpc_url.clone(), CommitmentConfig::confirmed());
 
@@ -66,8 +79,6 @@ async fn main() -> Result<()> {
 
     // Create ATAs
     let maker_ata_a = get_associated_token_address(&maker.pubkey(), &mint_a);
-    let maker_ata_b = get_associated_token_address(&maker.pubkey(), &mint_b);
-    let taker_ata_a = get_associated_token_address(&taker.pubkey(), &mint_a);
     let taker_ata_b = get_associated_token_address(&taker.pubkey(), &mint_b);
 
     create_ata_if_missing(&rpc, &maker, &maker.pubkey(), &mint_a).await?;
@@ -79,76 +90,44 @@ async fn main() -> Result<()> {
     mint_to(&rpc, &maker, &mint_a, &maker_ata_a, args.amount_a).await?;
     mint_to(&rpc, &maker, &mint_b, &taker_ata_b, args.amount_b).await?;
 
-    // Derive escrow PDA + vault ATA (owner = escrow PDA)
-    let (escrow_state, _bump) = Pubkey::find_program_address(
-        &[
-            b"escrow",
-            maker.pubkey().as_ref(),
-            &args.offer_id.to_le_bytes(),
-        ],
-        &program_id,
-    );
-    let vault_ata = get_associated_token_address(&escrow_state, &mint_a);
-
     // 1) create_offer (maker)
-    let ix_create = Instruction {
-        program_id,
-        accounts: vec![
-            AccountMeta::new(maker.pubkey(), true),      // maker
-            AccountMeta::new_readonly(mint_a, false),    // mint_a
-            AccountMeta::new_readonly(mint_b, false),    // mint_b
-            AccountMeta::new(escrow_state, false),       // escrow_state
-            AccountMeta::new(vault_ata, false),          // vault_ata
-            AccountMeta::new(maker_ata_a, false),        // maker_ata_a
-            AccountMeta::new_readonly(spl_token::id(), false),
-            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
-            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
-            AccountMeta::new_readonly(solana_sdk::sysvar::rent::id(), false),
-        ],
-        data: anchor_ix_data("create_offer", &(args.offer_id, args.amount_a, args.amount_b))?,
-    };
+    let ix_create = escrow_client::ix::create_offer(
+        &maker.pubkey(),
+        &mint_a,
+        &mint_b,
+        args.offer_id,
+        args.amount_a,
+        args.amount_b,
+        client_ref,
+    );
     send_tx(&rpc, &[ix_create], &[&maker]).await?;
     eprintln!("sent create_offer offer_id={}", args.offer_id);
 
     if args.action == "take" {
         // maker ATA B is already created above; mint_b to maker not needed.
-        let ix_take = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(taker.pubkey(), true),     // taker
-                AccountMeta::new_readonly(mint_a, false),   // mint_a
-                AccountMeta::new_readonly(mint_b, false),   // mint_b
-                AccountMeta::new(escrow_state, false),      // escrow_state
-                AccountMeta::new(maker.pubkey(), false),    // maker (system account)
-                AccountMeta::new(vault_ata, false),         // vault_ata
-                AccountMeta::new(taker_ata_a, false),       // taker_ata_a
-                AccountMeta::new(taker_ata_b, false),       // taker_ata_b
-                AccountMeta::new(maker_ata_b, false),       // maker_ata_b
-                AccountMeta::new_readonly(spl_token::id(), false),
-                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
-            ],
-            data: anchor_ix_data("take_offer", &())?,
-        };
+        let ix_take = escrow_client::ix::take_offer(
+            &taker.pubkey(),
+            &maker.pubkey(),
+            &mint_a,
+            &mint_b,
+            args.offer_id,
+        );
         send_tx(&rpc, &[ix_take], &[&taker]).await?;
         eprintln!("sent take_offer offer_id={}", args.offer_id);
     } else {
-        let ix_cancel = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(maker.pubkey(), true),     // maker
-                AccountMeta::new_readonly(mint_a, false),   // mint_a
-                AccountMeta::new(escrow_state, false),      // escrow_state
-                AccountMeta::new(vault_ata, false),         // vault_ata
-                AccountMeta::new(maker_ata_a, false),       // maker_ata_a
-                AccountMeta::new_readonly(spl_token::id(), false),
-                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
-            ],
-            data: anchor_ix_data("cancel_offer", &())?,
-        };
+        let ix_cancel = escrow_client::ix::cancel_offer(&maker.pubkey(), &mint_a, args.offer_id);
         send_tx(&rpc, &[ix_cancel], &[&maker]).await?;
         eprintln!("sent cancel_offer offer_id={}", args.offer_id);
     }
 
+    let (escrow_state, _bump) = escrow_client::pda::escrow_state(&maker.pubkey(), args.offer_id);
+    let st = escrow_client::decode_escrow_state(&rpc.get_account_data(&escrow_state).await?)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    eprintln!(
+        "escrow_state status={} filled_slot={} cancelled_slot={}",
+        st.status, st.filled_slot, st.cancelled_slot
+    );
+
     Ok(())
 }
 
@@ -156,28 +135,16 @@ fn read_keypair(path: &str) -> Result<Keypair> {
     read_keypair_file(path).map_err(|e| anyhow::anyhow!(e.to_string()))
 }
 
-fn anchor_discriminator(ix_name: &str) -> [u8; 8] {
-    let preimage = format!("global:{ix_name}");
-    let mut h = Sha256::new();
-    h.update(preimage.as_bytes());
-    let out = h.finalize();
-    let mut disc = [0u8; 8];
-    disc.copy_from_slice(&out[..8]);
-    disc
-}
-
-fn anchor_ix_data<T: BorshSerialize>(ix_name: &str, args: &T) -> Result<Vec<u8>> {
-    let mut data = Vec::with_capacity(8 + 32);
-    data.extend_from_slice(&anchor_discriminator(ix_name));
-    data.extend_from_slice(&args.try_to_vec()?);
-    Ok(data)
+fn client_ref_bytes(s: &str) -> Result<[u8; 32]> {
+    let raw = s.as_bytes();
+    anyhow::ensure!(raw.len() <= 32, "client_ref longer than 32 bytes");
+    let mut out = [0u8; 32];
+    out[..raw.len()].copy_from_slice(raw);
+    Ok(out)
 }
 
 async fn send_tx(rpc: &RpcClient, ixs: &[Instruction], signers: &[&dyn Signer]) -> Result<()> {
-    let fee_payer = signers
-        .first()
-        .context("no signers")?
-        .pubkey();
+    let fee_payer = signers.first().context("no signers")?.pubkey();
     let bh = rpc.get_latest_blockhash().await?;
     let tx = Transaction::new_signed_with_payer(ixs, Some(&fee_payer), signers, bh);
     let sig = rpc.send_and_confirm_transaction(&tx).await?;
@@ -187,11 +154,8 @@ async fn send_tx(rpc: &RpcClient, ixs: &[Instruction], signers: &[&dyn Signer])
 
 async fn maybe_airdrop(rpc: &RpcClient, pubkey: &Pubkey, lamports: u64) -> Result<()> {
     // localnet: airdrop may fail if faucet is disabled; ignore if so.
-    match rpc.request_airdrop(pubkey, lamports).await {
-        Ok(sig) => {
-            let _ = rpc.confirm_transaction(&sig).await;
-        }
-        Err(_) => {}
+    if let Ok(sig) = rpc.request_airdrop(pubkey, lamports).await {
+        let _ = rpc.confirm_transaction(&sig).await;
     }
     Ok(())
 }
@@ -264,4 +228,3 @@ async fn mint_to(
     send_tx(rpc, &[ix], &[mint_authority]).await?;
     Ok(())
 }
-
@@ -1,6 +1,11 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import {
+  BPF_LOADER_UPGRADEABLE_PROGRAM_ID,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+} from "@solana/web3.js";
 import BN from "bn.js";
 import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -30,6 +35,47 @@ function u64LeBytes(n: BN): Buffer {
   return buf;
 }
 
+function clientRef(s: string): number[] {
+  const buf = Buffer.alloc(32);
+  buf.write(s, "utf8");
+  return Array.from(buf);
+}
+
+function denyPda(wallet: PublicKey, programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("deny"), wallet.toBuffer()],
+    programId
+  )[0];
+}
+
+async function ensureConfig(
+  program: Program,
+  admin: PublicKey
+): Promise<PublicKey> {
+  const [config] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+  const existing = await program.provider.connection.getAccountInfo(config);
+  if (existing) return config;
+
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+  await program.methods
+    .initConfig()
+    .accounts({
+      admin,
+      config,
+      program: program.programId,
+      programData,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+  return config;
+}
+
 async function expectThrows(p: Promise<unknown>) {
   let threw = false;
   try {
@@ -101,7 +147,12 @@ describe("escrow", () => {
     const vaultAta = await getAssociatedTokenAddress(mintA, escrowState, true);
 
     await program.methods
-      .createOffer(offerId, amountA, amountB)
+      .createOffer(
+        offerId,
+        amountA,
+        amountB,
+        clientRef(`oms-${offerId.toString()}`)
+      )
       .accounts({
         maker: maker.publicKey,
         mintA,
@@ -109,6 +160,7 @@ describe("escrow", () => {
         escrowState,
         vaultAta,
         makerAtaA,
+        makerDeny: denyPda(maker.publicKey, program.programId),
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -119,6 +171,13 @@ describe("escrow", () => {
     const vaultAfterCreate = await getAccount(provider.connection, vaultAta);
     expect(Number(vaultAfterCreate.amount)).to.eq(Number(amountA.toString()));
 
+    const stateAfterCreate = await (program.account as any).escrowState.fetch(
+      escrowState
+    );
+    expect(stateAfterCreate.clientRef).to.deep.eq(
+      clientRef(`oms-${offerId.toString()}`)
+    );
+
     await program.methods
       .cancelOffer()
       .accounts({
@@ -226,7 +285,12 @@ describe("escrow", () => {
     const vaultAta = await getAssociatedTokenAddress(mintA, escrowState, true);
 
     await program.methods
-      .createOffer(offerId, amountA, amountB)
+      .createOffer(
+        offerId,
+        amountA,
+        amountB,
+        clientRef(`oms-${offerId.toString()}`)
+      )
       .accounts({
         maker: maker.publicKey,
         mintA,
@@ -234,6 +298,7 @@ describe("escrow", () => {
         escrowState,
         vaultAta,
         makerAtaA,
+        makerDeny: denyPda(maker.publicKey, program.programId),
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -259,6 +324,9 @@ describe("escrow", () => {
         takerAtaA,
         takerAtaB,
         makerAtaB,
+        makerAtaA,
+        makerDeny: denyPda(maker.publicKey, program.programId),
+        takerDeny: denyPda(taker.publicKey, program.programId),
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
       })
@@ -328,7 +396,12 @@ describe("escrow", () => {
     const vaultAta = await getAssociatedTokenAddress(mintA, escrowState, true);
 
     await program.methods
-      .createOffer(offerId, amountA, amountB)
+      .createOffer(
+        offerId,
+        amountA,
+        amountB,
+        clientRef(`oms-${offerId.toString()}`)
+      )
       .accounts({
         maker: maker.publicKey,
         mintA,
@@ -336,6 +409,7 @@ describe("escrow", () => {
         escrowState,
         vaultAta,
         makerAtaA,
+        makerDeny: denyPda(maker.publicKey, program.programId),
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -359,5 +433,654 @@ describe("escrow", () => {
         .rpc()
     );
   });
-});
 
+  it("denylist blocks create_offer; force_cancel refunds maker", async () => {
+    const admin = provider.wallet as anchor.Wallet;
+    const config = await ensureConfig(program, admin.publicKey);
+
+    // --- denylisted maker cannot create an offer
+    const flagged = Keypair.generate();
+    await airdropIfNeeded(provider.connection, flagged.publicKey, 2e9);
+    const flaggedDeny = denyPda(flagged.publicKey, program.programId);
+
+    await program.methods
+      .denyWallet(flagged.publicKey)
+      .accounts({
+        admin: admin.publicKey,
+        config,
+        denyEntry: flaggedDeny,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const mintA = await createMint(
+      provider.connection,
+      admin.payer,
+      admin.publicKey,
+      null,
+      0
+    );
+    const mintB = await createMint(
+      provider.connection,
+      admin.payer,
+      admin.publicKey,
+      null,
+      0
+    );
+    const flaggedAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        flagged,
+        mintA,
+        flagged.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      admin.payer,
+      mintA,
+      flaggedAtaA,
+      admin.publicKey,
+      BigInt(1000)
+    );
+
+    const flaggedOfferId = new BN((Date.now() + 3).toString());
+    const [flaggedEscrow] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("escrow"),
+        flagged.publicKey.toBuffer(),
+        u64LeBytes(flaggedOfferId),
+      ],
+      program.programId
+    );
+    const flaggedVault = await getAssociatedTokenAddress(
+      mintA,
+      flaggedEscrow,
+      true
+    );
+    await expectThrows(
+      program.methods
+        .createOffer(
+          flaggedOfferId,
+          new BN("1000"),
+          new BN("2000"),
+          clientRef("flagged")
+        )
+        .accounts({
+          maker: flagged.publicKey,
+          mintA,
+          mintB,
+          escrowState: flaggedEscrow,
+          vaultAta: flaggedVault,
+          makerAtaA: flaggedAtaA,
+          makerDeny: flaggedDeny,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([flagged])
+        .rpc()
+    );
+
+    await program.methods
+      .allowWallet(flagged.publicKey)
+      .accounts({ admin: admin.publicKey, config, denyEntry: flaggedDeny })
+      .rpc();
+
+    // --- admin force-cancels a live offer back to the maker
+    const offerId = new BN((Date.now() + 4).toString());
+    const amountA = new BN("1000");
+    const makerAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        admin.payer,
+        mintA,
+        admin.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      admin.payer,
+      mintA,
+      makerAtaA,
+      admin.publicKey,
+      BigInt(amountA.toString())
+    );
+
+    const [escrowState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), admin.publicKey.toBuffer(), u64LeBytes(offerId)],
+      program.programId
+    );
+    const vaultAta = await getAssociatedTokenAddress(mintA, escrowState, true);
+
+    await program.methods
+      .createOffer(offerId, amountA, new BN("2000"), clientRef("forced"))
+      .accounts({
+        maker: admin.publicKey,
+        mintA,
+        mintB,
+        escrowState,
+        vaultAta,
+        makerAtaA,
+        makerDeny: denyPda(admin.publicKey, program.programId),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    await program.methods
+      .forceCancel()
+      .accounts({
+        admin: admin.publicKey,
+        config,
+        mintA,
+        escrowState,
+        maker: admin.publicKey,
+        vaultAta,
+        makerAtaA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const makerAAfter = await getAccount(provider.connection, makerAtaA);
+    expect(Number(makerAAfter.amount)).to.eq(Number(amountA.toString()));
+  });
+
+  it("v1 offer: migrate_offer, then force_cancel refunds the maker", async () => {
+    // preloaded from tests/fixtures (Anchor.toml): an open offer written before client_ref
+    const escrowState = new PublicKey("4n89TbG4QdCHPfiZyZzjmEeBVvi2y9BuZRcaRaVdLAK4");
+    const maker = new PublicKey("3nBQK9ou2RXvnrAjcLXxqPzhmZJ4HJTNyPo3b7wW8gYF");
+    const mintA = new PublicKey("7v1mzq5hQnTfVYt6UPb4EYGkDHAbm3sdW8fZtFqDMRmu");
+    const vaultAta = new PublicKey("6UNNVvxX4pABUWwaLRFc1s7HNEKwWaY5EYxc7akBUuaU");
+    const admin = provider.wallet as anchor.Wallet;
+    const config = await ensureConfig(program, admin.publicKey);
+    await airdropIfNeeded(provider.connection, maker, 1e9);
+
+    const before = await provider.connection.getAccountInfo(escrowState);
+    expect(before!.data.length).to.eq(192);
+    expect(before!.data[8]).to.eq(1);
+
+    await program.methods
+      .migrateOffer()
+      .accounts({
+        payer: admin.publicKey,
+        escrowState,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const st = await (program.account as any).escrowState.fetch(escrowState);
+    expect(st.version).to.eq(2);
+    expect(st.offerId.toString()).to.eq("1");
+    expect(st.clientRef).to.deep.eq(new Array(32).fill(0));
+    // a second migration is refused
+    await expectThrows(
+      program.methods
+        .migrateOffer()
+        .accounts({
+          payer: admin.publicKey,
+          escrowState,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc()
+    );
+
+    const makerAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        admin.payer,
+        mintA,
+        maker
+      )
+    ).address;
+    await program.methods
+      .forceCancel()
+      .accounts({
+        admin: admin.publicKey,
+        config,
+        mintA,
+        escrowState,
+        maker,
+        vaultAta,
+        makerAtaA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const makerAAfter = await getAccount(provider.connection, makerAtaA);
+    expect(Number(makerAAfter.amount)).to.eq(1000);
+  });
+
+  it("take_offer sweeps a donated vault surplus back to the maker", async () => {
+    const maker = provider.wallet as anchor.Wallet;
+    const taker = Keypair.generate();
+    await airdropIfNeeded(provider.connection, taker.publicKey, 2e9);
+
+    const offerId = new BN((Date.now() + 5).toString());
+    const amountA = new BN("1000");
+    const amountB = new BN("2000");
+    const donation = BigInt(500);
+
+    const mintA = await createMint(
+      provider.connection,
+      maker.payer,
+      maker.publicKey,
+      null,
+      0
+    );
+    const mintB = await createMint(
+      provider.connection,
+      maker.payer,
+      maker.publicKey,
+      null,
+      0
+    );
+
+    const makerAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        maker.payer,
+        mintA,
+        maker.publicKey
+      )
+    ).address;
+    const makerAtaB = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        maker.payer,
+        mintB,
+        maker.publicKey
+      )
+    ).address;
+    const takerAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        taker,
+        mintA,
+        taker.publicKey
+      )
+    ).address;
+    const takerAtaB = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        taker,
+        mintB,
+        taker.publicKey
+      )
+    ).address;
+
+    await mintTo(
+      provider.connection,
+      maker.payer,
+      mintA,
+      makerAtaA,
+      maker.publicKey,
+      BigInt(amountA.toString())
+    );
+    await mintTo(
+      provider.connection,
+      maker.payer,
+      mintB,
+      takerAtaB,
+      maker.publicKey,
+      BigInt(amountB.toString())
+    );
+
+    const [escrowState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), maker.publicKey.toBuffer(), u64LeBytes(offerId)],
+      program.programId
+    );
+    const vaultAta = await getAssociatedTokenAddress(mintA, escrowState, true);
+
+    await program.methods
+      .createOffer(offerId, amountA, amountB, clientRef("surplus"))
+      .accounts({
+        maker: maker.publicKey,
+        mintA,
+        mintB,
+        escrowState,
+        vaultAta,
+        makerAtaA,
+        makerDeny: denyPda(maker.publicKey, program.programId),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    // someone donates extra token A straight into the vault
+    await mintTo(
+      provider.connection,
+      maker.payer,
+      mintA,
+      vaultAta,
+      maker.publicKey,
+      donation
+    );
+
+    await program.methods
+      .takeOffer()
+      .accounts({
+        taker: taker.publicKey,
+        mintA,
+        mintB,
+        escrowState,
+        maker: maker.publicKey,
+        vaultAta,
+        takerAtaA,
+        takerAtaB,
+        makerAtaB,
+        makerAtaA,
+        makerDeny: denyPda(maker.publicKey, program.programId),
+        takerDeny: denyPda(taker.publicKey, program.programId),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .signers([taker])
+      .rpc();
+
+    const takerAAfter = await getAccount(provider.connection, takerAtaA);
+    const makerAAfter = await getAccount(provider.connection, makerAtaA);
+    expect(Number(takerAAfter.amount)).to.eq(Number(amountA.toString()));
+    expect(Number(makerAAfter.amount)).to.eq(Number(donation));
+  });
+
+  it("rfq: accept one quote, refund the other", async () => {
+    const requester = provider.wallet as anchor.Wallet;
+    const responders = [Keypair.generate(), Keypair.generate()];
+    for (const r of responders) {
+      await airdropIfNeeded(provider.connection, r.publicKey, 2e9);
+    }
+
+    const rfqId = new BN((Date.now() + 6).toString());
+    const size = new BN("1000");
+    const prices = [new BN("2100"), new BN("1900")];
+
+    const mintA = await createMint(
+      provider.connection,
+      requester.payer,
+      requester.publicKey,
+      null,
+      0
+    );
+    const mintB = await createMint(
+      provider.connection,
+      requester.payer,
+      requester.publicKey,
+      null,
+      0
+    );
+
+    const requesterAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        requester.payer,
+        mintA,
+        requester.publicKey
+      )
+    ).address;
+    const requesterAtaB = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        requester.payer,
+        mintB,
+        requester.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      requester.payer,
+      mintB,
+      requesterAtaB,
+      requester.publicKey,
+      BigInt(10_000)
+    );
+
+    const [rfq] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("rfq"),
+        requester.publicKey.toBuffer(),
+        u64LeBytes(rfqId),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .createRfq(rfqId, size)
+      .accounts({
+        requester: requester.publicKey,
+        mintA,
+        mintB,
+        rfq,
+        requesterDeny: denyPda(requester.publicKey, program.programId),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const quotes: PublicKey[] = [];
+    const ataA: PublicKey[] = [];
+    const ataB: PublicKey[] = [];
+    for (const [i, r] of responders.entries()) {
+      const rAtaA = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          r,
+          mintA,
+          r.publicKey
+        )
+      ).address;
+      const rAtaB = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          r,
+          mintB,
+          r.publicKey
+        )
+      ).address;
+      await mintTo(
+        provider.connection,
+        requester.payer,
+        mintA,
+        rAtaA,
+        requester.publicKey,
+        BigInt(size.toString())
+      );
+
+      const [quote] = PublicKey.findProgramAddressSync(
+        [Buffer.from("quote"), rfq.toBuffer(), r.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .submitQuote(prices[i])
+        .accounts({
+          responder: r.publicKey,
+          rfq,
+          mintA,
+          quote,
+          quoteVault: await getAssociatedTokenAddress(mintA, quote, true),
+          responderAtaA: rAtaA,
+          responderDeny: denyPda(r.publicKey, program.programId),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([r])
+        .rpc();
+
+      quotes.push(quote);
+      ataA.push(rAtaA);
+      ataB.push(rAtaB);
+    }
+
+    // take the cheaper quote (index 1); refund index 0 via remaining accounts
+    const [winner, loser] = [1, 0];
+    await program.methods
+      .acceptQuote()
+      .accounts({
+        requester: requester.publicKey,
+        rfq,
+        mintA,
+        mintB,
+        quote: quotes[winner],
+        responder: responders[winner].publicKey,
+        quoteVault: await getAssociatedTokenAddress(
+          mintA,
+          quotes[winner],
+          true
+        ),
+        requesterAtaA,
+        requesterAtaB,
+        responderAtaA: ataA[winner],
+        responderAtaB: ataB[winner],
+        requesterDeny: denyPda(requester.publicKey, program.programId),
+        responderDeny: denyPda(responders[winner].publicKey, program.programId),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: quotes[loser], isWritable: true, isSigner: false },
+        {
+          pubkey: await getAssociatedTokenAddress(mintA, quotes[loser], true),
+          isWritable: true,
+          isSigner: false,
+        },
+        {
+          pubkey: responders[loser].publicKey,
+          isWritable: true,
+          isSigner: false,
+        },
+        { pubkey: ataA[loser], isWritable: true, isSigner: false },
+      ])
+      .rpc();
+
+    const requesterAAfter = await getAccount(provider.connection, requesterAtaA);
+    const winnerBAfter = await getAccount(provider.connection, ataB[winner]);
+    const loserAAfter = await getAccount(provider.connection, ataA[loser]);
+    expect(Number(requesterAAfter.amount)).to.eq(Number(size.toString()));
+    expect(Number(winnerBAfter.amount)).to.eq(
+      Number(prices[winner].toString())
+    );
+    expect(Number(loserAAfter.amount)).to.eq(Number(size.toString()));
+  });
+
+  it("rfq: cancel_rfq refunds the open quote and closes the rfq", async () => {
+    const requester = provider.wallet as anchor.Wallet;
+    const responder = Keypair.generate();
+    await airdropIfNeeded(provider.connection, responder.publicKey, 2e9);
+
+    const rfqId = new BN((Date.now() + 7).toString());
+    const size = new BN("500");
+
+    const mintA = await createMint(
+      provider.connection,
+      requester.payer,
+      requester.publicKey,
+      null,
+      0
+    );
+    const mintB = await createMint(
+      provider.connection,
+      requester.payer,
+      requester.publicKey,
+      null,
+      0
+    );
+
+    const [rfq] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("rfq"),
+        requester.publicKey.toBuffer(),
+        u64LeBytes(rfqId),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .createRfq(rfqId, size)
+      .accounts({
+        requester: requester.publicKey,
+        mintA,
+        mintB,
+        rfq,
+        requesterDeny: denyPda(requester.publicKey, program.programId),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const responderAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        responder,
+        mintA,
+        responder.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      requester.payer,
+      mintA,
+      responderAtaA,
+      requester.publicKey,
+      BigInt(size.toString())
+    );
+
+    const [quote] = PublicKey.findProgramAddressSync(
+      [Buffer.from("quote"), rfq.toBuffer(), responder.publicKey.toBuffer()],
+      program.programId
+    );
+    const quoteVault = await getAssociatedTokenAddress(mintA, quote, true);
+    await program.methods
+      .submitQuote(new BN("800"))
+      .accounts({
+        responder: responder.publicKey,
+        rfq,
+        mintA,
+        quote,
+        quoteVault,
+        responderAtaA,
+        responderDeny: denyPda(responder.publicKey, program.programId),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([responder])
+      .rpc();
+
+    const cancelAccounts = {
+      requester: requester.publicKey,
+      rfq,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    // the open quote would be stranded, so cancelling without it fails
+    await expectThrows(
+      program.methods.cancelRfq().accounts(cancelAccounts).rpc()
+    );
+
+    await program.methods
+      .cancelRfq()
+      .accounts(cancelAccounts)
+      .remainingAccounts([
+        { pubkey: quote, isWritable: true, isSigner: false },
+        { pubkey: quoteVault, isWritable: true, isSigner: false },
+        { pubkey: responder.publicKey, isWritable: true, isSigner: false },
+        { pubkey: responderAtaA, isWritable: true, isSigner: false },
+      ])
+      .rpc();
+
+    const responderAAfter = await getAccount(
+      provider.connection,
+      responderAtaA
+    );
+    expect(Number(responderAAfter.amount)).to.eq(Number(size.toString()));
+    expect(await provider.connection.getAccountInfo(rfq)).to.eq(null);
+  });
+});